@@ -0,0 +1,4 @@
+ALTER TABLE jobs
+    ADD COLUMN credentials_profile   TEXT;
+ALTER TABLE tasks
+    ADD COLUMN credentials_profile   TEXT;
@@ -0,0 +1,4 @@
+ALTER TABLE jobs
+    ADD COLUMN proxy   TEXT;
+ALTER TABLE tasks
+    ADD COLUMN proxy   TEXT;
@@ -0,0 +1,13 @@
+CREATE TABLE webhook_deliveries
+(
+    delivery_id     BLOB NOT NULL PRIMARY KEY,
+    url             TEXT NOT NULL,
+    event           TEXT NOT NULL,
+    payload         TEXT NOT NULL,
+    status          TEXT NOT NULL,
+    num_attempts    INTEGER DEFAULT 0 NOT NULL,
+    created_at      DATETIME NOT NULL,
+    next_attempt_at DATETIME NOT NULL,
+    delivered_at    DATETIME,
+    last_error      TEXT
+);
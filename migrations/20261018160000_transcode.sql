@@ -0,0 +1,8 @@
+ALTER TABLE jobs
+    ADD COLUMN transcode_preset TEXT;
+ALTER TABLE tasks
+    ADD COLUMN transcode_preset TEXT;
+ALTER TABLE tasks
+    ADD COLUMN source_path      TEXT;
+ALTER TABLE tasks
+    ADD COLUMN output_path      TEXT;
@@ -0,0 +1,19 @@
+ALTER TABLE tasks
+    ADD COLUMN depends_on BLOB;
+
+-- Downloads come from playlist fetched by the job's first task
+UPDATE tasks
+SET depends_on = (SELECT fetch.task_id
+                  FROM tasks AS fetch
+                  WHERE fetch.owner_job_id = tasks.owner_job_id
+                    AND fetch.kind = 'FetchUrlContents')
+WHERE kind = 'DownloadAndConvert';
+
+-- Transcoding takes file produced by a download
+UPDATE tasks
+SET depends_on = (SELECT download.task_id
+                  FROM tasks AS download
+                  WHERE download.owner_job_id = tasks.owner_job_id
+                    AND download.kind = 'DownloadAndConvert'
+                    AND download.output_path = tasks.source_path)
+WHERE kind = 'Transcode';
@@ -0,0 +1,14 @@
+CREATE TABLE task_artifacts
+(
+    task_id BLOB NOT NULL REFERENCES tasks (task_id) ON DELETE CASCADE,
+    kind    TEXT NOT NULL,
+    path    TEXT NOT NULL
+);
+
+CREATE INDEX task_artifacts_task_id ON task_artifacts (task_id);
+
+-- Files downloaded so far had no sidecars
+INSERT INTO task_artifacts (task_id, kind, path)
+SELECT task_id, 'Media', output_path
+FROM tasks
+WHERE output_path IS NOT NULL;
@@ -0,0 +1,3 @@
+-- Set for tasks paused because the server was shutting down
+ALTER TABLE tasks
+    ADD COLUMN interrupted BOOLEAN DEFAULT false NOT NULL;
@@ -0,0 +1,5 @@
+-- Why the task has failed last time, parsed from worker's stderr
+ALTER TABLE tasks
+    ADD COLUMN failure_kind TEXT;
+ALTER TABLE tasks
+    ADD COLUMN failure_message TEXT;
@@ -0,0 +1,5 @@
+-- Kept for historical statistics
+ALTER TABLE tasks
+    ADD COLUMN uploader TEXT;
+ALTER TABLE tasks
+    ADD COLUMN output_size INTEGER;
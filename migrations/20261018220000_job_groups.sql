@@ -0,0 +1,3 @@
+-- Jobs made from an imported list of URLs, one task per URL
+ALTER TABLE jobs
+    ADD COLUMN is_group BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1,3 @@
+-- Playlist file written to the output folder once the job is done
+ALTER TABLE jobs
+    ADD COLUMN playlist_path TEXT;
@@ -0,0 +1,25 @@
+-- Jobs in preview mode only queue entries which were picked
+-- by hand or by the rules below
+ALTER TABLE jobs
+    ADD COLUMN preview BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE jobs
+    ADD COLUMN select_first INTEGER;
+ALTER TABLE jobs
+    ADD COLUMN select_newest INTEGER;
+ALTER TABLE jobs
+    ADD COLUMN select_title TEXT;
+
+CREATE TABLE playlist_entries
+(
+    job_id         BLOB NOT NULL REFERENCES jobs (job_id) ON DELETE CASCADE,
+    entry_index    INTEGER NOT NULL,
+    url            TEXT NOT NULL,
+    title          TEXT NOT NULL,
+    thumbnail      TEXT,
+    duration       REAL,
+    uploader       TEXT,
+    bytes_estimate INTEGER,
+    published_at   DATETIME,
+    selected       BOOLEAN DEFAULT false NOT NULL,
+    PRIMARY KEY (job_id, entry_index)
+);
@@ -0,0 +1,31 @@
+-- Everything yt-dlp told us about a video besides what tasks already keep.
+-- Chapters and tags are JSON arrays.
+CREATE TABLE media_metadata
+(
+    task_id      BLOB PRIMARY KEY NOT NULL REFERENCES tasks (task_id) ON DELETE CASCADE,
+    extractor    TEXT,
+    duration     REAL,
+    channel      TEXT,
+    published_at DATETIME,
+    view_count   INTEGER,
+    like_count   INTEGER,
+    description  TEXT,
+    chapters     TEXT,
+    tags         TEXT
+);
+
+-- Same for entries of preview jobs, to carry it over once they're picked
+ALTER TABLE playlist_entries
+    ADD COLUMN extractor TEXT;
+ALTER TABLE playlist_entries
+    ADD COLUMN channel TEXT;
+ALTER TABLE playlist_entries
+    ADD COLUMN view_count INTEGER;
+ALTER TABLE playlist_entries
+    ADD COLUMN like_count INTEGER;
+ALTER TABLE playlist_entries
+    ADD COLUMN description TEXT;
+ALTER TABLE playlist_entries
+    ADD COLUMN chapters TEXT;
+ALTER TABLE playlist_entries
+    ADD COLUMN tags TEXT;
@@ -0,0 +1,7 @@
+-- Metrics that can't be recomputed from tasks, saved as JSON
+-- so they carry on after restart.
+CREATE TABLE server_metrics
+(
+    name  TEXT PRIMARY KEY NOT NULL,
+    value TEXT NOT NULL
+);
@@ -0,0 +1,15 @@
+-- Outcomes of downloads rolled up by hour as they finish, kept for
+-- statistics after their tasks are gone. Unknown uploader is ''.
+CREATE TABLE history_rollup
+(
+    hour                DATETIME NOT NULL,
+    format              TEXT     NOT NULL,
+    host                TEXT     NOT NULL,
+    uploader            TEXT     NOT NULL,
+    num_done            INTEGER  NOT NULL DEFAULT 0,
+    num_failed          INTEGER  NOT NULL DEFAULT 0,
+    bytes               INTEGER  NOT NULL DEFAULT 0,
+    total_duration_secs REAL     NOT NULL DEFAULT 0,
+    num_timed           INTEGER  NOT NULL DEFAULT 0,
+    PRIMARY KEY (hour, format, host, uploader)
+);
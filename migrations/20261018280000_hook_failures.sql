@@ -0,0 +1,3 @@
+-- Post-download hooks which failed on the last run, as a JSON array
+ALTER TABLE tasks
+    ADD COLUMN hook_failures TEXT;
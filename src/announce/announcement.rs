@@ -0,0 +1,116 @@
+use crate::job::{Job, JobEvent};
+use crate::job_manager::PauseReason;
+use crate::task::Task;
+use rocket::serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum AnnounceEvent {
+    JobDone,
+    JobPartiallyDone,
+    JobFailed,
+    JobPaused,
+    JobCancelled,
+    FetchFailed,
+    PlaylistEmpty,
+    QueuePaused,
+    TaskHeldBack,
+}
+
+impl From<JobEvent> for AnnounceEvent {
+    fn from(value: JobEvent) -> Self {
+        match value {
+            JobEvent::JobDone => AnnounceEvent::JobDone,
+            JobEvent::JobPartiallyDone => AnnounceEvent::JobPartiallyDone,
+            JobEvent::JobFailed => AnnounceEvent::JobFailed,
+            JobEvent::FetchFailed => AnnounceEvent::FetchFailed,
+            JobEvent::PlaylistEmpty => AnnounceEvent::PlaylistEmpty,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageTemplate {
+    pub summary: String,
+    pub body: String,
+}
+
+impl MessageTemplate {
+    fn new(summary: &str, body: &str) -> Self {
+        Self {
+            summary: summary.to_string(),
+            body: body.to_string(),
+        }
+    }
+
+    pub fn default_for(event: AnnounceEvent) -> Self {
+        match event {
+            AnnounceEvent::JobDone => Self::new("YouTube download complete", "{{title}}"),
+            AnnounceEvent::JobPartiallyDone => {
+                Self::new("YouTube download partially complete", "{{title}}")
+            }
+            AnnounceEvent::JobFailed => Self::new("YouTube download failed", "{{title}}"),
+            AnnounceEvent::JobPaused => Self::new("YouTube download paused", "{{title}}"),
+            AnnounceEvent::JobCancelled => Self::new("YouTube download cancelled", "{{title}}"),
+            AnnounceEvent::FetchFailed => Self::new("Failed to fetch video data", "{{url}}"),
+            AnnounceEvent::PlaylistEmpty => Self::new("No videos available.", "{{url}}"),
+            AnnounceEvent::QueuePaused => Self::new(
+                "YouTube downloads paused",
+                "Downloads paused due to {{reason}}",
+            ),
+            AnnounceEvent::TaskHeldBack => Self::new(
+                "YouTube download held back",
+                "{{title}} can't start due to {{reason}}",
+            ),
+        }
+    }
+}
+
+// Values available to message templates.
+#[derive(Debug, Clone, Serialize)]
+pub struct AnnouncementData {
+    pub event: AnnounceEvent,
+    pub job_id: Option<Uuid>,
+    pub title: String,
+    pub url: String,
+    pub reason: String,
+}
+
+impl AnnouncementData {
+    pub fn from_job(event: AnnounceEvent, job: &Job) -> Self {
+        Self {
+            event,
+            job_id: Some(job.job_id),
+            title: job.title.clone(),
+            url: job.url.clone(),
+            reason: String::new(),
+        }
+    }
+
+    pub fn from_held_back_task(task: &Task, reason: &PauseReason) -> Self {
+        Self {
+            event: AnnounceEvent::TaskHeldBack,
+            job_id: Some(task.owner_job_id),
+            title: task.title.clone(),
+            url: task.url.clone(),
+            reason: reason.to_string(),
+        }
+    }
+
+    pub fn from_pause_reason(reason: &PauseReason) -> Self {
+        Self {
+            event: AnnounceEvent::QueuePaused,
+            job_id: None,
+            title: String::new(),
+            url: String::new(),
+            reason: reason.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Announcement {
+    pub event: AnnounceEvent,
+    pub summary: String,
+    pub body: String,
+}
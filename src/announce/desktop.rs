@@ -0,0 +1,24 @@
+use crate::announce::{Announcement, Notifier};
+use notify_rust::Notification;
+use rocket::async_trait;
+
+#[derive(Debug)]
+pub struct DesktopNotifier;
+
+#[async_trait]
+impl Notifier for DesktopNotifier {
+    fn name(&self) -> &'static str {
+        "desktop"
+    }
+
+    async fn notify(&self, announcement: &Announcement) -> anyhow::Result<()> {
+        let summary = announcement.summary.clone();
+        let body = announcement.body.clone();
+        // May fail when there's no desktop session, e.g. when running as a service
+        tokio::task::spawn_blocking(move || {
+            Notification::new().summary(&summary).body(&body).show()?;
+            anyhow::Ok(())
+        })
+        .await?
+    }
+}
@@ -0,0 +1,64 @@
+use crate::announce::{Announcement, EmailConfig, Notifier, SmtpSecurity};
+use lettre::message::header::ContentType;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use rocket::async_trait;
+
+#[derive(Debug)]
+pub struct EmailNotifier {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+    to: Vec<Mailbox>,
+}
+
+impl EmailNotifier {
+    pub fn new(config: &EmailConfig) -> anyhow::Result<Self> {
+        type Transport = AsyncSmtpTransport<Tokio1Executor>;
+        let mut builder = match config.security {
+            SmtpSecurity::None => Transport::builder_dangerous(&config.smtp_host),
+            SmtpSecurity::StartTls => Transport::starttls_relay(&config.smtp_host)?,
+            SmtpSecurity::Tls => Transport::relay(&config.smtp_host)?,
+        };
+        if let Some(port) = config.smtp_port {
+            builder = builder.port(port);
+        }
+        if let Some(username) = &config.username {
+            let password = config
+                .password
+                .as_ref()
+                .map(|x| x.expose().to_string())
+                .unwrap_or_default();
+            builder = builder.credentials(Credentials::new(username.clone(), password));
+        }
+        Ok(Self {
+            transport: builder.build(),
+            from: config.from.parse()?,
+            to: config
+                .to
+                .iter()
+                .map(|x| x.parse())
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+#[async_trait]
+impl Notifier for EmailNotifier {
+    fn name(&self) -> &'static str {
+        "email"
+    }
+
+    async fn notify(&self, announcement: &Announcement) -> anyhow::Result<()> {
+        let mut message = Message::builder().from(self.from.clone());
+        for to in &self.to {
+            message = message.to(to.clone());
+        }
+        let message = message
+            .subject(&announcement.summary)
+            .header(ContentType::TEXT_PLAIN)
+            .body(announcement.body.clone())?;
+        self.transport.send(message).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,88 @@
+use crate::announce::{Announcement, GotifyConfig, Notifier, NtfyConfig};
+use rocket::async_trait;
+use serde_json::json;
+use std::time::Duration;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug)]
+pub struct NtfyNotifier {
+    client: reqwest::Client,
+    config: NtfyConfig,
+}
+
+impl NtfyNotifier {
+    pub fn new(config: NtfyConfig) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            config,
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for NtfyNotifier {
+    fn name(&self) -> &'static str {
+        "ntfy"
+    }
+
+    async fn notify(&self, announcement: &Announcement) -> anyhow::Result<()> {
+        // Title goes into query rather than header, so it may contain any characters
+        let mut request = self
+            .client
+            .post(&self.config.url)
+            .timeout(REQUEST_TIMEOUT)
+            .query(&[("title", &announcement.summary)]);
+        if let Some(priority) = self.config.priority {
+            request = request.query(&[("priority", priority)]);
+        }
+        if let Some(token) = &self.config.token {
+            request = request.bearer_auth(token.expose());
+        }
+        request
+            .body(announcement.body.clone())
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct GotifyNotifier {
+    client: reqwest::Client,
+    config: GotifyConfig,
+}
+
+impl GotifyNotifier {
+    pub fn new(config: GotifyConfig) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            config,
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for GotifyNotifier {
+    fn name(&self) -> &'static str {
+        "Gotify"
+    }
+
+    async fn notify(&self, announcement: &Announcement) -> anyhow::Result<()> {
+        let url = format!("{}/message", self.config.url.trim_end_matches('/'));
+        self.client
+            .post(url)
+            .timeout(REQUEST_TIMEOUT)
+            .header("X-Gotify-Key", self.config.token.expose())
+            .json(&json!({
+                "title": announcement.summary,
+                "message": announcement.body,
+                "priority": self.config.priority.unwrap_or(5),
+            }))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,21 @@
+use crate::announce::{Announcement, Notifier};
+use rocket::async_trait;
+use tracing::info;
+
+#[derive(Debug)]
+pub struct LogNotifier;
+
+#[async_trait]
+impl Notifier for LogNotifier {
+    fn name(&self) -> &'static str {
+        "log"
+    }
+
+    async fn notify(&self, announcement: &Announcement) -> anyhow::Result<()> {
+        info!(
+            "Announcement {:?}: {}: {}",
+            announcement.event, announcement.summary, announcement.body
+        );
+        Ok(())
+    }
+}
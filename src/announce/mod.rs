@@ -0,0 +1,161 @@
+use crate::database::ServerDatabase;
+use crate::env::Config;
+use crate::job::{Job, JobEvent, JobStatus};
+use crate::job_manager::PauseReason;
+use crate::task::{Task, TaskKind, TaskResult, TaskStatus};
+use crate::webhooks::WebhookSystem;
+pub use announcement::{AnnounceEvent, Announcement, AnnouncementData, MessageTemplate};
+use handlebars::Handlebars;
+use notifier::create_notifier;
+pub use notifier::Notifier;
+pub use notifier_config::{
+    EmailConfig, GotifyConfig, NotifierBackend, NotifierConfig, NtfyConfig, SmtpSecurity,
+};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::warn;
+use uuid::Uuid;
+
+mod announcement;
+mod desktop;
+mod email;
+mod http_push;
+mod log_only;
+mod notifier;
+mod notifier_config;
+
+#[derive(Debug)]
+pub struct AnnounceSystem {
+    enabled: bool,
+    db: Arc<Mutex<ServerDatabase>>,
+    webhooks: Arc<WebhookSystem>,
+    notifiers: Vec<(NotifierConfig, Arc<dyn Notifier>)>,
+    handlebars: Handlebars<'static>,
+}
+
+impl AnnounceSystem {
+    pub fn new(
+        config: &Config,
+        db: Arc<Mutex<ServerDatabase>>,
+        webhooks: Arc<WebhookSystem>,
+    ) -> anyhow::Result<Self> {
+        let notifiers = config
+            .notifiers
+            .iter()
+            .filter(|x| x.enabled)
+            .map(|x| Ok((x.clone(), create_notifier(&x.backend)?)))
+            .collect::<anyhow::Result<_>>()?;
+
+        let mut handlebars = Handlebars::new();
+        handlebars.register_escape_fn(handlebars::no_escape);
+
+        Ok(Self {
+            enabled: config.show_announcements,
+            db,
+            webhooks,
+            notifiers,
+            handlebars,
+        })
+    }
+
+    pub async fn on_task_result(&self, task_result: &TaskResult) -> anyhow::Result<()> {
+        let job_id = task_result.task.owner_job_id;
+        // Nested playlists don't fail the job, but may be the last thing it waits for
+        let is_nested_fetch = task_result.task.depends_on.is_some();
+        if task_result.task.kind != TaskKind::FetchUrlContents || is_nested_fetch {
+            if self.check_needs_completion_announcement(job_id).await? {
+                let job: Job = self.db.lock().await.get_job(job_id).await?;
+                if let Some(event) = JobEvent::from_finished_job_status(job.status) {
+                    self.webhooks.on_job_event(event, &job).await?;
+                }
+                if let Some(event) = Self::get_completion_event(job.status) {
+                    self.announce(AnnouncementData::from_job(event, &job));
+                }
+            }
+        } else if task_result.status == TaskStatus::Failed {
+            let job: Job = self.db.lock().await.get_job(job_id).await?;
+            self.webhooks
+                .on_job_event(JobEvent::FetchFailed, &job)
+                .await?;
+            self.announce(AnnouncementData::from_job(AnnounceEvent::FetchFailed, &job));
+        }
+
+        Ok(())
+    }
+
+    async fn check_needs_completion_announcement(&self, job_id: Uuid) -> anyhow::Result<bool> {
+        let stats = self.db.lock().await.get_job_task_stats(job_id).await?;
+        Ok(stats.num_total > 1 && stats.num_waiting == 0 && stats.num_active == 0)
+    }
+
+    fn get_completion_event(status: JobStatus) -> Option<AnnounceEvent> {
+        match status {
+            JobStatus::Done => Some(AnnounceEvent::JobDone),
+            JobStatus::PartiallyDone => Some(AnnounceEvent::JobPartiallyDone),
+            JobStatus::Paused => Some(AnnounceEvent::JobPaused),
+            JobStatus::Failed => Some(AnnounceEvent::JobFailed),
+            JobStatus::Cancelled => Some(AnnounceEvent::JobCancelled),
+            _ => None,
+        }
+    }
+
+    fn render_announcement(
+        &self,
+        config: &NotifierConfig,
+        data: &AnnouncementData,
+    ) -> anyhow::Result<Announcement> {
+        let message = config.get_message(data.event);
+        Ok(Announcement {
+            event: data.event,
+            summary: self.handlebars.render_template(&message.summary, data)?,
+            body: self.handlebars.render_template(&message.body, data)?,
+        })
+    }
+
+    // Notifications are sent in background, failing to deliver them only gets logged.
+    fn announce(&self, data: AnnouncementData) {
+        if !self.enabled {
+            return;
+        }
+        for (config, notifier) in &self.notifiers {
+            if !config.accepts(data.event) {
+                continue;
+            }
+            let announcement = match self.render_announcement(config, &data) {
+                Ok(announcement) => announcement,
+                Err(e) => {
+                    warn!("Failed to render {} announcement: {e}", notifier.name());
+                    continue;
+                }
+            };
+            let notifier = notifier.clone();
+            tokio::task::spawn(async move {
+                if let Err(e) = notifier.notify(&announcement).await {
+                    warn!("Failed to send {} announcement: {e}", notifier.name());
+                }
+            });
+        }
+    }
+
+    pub async fn on_queue_paused(&self, reason: &PauseReason) -> anyhow::Result<()> {
+        self.announce(AnnouncementData::from_pause_reason(reason));
+        Ok(())
+    }
+
+    pub async fn on_task_held_back(&self, task: &Task, reason: &PauseReason) -> anyhow::Result<()> {
+        self.announce(AnnouncementData::from_held_back_task(task, reason));
+        Ok(())
+    }
+
+    pub async fn on_contents_empty(&self, job_id: Uuid) -> anyhow::Result<()> {
+        let job: Job = self.db.lock().await.get_job(job_id).await?;
+        self.webhooks
+            .on_job_event(JobEvent::PlaylistEmpty, &job)
+            .await?;
+        self.announce(AnnouncementData::from_job(
+            AnnounceEvent::PlaylistEmpty,
+            &job,
+        ));
+        Ok(())
+    }
+}
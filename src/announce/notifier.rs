@@ -0,0 +1,25 @@
+use crate::announce::desktop::DesktopNotifier;
+use crate::announce::email::EmailNotifier;
+use crate::announce::http_push::{GotifyNotifier, NtfyNotifier};
+use crate::announce::log_only::LogNotifier;
+use crate::announce::{Announcement, NotifierBackend};
+use rocket::async_trait;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+#[async_trait]
+pub trait Notifier: Debug + Send + Sync {
+    fn name(&self) -> &'static str;
+
+    async fn notify(&self, announcement: &Announcement) -> anyhow::Result<()>;
+}
+
+pub fn create_notifier(backend: &NotifierBackend) -> anyhow::Result<Arc<dyn Notifier>> {
+    Ok(match backend {
+        NotifierBackend::Desktop => Arc::new(DesktopNotifier),
+        NotifierBackend::Log => Arc::new(LogNotifier),
+        NotifierBackend::Email(config) => Arc::new(EmailNotifier::new(config)?),
+        NotifierBackend::Ntfy(config) => Arc::new(NtfyNotifier::new(config.clone())),
+        NotifierBackend::Gotify(config) => Arc::new(GotifyNotifier::new(config.clone())),
+    })
+}
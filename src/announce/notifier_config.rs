@@ -0,0 +1,164 @@
+use crate::announce::{AnnounceEvent, MessageTemplate};
+use crate::env::Secret;
+use handlebars::Template;
+use lettre::message::Mailbox;
+use rocket::serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotifierConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    // No events means all of them
+    #[serde(default)]
+    pub events: Vec<AnnounceEvent>,
+    // Overrides default message for given events
+    #[serde(default)]
+    pub messages: HashMap<AnnounceEvent, MessageTemplate>,
+    #[serde(flatten)]
+    pub backend: NotifierBackend,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum NotifierBackend {
+    Desktop,
+    Log,
+    Email(EmailConfig),
+    Ntfy(NtfyConfig),
+    Gotify(GotifyConfig),
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub enum SmtpSecurity {
+    None,
+    #[default]
+    StartTls,
+    Tls,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmailConfig {
+    pub smtp_host: String,
+    // Default depends on `security`
+    #[serde(default)]
+    pub smtp_port: Option<u16>,
+    #[serde(default)]
+    pub security: SmtpSecurity,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<Secret>,
+    pub from: String,
+    pub to: Vec<String>,
+}
+
+// Topic URL, e.g. "https://ntfy.sh/my-downloads".
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NtfyConfig {
+    pub url: String,
+    #[serde(default)]
+    pub token: Option<Secret>,
+    #[serde(default)]
+    pub priority: Option<u8>,
+}
+
+// Server URL, e.g. "https://gotify.example.com/", and application token.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GotifyConfig {
+    pub url: String,
+    pub token: Secret,
+    #[serde(default)]
+    pub priority: Option<u8>,
+}
+
+impl NotifierBackend {
+    pub fn secrets_mut(&mut self) -> Vec<&mut Secret> {
+        match self {
+            NotifierBackend::Desktop | NotifierBackend::Log => vec![],
+            NotifierBackend::Email(email) => email.password.iter_mut().collect(),
+            NotifierBackend::Ntfy(ntfy) => ntfy.token.iter_mut().collect(),
+            NotifierBackend::Gotify(gotify) => vec![&mut gotify.token],
+        }
+    }
+
+    // Only a backend of the same kind has the secrets to fill in.
+    pub fn restore_secrets(&mut self, current: &NotifierBackend) {
+        if std::mem::discriminant(self) != std::mem::discriminant(current) {
+            return;
+        }
+        let mut current = current.clone();
+        let mut current_secrets = current.secrets_mut().into_iter();
+        for secret in self.secrets_mut() {
+            secret.restore(current_secrets.next().map(|x| &*x));
+        }
+    }
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn is_http_url(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://")
+}
+
+impl NotifierConfig {
+    pub fn desktop() -> Self {
+        Self {
+            enabled: true,
+            events: vec![],
+            messages: HashMap::new(),
+            backend: NotifierBackend::Desktop,
+        }
+    }
+
+    pub fn accepts(&self, event: AnnounceEvent) -> bool {
+        self.enabled && (self.events.is_empty() || self.events.contains(&event))
+    }
+
+    pub fn get_message(&self, event: AnnounceEvent) -> MessageTemplate {
+        self.messages
+            .get(&event)
+            .cloned()
+            .unwrap_or_else(|| MessageTemplate::default_for(event))
+    }
+
+    // Returns description of the first problem found.
+    pub fn check_validity(&self) -> Result<(), String> {
+        for (event, message) in &self.messages {
+            for template in [&message.summary, &message.body] {
+                if let Err(e) = Template::compile(template) {
+                    return Err(format!("bad {event:?} message template: {e}"));
+                }
+            }
+        }
+        match &self.backend {
+            NotifierBackend::Desktop | NotifierBackend::Log => {}
+            NotifierBackend::Email(email) => {
+                if email.smtp_host.is_empty() {
+                    return Err("empty smtp_host".to_string());
+                }
+                if email.to.is_empty() {
+                    return Err("no email recipients".to_string());
+                }
+                for address in std::iter::once(&email.from).chain(&email.to) {
+                    if address.parse::<Mailbox>().is_err() {
+                        return Err(format!("invalid email address {address:?}"));
+                    }
+                }
+            }
+            NotifierBackend::Ntfy(ntfy) => {
+                if !is_http_url(&ntfy.url) {
+                    return Err(format!("invalid ntfy URL {:?}", ntfy.url));
+                }
+            }
+            NotifierBackend::Gotify(gotify) => {
+                if !is_http_url(&gotify.url) {
+                    return Err(format!("invalid Gotify URL {:?}", gotify.url));
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,225 @@
+use crate::filesystem::{ensure_private_dir_exists, path_exists, write_private_file};
+use crate::url_host::{host_matches, host_of_url};
+pub use profile::{CredentialsProfile, CredentialsProfileInfo, CredentialsProfileUpdate};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use tokio::sync::Mutex;
+
+mod profile;
+
+const PROFILES_FILE_NAME: &str = "profiles.yaml";
+
+// Keeps login data for sites that require it, such as members-only
+// or age-restricted videos, and turns it into yt-dlp arguments.
+//
+// Secrets are never passed on the command line (which would end up
+// in the logs and in process list), only paths to the files with them.
+#[derive(Debug)]
+pub struct CredentialStore {
+    dir: PathBuf,
+    profiles: Mutex<Vec<CredentialsProfile>>,
+}
+
+impl CredentialStore {
+    pub async fn open(dir: &Path) -> anyhow::Result<Self> {
+        ensure_private_dir_exists(dir).await?;
+        let profiles_file = dir.join(PROFILES_FILE_NAME);
+        let profiles = if path_exists(&profiles_file).await {
+            let contents = tokio::fs::read_to_string(&profiles_file).await?;
+            serde_yaml::from_str(&contents)?
+        } else {
+            vec![]
+        };
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            profiles: Mutex::new(profiles),
+        })
+    }
+
+    fn get_cookies_file(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{name}.cookies.txt"))
+    }
+
+    fn get_netrc_file(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{name}.netrc"))
+    }
+
+    fn get_ytdlp_config_file(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{name}.conf"))
+    }
+
+    async fn save_profiles(&self, profiles: &[CredentialsProfile]) -> anyhow::Result<()> {
+        let data = serde_yaml::to_string(profiles)?;
+        write_private_file(&self.dir.join(PROFILES_FILE_NAME), data.as_bytes()).await
+    }
+
+    pub async fn contains(&self, name: &str) -> bool {
+        self.profiles.lock().await.iter().any(|x| x.name == name)
+    }
+
+    pub async fn list(&self) -> Vec<CredentialsProfileInfo> {
+        let profiles = self.profiles.lock().await.clone();
+        let mut ret = Vec::with_capacity(profiles.len());
+        for profile in profiles {
+            ret.push(CredentialsProfileInfo {
+                has_password: profile.password.is_some(),
+                has_cookies: path_exists(&self.get_cookies_file(&profile.name)).await,
+                has_netrc: path_exists(&self.get_netrc_file(&profile.name)).await,
+                name: profile.name,
+                hosts: profile.hosts,
+                username: profile.username,
+            });
+        }
+        ret
+    }
+
+    pub async fn update(&self, update: CredentialsProfileUpdate) -> anyhow::Result<()> {
+        let CredentialsProfileUpdate {
+            mut profile,
+            cookies,
+            netrc,
+        } = update;
+        if !CredentialsProfile::is_valid_name(&profile.name) {
+            return Err(anyhow!("invalid profile name: {:?}", profile.name));
+        }
+        if profile.hosts.iter().any(|x| x.is_empty()) {
+            return Err(anyhow!("profile hosts must not be empty"));
+        }
+
+        let has_line_breaks = |x: &Option<String>| x.as_deref().unwrap_or_default().contains('\n');
+        if has_line_breaks(&profile.username) || has_line_breaks(&profile.password) {
+            return Err(anyhow!("username and password must not contain line breaks"));
+        }
+
+        let mut profiles = self.profiles.lock().await;
+
+        // Same as with cookies, missing password means "keep the old one"
+        match profile.password.as_deref() {
+            None => {
+                profile.password = profiles
+                    .iter()
+                    .find(|x| x.name == profile.name)
+                    .and_then(|x| x.password.clone());
+            }
+            Some("") => profile.password = None,
+            _ => {}
+        }
+
+        update_private_file(&self.get_cookies_file(&profile.name), cookies).await?;
+        update_private_file(&self.get_netrc_file(&profile.name), netrc).await?;
+        let config_contents = render_login_config(&profile);
+        update_private_file(
+            &self.get_ytdlp_config_file(&profile.name),
+            Some(config_contents),
+        )
+        .await?;
+
+        if let Some(existing) = profiles.iter_mut().find(|x| x.name == profile.name) {
+            *existing = profile;
+        } else {
+            profiles.push(profile);
+        }
+        self.save_profiles(&profiles).await
+    }
+
+    pub async fn remove(&self, name: &str) -> anyhow::Result<()> {
+        let mut profiles = self.profiles.lock().await;
+        let index = profiles
+            .iter()
+            .position(|x| x.name == name)
+            .ok_or_else(|| anyhow!("no such profile: {name:?}"))?;
+        profiles.remove(index);
+        self.save_profiles(&profiles).await?;
+
+        update_private_file(&self.get_cookies_file(name), Some(String::new())).await?;
+        update_private_file(&self.get_netrc_file(name), Some(String::new())).await?;
+        update_private_file(&self.get_ytdlp_config_file(name), Some(String::new())).await?;
+        Ok(())
+    }
+
+    // Picks the requested profile, or the first one that matches the URL.
+    async fn find_profile(
+        &self,
+        url: &str,
+        profile_name: Option<&str>,
+    ) -> anyhow::Result<Option<CredentialsProfile>> {
+        let profiles = self.profiles.lock().await;
+        if let Some(profile_name) = profile_name {
+            let profile = profiles
+                .iter()
+                .find(|x| x.name == profile_name)
+                .ok_or_else(|| anyhow!("unknown credentials profile: {profile_name:?}"))?;
+            return Ok(Some(profile.clone()));
+        }
+        let host = match host_of_url(url) {
+            Some(host) => host,
+            None => return Ok(None),
+        };
+        Ok(profiles
+            .iter()
+            .find(|x| x.hosts.iter().any(|domain| host_matches(&host, domain)))
+            .cloned())
+    }
+
+    pub async fn get_ytdlp_args(
+        &self,
+        url: &str,
+        profile_name: Option<&str>,
+    ) -> anyhow::Result<Vec<OsString>> {
+        let profile = match self.find_profile(url, profile_name).await? {
+            Some(profile) => profile,
+            None => return Ok(vec![]),
+        };
+
+        let mut args = vec![];
+        let cookies_file = self.get_cookies_file(&profile.name);
+        if path_exists(&cookies_file).await {
+            args.push(OsString::from("--cookies"));
+            args.push(cookies_file.into_os_string());
+        }
+        let netrc_file = self.get_netrc_file(&profile.name);
+        if path_exists(&netrc_file).await {
+            args.push(OsString::from("--netrc"));
+            args.push(OsString::from("--netrc-location"));
+            args.push(netrc_file.into_os_string());
+        }
+        let config_file = self.get_ytdlp_config_file(&profile.name);
+        if profile.username.is_some() && path_exists(&config_file).await {
+            args.push(OsString::from("--config-locations"));
+            args.push(config_file.into_os_string());
+        }
+        Ok(args)
+    }
+}
+
+// Writes the file if there are new contents, removes it if they're empty.
+async fn update_private_file(path: &Path, contents: Option<String>) -> anyhow::Result<()> {
+    match contents {
+        None => Ok(()),
+        Some(contents) if contents.is_empty() => {
+            if path_exists(path).await {
+                tokio::fs::remove_file(path).await?;
+            }
+            Ok(())
+        }
+        Some(contents) => write_private_file(path, contents.as_bytes()).await,
+    }
+}
+
+// yt-dlp config file, to avoid putting the password on the command line.
+fn render_login_config(profile: &CredentialsProfile) -> String {
+    // yt-dlp parses config files as shell-like arguments,
+    // and inside double quotes only `"` and `\` need escaping.
+    fn quote(value: &str) -> String {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+
+    let mut ret = String::new();
+    if let Some(username) = &profile.username {
+        ret += &format!("--username {}\n", quote(username));
+        if let Some(password) = &profile.password {
+            ret += &format!("--password {}\n", quote(password));
+        }
+    }
+    ret
+}
@@ -0,0 +1,49 @@
+use rocket::serde::{Deserialize, Serialize};
+
+// Stored in the profiles file. Cookies and netrc data
+// are too big to comfortably live here, so they go into separate files.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CredentialsProfile {
+    pub name: String,
+    #[serde(default)]
+    pub hosts: Vec<String>,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+}
+
+// Submitted through the API to create or modify a profile.
+// `None` for password, cookies or netrc keeps what's already stored,
+// while an empty string removes it.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CredentialsProfileUpdate {
+    #[serde(flatten)]
+    pub profile: CredentialsProfile,
+    #[serde(default)]
+    pub cookies: Option<String>,
+    #[serde(default)]
+    pub netrc: Option<String>,
+}
+
+// What we're willing to show about a profile, i.e. no secrets.
+#[derive(Debug, Clone, Serialize)]
+pub struct CredentialsProfileInfo {
+    pub name: String,
+    pub hosts: Vec<String>,
+    pub username: Option<String>,
+    pub has_password: bool,
+    pub has_cookies: bool,
+    pub has_netrc: bool,
+}
+
+impl CredentialsProfile {
+    pub fn is_valid_name(name: &str) -> bool {
+        // Name is used as part of file names
+        !name.is_empty()
+            && name.len() <= 64
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    }
+}
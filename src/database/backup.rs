@@ -0,0 +1,405 @@
+use crate::database::media_metadata::TaskMediaMetadata;
+use crate::database::{JobFetch, ServerDatabase, ServerLock};
+use crate::task::{Task, TaskArtifact};
+use chrono::{DateTime, Utc};
+use rocket::serde::{Deserialize, Serialize};
+use sqlx::sqlite::SqliteConnectOptions;
+use sqlx::{query, query_as, ConnectOptions, FromRow};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use tokio::io::AsyncReadExt;
+use uuid::Uuid;
+
+const EXPORT_VERSION: u32 = 1;
+const SQLITE_HEADER: &[u8] = b"SQLite format 3\0";
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ExportedUser {
+    pub user_id: Uuid,
+    pub name: String,
+    pub api_token: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ExportedTask {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub task: Task,
+    pub task_index: i64,
+    // Task's own field is redacted when serialized
+    #[serde(default)]
+    #[sqlx(rename = "proxy")]
+    pub proxy_url: Option<String>,
+}
+
+// Contents of the database which are worth moving to another install.
+// Sessions and webhook delivery history are left behind.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DatabaseExport {
+    pub version: u32,
+    pub exported_at: DateTime<Utc>,
+    pub users: Vec<ExportedUser>,
+    pub jobs: Vec<JobFetch>,
+    pub tasks: Vec<ExportedTask>,
+    pub artifacts: Vec<TaskArtifact>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ImportSummary {
+    pub num_users: u64,
+    pub num_jobs: u64,
+    pub num_tasks: u64,
+    pub num_artifacts: u64,
+}
+
+pub async fn is_database_snapshot(path: &Path) -> anyhow::Result<bool> {
+    let mut header = vec![0u8; SQLITE_HEADER.len()];
+    let mut file = tokio::fs::File::open(path).await?;
+    let num_read = file.read(&mut header).await?;
+    Ok(num_read == header.len() && header == SQLITE_HEADER)
+}
+
+// Replaces database with a snapshot made by `backup_to`. Current
+// database doesn't have to be readable, so this works for recovery too,
+// but it's moved aside rather than lost.
+pub async fn restore_snapshot(snapshot: &Path, database_file: &Path) -> anyhow::Result<PathBuf> {
+    let lock = ServerLock::acquire(database_file).await?;
+    {
+        let mut conn = SqliteConnectOptions::from_str(&snapshot.to_string_lossy())?
+            .read_only(true)
+            .disable_statement_logging()
+            .connect()
+            .await?;
+        let (integrity,): (String,) = query_as("PRAGMA integrity_check")
+            .fetch_one(&mut conn)
+            .await?;
+        if integrity != "ok" {
+            return Err(anyhow!("snapshot is damaged: {integrity}"));
+        }
+        query("SELECT version FROM _sqlx_migrations")
+            .fetch_all(&mut conn)
+            .await
+            .map_err(|_| anyhow!("file is not a database of this server"))?;
+    }
+
+    let timestamp = Utc::now().format("%Y%m%d-%H%M%S");
+    let with_suffix = |path: &Path, suffix: &str| {
+        let mut path = path.as_os_str().to_owned();
+        path.push(suffix);
+        PathBuf::from(path)
+    };
+    let replaced_file = with_suffix(database_file, &format!(".replaced-{timestamp}"));
+    // Journal goes along with its database: the old one may need it to
+    // be complete, and the new one would get it applied otherwise
+    for suffix in ["", "-wal", "-shm", "-journal"] {
+        let from = with_suffix(database_file, suffix);
+        if tokio::fs::try_exists(&from).await? {
+            tokio::fs::rename(&from, with_suffix(&replaced_file, suffix)).await?;
+        }
+    }
+    tokio::fs::copy(snapshot, database_file).await?;
+    lock.release().await?;
+    Ok(replaced_file)
+}
+
+impl ServerDatabase {
+    // Consistent copy of the whole database, which can be taken
+    // while the server is running.
+    pub async fn backup_to(&self, path: &Path) -> anyhow::Result<()> {
+        if tokio::fs::try_exists(path).await? {
+            return Err(anyhow!("file {path:?} already exists"));
+        }
+        query("VACUUM INTO ?1")
+            .bind(path.to_string_lossy().to_string())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn export_json(&self) -> anyhow::Result<DatabaseExport> {
+        let mut tx = self.pool.begin().await?;
+        let users: Vec<ExportedUser> = query_as(r#"SELECT user_id, name, api_token FROM users"#)
+            .fetch_all(&mut *tx)
+            .await?;
+        let jobs: Vec<JobFetch> = query_as(r#"SELECT * FROM jobs ORDER BY created_at"#)
+            .fetch_all(&mut *tx)
+            .await?;
+        let mut tasks: Vec<ExportedTask> = query_as(
+            r#"SELECT * FROM tasks WHERE pending_delete = false ORDER BY created_at, task_index"#,
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        let metadata: Vec<TaskMediaMetadata> = query_as(r#"SELECT * FROM media_metadata"#)
+            .fetch_all(&mut *tx)
+            .await?;
+        let mut metadata: HashMap<Uuid, _> = metadata
+            .into_iter()
+            .map(|x| (x.task_id, x.metadata))
+            .collect();
+        for ExportedTask { task, .. } in &mut tasks {
+            task.metadata = metadata.remove(&task.task_id);
+        }
+        let artifacts: Vec<TaskArtifact> = query_as(r#"SELECT * FROM task_artifacts"#)
+            .fetch_all(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(DatabaseExport {
+            version: EXPORT_VERSION,
+            exported_at: Utc::now(),
+            users,
+            jobs,
+            tasks,
+            artifacts,
+        })
+    }
+
+    // Adds exported contents to this database. Jobs and tasks which are
+    // already present are left as they are. Users replace the ones with
+    // the same name, so that the old API token keeps working.
+    pub async fn import_json(&self, data: &DatabaseExport) -> anyhow::Result<ImportSummary> {
+        if data.version > EXPORT_VERSION {
+            return Err(anyhow!(
+                "export version {} is newer than supported {EXPORT_VERSION}",
+                data.version
+            ));
+        }
+        let mut summary = ImportSummary::default();
+        let mut tx = self.pool.begin().await?;
+
+        for user in &data.users {
+            query!(
+                r#"
+                DELETE FROM users
+                WHERE name = ?1 OR user_id = ?2 OR api_token = ?3
+                "#,
+                user.name,
+                user.user_id,
+                user.api_token,
+            )
+            .execute(&mut *tx)
+            .await?;
+            summary.num_users += query!(
+                r#"
+                INSERT INTO users
+                    (user_id, name, api_token)
+                VALUES
+                    (?1, ?2, ?3)
+                "#,
+                user.user_id,
+                user.name,
+                user.api_token,
+            )
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        }
+
+        for job in &data.jobs {
+            summary.num_jobs += query!(
+                r#"
+                INSERT OR IGNORE INTO jobs
+                    (job_id, thumbnail, url, format, created_at, prioritized, title, credentials_profile, proxy, transcode_preset, is_group, playlist_path, preview, select_first, select_newest, select_title)
+                VALUES
+                    (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)
+                "#,
+                job.job_id,
+                job.thumbnail,
+                job.url,
+                job.format,
+                job.created_at,
+                job.prioritized,
+                job.title,
+                job.credentials_profile,
+                job.proxy,
+                job.transcode_preset,
+                job.is_group,
+                job.playlist_path,
+                job.preview,
+                job.selection.select_first,
+                job.selection.select_newest,
+                job.selection.select_title,
+            )
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        }
+
+        for ExportedTask {
+            task,
+            task_index,
+            proxy_url,
+        } in &data.tasks
+        {
+            // Exports made before proxies got redacted only have them in the task
+            let proxy = proxy_url.as_ref().or(task.proxy.as_ref());
+            let num_inserted = query!(
+                r#"
+                INSERT OR IGNORE INTO tasks
+                    (task_id, status, kind, thumbnail, owner_job_id, url, format, created_at, started_at, finished_at, prioritized, task_index, title, is_resumed, num_retries, pending_cleanup, bytes_estimate, credentials_profile, proxy, transcode_preset, source_path, output_path, depends_on, failure_kind, failure_message, uploader, output_size, hook_failures)
+                VALUES
+                    (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27, ?28)
+                "#,
+                task.task_id,
+                task.status,
+                task.kind,
+                task.thumbnail,
+                task.owner_job_id,
+                task.url,
+                task.format,
+                task.created_at,
+                task.started_at,
+                task.finished_at,
+                task.prioritized,
+                task_index,
+                task.title,
+                task.is_resumed,
+                task.num_retries,
+                task.pending_cleanup,
+                task.bytes_estimate,
+                task.credentials_profile,
+                proxy,
+                task.transcode_preset,
+                task.source_path,
+                task.output_path,
+                task.depends_on,
+                task.failure_kind,
+                task.failure_message,
+                task.uploader,
+                task.output_size,
+                task.hook_failures,
+            )
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+            summary.num_tasks += num_inserted;
+
+            let metadata = match &task.metadata {
+                Some(x) if num_inserted > 0 => x,
+                _ => continue,
+            };
+            query!(
+                r#"
+                INSERT OR REPLACE INTO media_metadata
+                    (task_id, extractor, duration, channel, published_at, view_count, like_count, description, chapters, tags)
+                VALUES
+                    (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+                "#,
+                task.task_id,
+                metadata.extractor,
+                metadata.duration,
+                metadata.channel,
+                metadata.published_at,
+                metadata.view_count,
+                metadata.like_count,
+                metadata.description,
+                metadata.chapters,
+                metadata.tags,
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        for artifact in &data.artifacts {
+            summary.num_artifacts += query!(
+                r#"
+                INSERT INTO task_artifacts
+                    (task_id, kind, path)
+                SELECT ?1, ?2, ?3
+                WHERE NOT EXISTS (
+                    SELECT 1 FROM task_artifacts WHERE task_id = ?1 AND path = ?3
+                )
+                "#,
+                artifact.task_id,
+                artifact.kind,
+                artifact.path,
+            )
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        }
+
+        tx.commit().await?;
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filesystem::FilesystemDriver;
+    use crate::queue_command::QueueCommand;
+    use crate::task::TaskStatus;
+    use crate::test_util::{new_job_form, new_test_server, TempDir};
+
+    #[tokio::test]
+    async fn read_only_export_leaves_database_alone() {
+        let dir = TempDir::new();
+        let (env, db) = new_test_server(&dir, |_| {}).await;
+        let task_id = {
+            let db = db.lock().await;
+            let job = db
+                .create_job(&new_job_form("https://a.com/1"))
+                .await
+                .unwrap();
+            let task_id = job.tasks[0].task_id;
+            db.modify_task(
+                task_id,
+                QueueCommand::TaskStatusChange(TaskStatus::Processing),
+            )
+            .await
+            .unwrap();
+            task_id
+        };
+
+        // Opening it normally would mark the task as failed
+        let fs = FilesystemDriver::new(env);
+        let read_only = ServerDatabase::open_read_only(&fs).await.unwrap();
+        let data = read_only.export_json().await.unwrap();
+        let task = &data
+            .tasks
+            .iter()
+            .find(|x| x.task.task_id == task_id)
+            .unwrap()
+            .task;
+        assert_eq!(task.status, TaskStatus::Processing);
+        assert!(read_only.add_user("someone", "token").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn restore_moves_journal_along_with_database() {
+        let dir = TempDir::new();
+        let (env, db) = new_test_server(&dir, |_| {}).await;
+        let snapshot = dir.path().join("snapshot.db");
+        db.lock().await.backup_to(&snapshot).await.unwrap();
+        drop(db);
+
+        let database_file = &env.paths.database_file;
+        let journal = PathBuf::from(format!("{}-journal", database_file.display()));
+        tokio::fs::write(&journal, b"journal").await.unwrap();
+        let replaced = restore_snapshot(&snapshot, database_file).await.unwrap();
+
+        assert!(!journal.exists());
+        let replaced_journal = PathBuf::from(format!("{}-journal", replaced.display()));
+        assert_eq!(std::fs::read(replaced_journal).unwrap(), b"journal");
+        assert!(is_database_snapshot(database_file).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn restore_is_refused_while_server_is_running() {
+        let dir = TempDir::new();
+        let (env, db) = new_test_server(&dir, |_| {}).await;
+        let snapshot = dir.path().join("snapshot.db");
+        db.lock().await.backup_to(&snapshot).await.unwrap();
+
+        let database_file = &env.paths.database_file;
+        let lock = ServerLock::acquire(database_file).await.unwrap();
+        let error = restore_snapshot(&snapshot, database_file)
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), "database is in use by a running server");
+        assert!(ServerLock::acquire(database_file).await.is_err());
+        lock.release().await.unwrap();
+        restore_snapshot(&snapshot, database_file).await.unwrap();
+    }
+}
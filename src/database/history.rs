@@ -0,0 +1,462 @@
+use crate::database::ServerDatabase;
+use crate::task::{TaskKind, TaskStatus};
+use crate::url_host::host_of_url;
+use chrono::{DateTime, Datelike, Duration, TimeZone, Utc};
+use rocket::serde::Serialize;
+use sqlx::{query, query_as_with, Arguments, FromRow, Sqlite, Transaction};
+use uuid::Uuid;
+
+const NUM_TOP_UPLOADERS: usize = 10;
+const MAX_BUCKETS: usize = 5000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum HistoryBucket {
+    Hour,
+    Day,
+    Week,
+    Month,
+}
+
+impl HistoryBucket {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "hour" => Some(HistoryBucket::Hour),
+            "day" => Some(HistoryBucket::Day),
+            "week" => Some(HistoryBucket::Week),
+            "month" => Some(HistoryBucket::Month),
+            _ => None,
+        }
+    }
+
+    // Buckets are aligned in UTC, weeks start on Monday.
+    fn start_of(self, time: DateTime<Utc>) -> DateTime<Utc> {
+        let day = time.date_naive();
+        let start = match self {
+            HistoryBucket::Hour => {
+                return Utc
+                    .timestamp_opt(time.timestamp() - time.timestamp() % 3600, 0)
+                    .unwrap();
+            }
+            HistoryBucket::Day => day,
+            HistoryBucket::Week => {
+                day - Duration::days(day.weekday().num_days_from_monday() as i64)
+            }
+            HistoryBucket::Month => day.with_day(1).unwrap(),
+        };
+        Utc.from_utc_datetime(&start.and_hms_opt(0, 0, 0).unwrap())
+    }
+
+    fn next(self, start: DateTime<Utc>) -> DateTime<Utc> {
+        match self {
+            HistoryBucket::Hour => start + Duration::hours(1),
+            HistoryBucket::Day => start + Duration::days(1),
+            HistoryBucket::Week => start + Duration::weeks(1),
+            HistoryBucket::Month => {
+                let (year, month) = if start.month() == 12 {
+                    (start.year() + 1, 1)
+                } else {
+                    (start.year(), start.month() + 1)
+                };
+                Utc.with_ymd_and_hms(year, month, 1, 0, 0, 0).unwrap()
+            }
+        }
+    }
+}
+
+// Download which has either finished or failed for good.
+#[derive(Debug, FromRow)]
+struct FinishedTask {
+    status: TaskStatus,
+    format: String,
+    url: String,
+    uploader: Option<String>,
+    started_at: Option<DateTime<Utc>>,
+    finished_at: DateTime<Utc>,
+    output_size: Option<i64>,
+}
+
+#[derive(Debug, Default, FromRow)]
+struct Totals {
+    num_done: i64,
+    num_failed: i64,
+    bytes: i64,
+    total_duration_secs: f64,
+    num_timed: i64,
+}
+
+impl Totals {
+    fn add(&mut self, other: &Totals) {
+        self.num_done += other.num_done;
+        self.num_failed += other.num_failed;
+        self.bytes += other.bytes;
+        self.total_duration_secs += other.total_duration_secs;
+        self.num_timed += other.num_timed;
+    }
+
+    fn finish(&self) -> OutcomeStats {
+        let num_total = self.num_done + self.num_failed;
+        OutcomeStats {
+            num_done: self.num_done as u32,
+            num_failed: self.num_failed as u32,
+            success_rate: (num_total > 0).then(|| self.num_done as f64 / num_total as f64),
+            bytes: self.bytes,
+            average_duration_secs: (self.num_timed > 0)
+                .then(|| self.total_duration_secs / self.num_timed as f64),
+        }
+    }
+}
+
+#[derive(Debug, FromRow)]
+struct HourTotals {
+    hour: DateTime<Utc>,
+    #[sqlx(flatten)]
+    totals: Totals,
+}
+
+#[derive(Debug, FromRow)]
+struct GroupTotals {
+    name: String,
+    #[sqlx(flatten)]
+    totals: Totals,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct OutcomeStats {
+    pub num_done: u32,
+    pub num_failed: u32,
+    pub success_rate: Option<f64>,
+    pub bytes: i64,
+    pub average_duration_secs: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BucketStats {
+    pub start: DateTime<Utc>,
+    #[serde(flatten)]
+    pub stats: OutcomeStats,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GroupStats {
+    pub name: String,
+    #[serde(flatten)]
+    pub stats: OutcomeStats,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HistoryStats {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub bucket: HistoryBucket,
+    pub total: OutcomeStats,
+    pub buckets: Vec<BucketStats>,
+    pub formats: Vec<GroupStats>,
+    pub sites: Vec<GroupStats>,
+    pub top_uploaders: Vec<GroupStats>,
+}
+
+const TOTALS_COLUMNS: &str = r#"
+    COALESCE(SUM(num_done), 0) AS num_done,
+    COALESCE(SUM(num_failed), 0) AS num_failed,
+    COALESCE(SUM(bytes), 0) AS bytes,
+    COALESCE(SUM(total_duration_secs), 0.0) AS total_duration_secs,
+    COALESCE(SUM(num_timed), 0) AS num_timed
+"#;
+
+async fn add_to_history(
+    tx: &mut Transaction<'_, Sqlite>,
+    task: &FinishedTask,
+) -> anyhow::Result<()> {
+    let hour = HistoryBucket::Hour.start_of(task.finished_at);
+    let host = host_of_url(&task.url).unwrap_or_default();
+    let uploader = task.uploader.clone().unwrap_or_default();
+    let is_done = task.status == TaskStatus::Done;
+    let (num_done, num_failed) = if is_done { (1, 0) } else { (0, 1) };
+    let bytes = if is_done {
+        task.output_size.unwrap_or_default()
+    } else {
+        0
+    };
+    let duration_secs = match task.started_at {
+        Some(started_at) if is_done => {
+            let duration = (task.finished_at - started_at).num_milliseconds();
+            Some(duration.max(0) as f64 / 1000.0)
+        }
+        _ => None,
+    };
+    let total_duration_secs = duration_secs.unwrap_or_default();
+    let num_timed = duration_secs.map_or(0, |_| 1);
+    query!(
+        r#"
+        INSERT INTO history_rollup
+            (hour, format, host, uploader, num_done, num_failed, bytes, total_duration_secs, num_timed)
+        VALUES
+            (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+        ON CONFLICT (hour, format, host, uploader) DO UPDATE SET
+            num_done = num_done + excluded.num_done,
+            num_failed = num_failed + excluded.num_failed,
+            bytes = bytes + excluded.bytes,
+            total_duration_secs = total_duration_secs + excluded.total_duration_secs,
+            num_timed = num_timed + excluded.num_timed
+        "#,
+        hour,
+        task.format,
+        host,
+        uploader,
+        num_done,
+        num_failed,
+        bytes,
+        total_duration_secs,
+        num_timed,
+    )
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+impl ServerDatabase {
+    // Adds the download to history once it won't change anymore,
+    // i.e. it's done or has failed without retries left.
+    pub async fn record_task_history(&self, task_id: Uuid) -> anyhow::Result<()> {
+        let mut tx = self.pool.begin().await?;
+        let mut args = Self::new_args();
+        args.add(task_id);
+        args.add(TaskKind::DownloadAndConvert);
+        args.add(TaskStatus::Done);
+        args.add(TaskStatus::Failed);
+        let task: Option<FinishedTask> = query_as_with(
+            r#"
+            SELECT status, format, url, uploader, started_at, finished_at, output_size
+            FROM tasks
+            WHERE task_id = ?1 AND kind = ?2 AND (status = ?3 OR status = ?4)
+                AND finished_at IS NOT NULL
+            "#,
+            args,
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+        if let Some(task) = task {
+            add_to_history(&mut tx, &task).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    // Databases from before the history was kept get it filled in
+    // from whatever tasks they still have.
+    pub(super) async fn backfill_history(&self) -> anyhow::Result<()> {
+        let mut tx = self.pool.begin().await?;
+        let is_empty = query!(r#"SELECT COUNT(*) AS count FROM history_rollup"#)
+            .fetch_one(&mut *tx)
+            .await?
+            .count
+            == 0;
+        if !is_empty {
+            return Ok(());
+        }
+        let mut args = Self::new_args();
+        args.add(TaskKind::DownloadAndConvert);
+        args.add(TaskStatus::Done);
+        args.add(TaskStatus::Failed);
+        let tasks: Vec<FinishedTask> = query_as_with(
+            r#"
+            SELECT status, format, url, uploader, started_at, finished_at, output_size
+            FROM tasks
+            WHERE kind = ?1 AND (status = ?2 OR status = ?3) AND pending_delete = false
+                AND finished_at IS NOT NULL
+            "#,
+            args,
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        for task in &tasks {
+            add_to_history(&mut tx, task).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    // Most active groups go first.
+    async fn get_history_groups(
+        &self,
+        column: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        limit: Option<usize>,
+    ) -> anyhow::Result<Vec<GroupStats>> {
+        let mut args = Self::new_args();
+        args.add(from);
+        args.add(to);
+        args.add(limit.map_or(-1, |x| x as i64));
+        let sql = format!(
+            r#"
+            SELECT {column} AS name, {TOTALS_COLUMNS}
+            FROM history_rollup
+            WHERE hour >= ?1 AND hour < ?2 AND {column} != ''
+            GROUP BY {column}
+            ORDER BY SUM(num_done) + SUM(num_failed) DESC, name
+            LIMIT ?3
+            "#
+        );
+        let groups: Vec<GroupTotals> = query_as_with(&sql, args).fetch_all(&self.pool).await?;
+        Ok(groups
+            .into_iter()
+            .map(|x| GroupStats {
+                name: x.name,
+                stats: x.totals.finish(),
+            })
+            .collect())
+    }
+
+    // Aggregates downloads which have finished within [from, to),
+    // to the precision of an hour.
+    pub async fn get_history_stats(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        bucket: HistoryBucket,
+    ) -> anyhow::Result<HistoryStats> {
+        let mut bucket_starts = vec![];
+        let mut start = bucket.start_of(from);
+        while start < to {
+            if bucket_starts.len() >= MAX_BUCKETS {
+                return Err(anyhow!("too many buckets, use a larger bucket size"));
+            }
+            bucket_starts.push(start);
+            start = bucket.next(start);
+        }
+
+        let first_hour = HistoryBucket::Hour.start_of(from);
+        let mut args = Self::new_args();
+        args.add(first_hour);
+        args.add(to);
+        let sql = format!(
+            r#"
+            SELECT hour, {TOTALS_COLUMNS}
+            FROM history_rollup
+            WHERE hour >= ?1 AND hour < ?2
+            GROUP BY hour
+            "#
+        );
+        let hours: Vec<HourTotals> = query_as_with(&sql, args).fetch_all(&self.pool).await?;
+
+        let mut total = Totals::default();
+        let mut buckets: Vec<Totals> = bucket_starts.iter().map(|_| Totals::default()).collect();
+        for x in &hours {
+            total.add(&x.totals);
+            let hour_bucket = bucket.start_of(x.hour);
+            if let Ok(idx) = bucket_starts.binary_search(&hour_bucket) {
+                buckets[idx].add(&x.totals);
+            }
+        }
+
+        Ok(HistoryStats {
+            from,
+            to,
+            bucket,
+            total: total.finish(),
+            buckets: bucket_starts
+                .into_iter()
+                .zip(buckets)
+                .map(|(start, totals)| BucketStats {
+                    start,
+                    stats: totals.finish(),
+                })
+                .collect(),
+            formats: self
+                .get_history_groups("format", first_hour, to, None)
+                .await?,
+            sites: self
+                .get_history_groups("host", first_hour, to, None)
+                .await?,
+            top_uploaders: self
+                .get_history_groups("uploader", first_hour, to, Some(NUM_TOP_UPLOADERS))
+                .await?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::queue_command::QueueCommand;
+    use crate::task::Task;
+    use crate::test_util::{new_job_form, new_test_server, TempDir};
+
+    async fn finish_download(
+        db: &ServerDatabase,
+        url: &str,
+        uploader: &str,
+        status: TaskStatus,
+    ) -> Uuid {
+        let job = db.create_job(&new_job_form(url)).await.unwrap();
+        let mut task = Task::new_fetch_url_contents(&job);
+        task.kind = TaskKind::DownloadAndConvert;
+        task.uploader = Some(uploader.to_string());
+        db.modify_job(job.job_id, QueueCommand::TasksAdded(vec![task.clone()]))
+            .await
+            .unwrap();
+        db.modify_task(task.task_id, QueueCommand::TaskStatusChange(status))
+            .await
+            .unwrap();
+        db.set_task_output(task.task_id, "video.mp3", 1000)
+            .await
+            .unwrap();
+        db.record_task_history(task.task_id).await.unwrap();
+        job.job_id
+    }
+
+    #[tokio::test]
+    async fn history_outlives_deleted_jobs() {
+        let dir = TempDir::new();
+        let (_, db) = new_test_server(&dir, |_| {}).await;
+        let db = db.lock().await;
+        let job_id = finish_download(&db, "https://www.a.com/1", "x", TaskStatus::Done).await;
+        finish_download(&db, "https://a.com/2", "y", TaskStatus::Done).await;
+        finish_download(&db, "https://b.com/3", "x", TaskStatus::Failed).await;
+
+        db.modify_job(job_id, QueueCommand::Delete).await.unwrap();
+        let pending_ops = db.get_pending_operations().await.unwrap();
+        db.confirm_deletion(&pending_ops.delete).await.unwrap();
+
+        let now = Utc::now();
+        let stats = db
+            .get_history_stats(now - Duration::days(1), now, HistoryBucket::Day)
+            .await
+            .unwrap();
+        assert_eq!(stats.total.num_done, 2);
+        assert_eq!(stats.total.num_failed, 1);
+        assert_eq!(stats.total.bytes, 2000);
+        assert_eq!(stats.buckets.len(), 2);
+        let num_bucketed: u32 = stats
+            .buckets
+            .iter()
+            .map(|x| x.stats.num_done + x.stats.num_failed)
+            .sum();
+        assert_eq!(num_bucketed, 3);
+        let sites: Vec<(&str, u32)> = stats
+            .sites
+            .iter()
+            .map(|x| (x.name.as_str(), x.stats.num_done))
+            .collect();
+        assert_eq!(sites, [("a.com", 2), ("b.com", 0)]);
+        assert_eq!(stats.top_uploaders[0].name, "x");
+        assert_eq!(stats.top_uploaders[0].stats.success_rate, Some(0.5));
+    }
+
+    #[tokio::test]
+    async fn unfinished_tasks_are_not_recorded() {
+        let dir = TempDir::new();
+        let (_, db) = new_test_server(&dir, |_| {}).await;
+        let db = db.lock().await;
+        finish_download(&db, "https://a.com/1", "x", TaskStatus::Paused).await;
+
+        let now = Utc::now();
+        let stats = db
+            .get_history_stats(now - Duration::days(1), now, HistoryBucket::Hour)
+            .await
+            .unwrap();
+        assert_eq!(stats.total.num_done + stats.total.num_failed, 0);
+        assert!(stats.formats.is_empty());
+    }
+}
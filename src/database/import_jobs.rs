@@ -0,0 +1,175 @@
+use crate::database::ServerDatabase;
+use crate::job::{Job, JobStatus};
+use crate::job_import::{url_key, ImportEntry, ImportOptions};
+use crate::task::{Task, TaskKind, TaskStatus};
+use chrono::Utc;
+use sqlx::{query_as_with, Arguments};
+use std::collections::HashSet;
+use uuid::Uuid;
+
+#[derive(Debug, Default)]
+pub struct ImportedJobs {
+    pub jobs: Vec<Job>,
+    pub num_already_queued: u32,
+}
+
+fn new_job(options: &ImportOptions, url: &str, format: &str, title: &str) -> Job {
+    Job {
+        job_id: Uuid::new_v4(),
+        status: JobStatus::Waiting,
+        thumbnail: "".to_string(),
+        url: url.to_string(),
+        format: format.to_string(),
+        created_at: Utc::now(),
+        started_at: None,
+        finished_at: None,
+        tasks: vec![],
+        progress: Default::default(),
+        prioritized: false,
+        title: title.to_string(),
+        credentials_profile: options.credentials_profile.clone(),
+        proxy: options.proxy.clone(),
+        transcode_preset: options.transcode_preset.clone(),
+        is_group: options.group,
+        playlist_path: None,
+        preview: false,
+        selection: Default::default(),
+        groups: vec![],
+    }
+}
+
+impl ServerDatabase {
+    // Creates either one job with a task per URL, or a job per URL.
+    // Everything happens in one transaction, and URLs of jobs which are
+    // still in progress are skipped. Finished ones can be downloaded again.
+    pub async fn import_jobs(
+        &self,
+        entries: &[ImportEntry],
+        options: &ImportOptions,
+    ) -> anyhow::Result<ImportedJobs> {
+        let mut tx = self.pool.begin().await?;
+
+        let mut args = Self::new_args();
+        args.add(TaskKind::FetchUrlContents);
+        args.add(TaskKind::DownloadAndConvert);
+        args.add(TaskStatus::Waiting);
+        args.add(TaskStatus::Processing);
+        args.add(TaskStatus::Paused);
+        let known_urls: Vec<(String,)> = query_as_with(
+            r#"
+            SELECT url FROM tasks
+            WHERE (kind = ?1 OR kind = ?2) AND pending_delete = false
+                AND owner_job_id IN (
+                    SELECT owner_job_id FROM tasks
+                    WHERE (status = ?3 OR status = ?4 OR status = ?5) AND pending_delete = false
+                )
+            "#,
+            args,
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        let known_urls: HashSet<String> = known_urls.iter().map(|(x,)| url_key(x)).collect();
+
+        let (entries, already_queued): (Vec<&ImportEntry>, Vec<&ImportEntry>) = entries
+            .iter()
+            .partition(|x| !known_urls.contains(&url_key(&x.url)));
+        let mut ret = ImportedJobs {
+            jobs: vec![],
+            num_already_queued: already_queued.len() as u32,
+        };
+        if entries.is_empty() {
+            return Ok(ret);
+        }
+
+        let format_of = |entry: &ImportEntry| {
+            entry
+                .format
+                .clone()
+                .unwrap_or_else(|| options.format.clone())
+        };
+        if options.group {
+            let title = match entries.len() {
+                1 => "Imported 1 URL".to_string(),
+                n => format!("Imported {n} URLs"),
+            };
+            let mut job = new_job(options, "", &options.format, &title);
+            for entry in &entries {
+                let mut task = Task::new_fetch_url_contents(&job);
+                task.url = entry.url.clone();
+                task.format = format_of(entry);
+                job.tasks.push(task);
+            }
+            ret.jobs.push(job);
+        } else {
+            for entry in &entries {
+                let mut job = new_job(options, &entry.url, &format_of(entry), "...");
+                job.tasks.push(Task::new_fetch_url_contents(&job));
+                ret.jobs.push(job);
+            }
+        }
+
+        for job in &mut ret.jobs {
+            Self::insert_job(&mut tx, job).await?;
+            job.status = Job::status_from_tasks(&job.tasks);
+        }
+
+        tx.commit().await?;
+        Ok(ret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::queue_command::QueueCommand;
+    use crate::test_util::{new_job_form, new_test_server, TempDir};
+
+    fn entry(url: &str) -> ImportEntry {
+        ImportEntry {
+            line: 1,
+            url: url.to_string(),
+            format: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn only_urls_still_in_progress_are_skipped() {
+        let dir = TempDir::new();
+        let (_, db) = new_test_server(&dir, |_| {}).await;
+        let db = db.lock().await;
+        db.create_job(&new_job_form("https://a.com/waiting"))
+            .await
+            .unwrap();
+        let done_job = db
+            .create_job(&new_job_form("https://a.com/done"))
+            .await
+            .unwrap();
+        db.modify_task(
+            done_job.tasks[0].task_id,
+            QueueCommand::TaskStatusChange(TaskStatus::Done),
+        )
+        .await
+        .unwrap();
+
+        let options = ImportOptions {
+            format: "mp3".to_string(),
+            credentials_profile: None,
+            proxy: None,
+            transcode_preset: None,
+            group: false,
+        };
+        let entries = [
+            entry("https://a.com/waiting#t=1"),
+            entry("https://a.com/done"),
+            entry("https://a.com/new"),
+        ];
+        let imported = db.import_jobs(&entries, &options).await.unwrap();
+        assert_eq!(imported.num_already_queued, 1);
+        let urls: Vec<&str> = imported.jobs.iter().map(|x| x.url.as_str()).collect();
+        assert_eq!(urls, ["https://a.com/done", "https://a.com/new"]);
+
+        let job = db.get_job(imported.jobs[0].job_id).await.unwrap();
+        assert_eq!(job.tasks.len(), 1);
+        assert_eq!(job.tasks[0].kind, TaskKind::FetchUrlContents);
+    }
+}
@@ -0,0 +1,68 @@
+use crate::database::ServerDatabase;
+use crate::playlist::MediaMetadata;
+use sqlx::{query, query_as, query_as_with, Arguments, FromRow, Sqlite, Transaction};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, FromRow)]
+pub struct TaskMediaMetadata {
+    pub task_id: Uuid,
+    #[sqlx(flatten)]
+    pub metadata: MediaMetadata,
+}
+
+impl ServerDatabase {
+    pub(super) async fn set_task_metadata(
+        tx: &mut Transaction<'_, Sqlite>,
+        task_id: Uuid,
+        metadata: &MediaMetadata,
+    ) -> anyhow::Result<()> {
+        query!(
+            r#"
+            INSERT OR REPLACE INTO media_metadata
+                (task_id, extractor, duration, channel, published_at, view_count, like_count, description, chapters, tags)
+            VALUES
+                (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+            "#,
+            task_id,
+            metadata.extractor,
+            metadata.duration,
+            metadata.channel,
+            metadata.published_at,
+            metadata.view_count,
+            metadata.like_count,
+            metadata.description,
+            metadata.chapters,
+            metadata.tags,
+        )
+        .execute(&mut **tx)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get_job_metadata(
+        &self,
+        job_id: Uuid,
+    ) -> anyhow::Result<HashMap<Uuid, MediaMetadata>> {
+        let mut args = Self::new_args();
+        args.add(job_id);
+        let rows: Vec<TaskMediaMetadata> = query_as_with(
+            r#"
+            SELECT media_metadata.* FROM media_metadata
+            INNER JOIN tasks ON tasks.task_id = media_metadata.task_id
+            WHERE tasks.owner_job_id = ?1
+            "#,
+            args,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.into_iter().map(|x| (x.task_id, x.metadata)).collect())
+    }
+
+    pub async fn get_all_metadata(&self) -> anyhow::Result<HashMap<Uuid, MediaMetadata>> {
+        let rows: Vec<TaskMediaMetadata> = query_as(r#"SELECT * FROM media_metadata"#)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows.into_iter().map(|x| (x.task_id, x.metadata)).collect())
+    }
+}
@@ -1,23 +1,54 @@
 use crate::auth::User;
 use crate::filesystem::FilesystemDriver;
-use crate::job::{Job, JobStatus};
+use crate::job::{Job, JobStatus, SelectionRules};
+use crate::metrics::{HistogramVec, ServerMetrics};
 use crate::queue_command::QueueCommand;
-use crate::task::{Task, TaskStatus};
+use crate::task::{
+    HookFailure, Task, TaskArtifact, TaskFailure, TaskKind, TaskStatus, WorkerPool,
+};
 use crate::web_api::NewJobForm;
+use crate::webhooks::{WebhookDelivery, WebhookDeliveryStatus};
 use anyhow::Context;
 use chrono::{DateTime, Utc};
+pub use backup::{is_database_snapshot, restore_snapshot, DatabaseExport};
+pub use history::{HistoryBucket, HistoryStats};
+pub use import_jobs::ImportedJobs;
+pub use playlist_entries::PlaylistEntry;
+pub use server_lock::ServerLock;
 use rand::{RngCore, SeedableRng};
 use rand_chacha::ChaCha20Rng;
 use rocket::serde::{Deserialize, Serialize};
 use sqlx::sqlite::{SqliteArguments, SqliteConnectOptions, SqlitePoolOptions};
-use sqlx::{query, query_as, query_as_with, ConnectOptions, Pool, Sqlite};
+use sqlx::types::Json;
+use sqlx::{query, query_as, query_as_with, ConnectOptions, Pool, Sqlite, Transaction};
 use sqlx::{Arguments, FromRow};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::default::Default;
 use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Instant;
 use tokio::sync::Mutex;
 use uuid::Uuid;
 
+mod backup;
+mod history;
+mod import_jobs;
+mod media_metadata;
+mod playlist_entries;
+mod server_lock;
+mod server_metrics;
+
+const ACQUIRE_PAGE_SIZE: u32 = 100;
+
+// What `acquire_tasks` does with a waiting task it comes across.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AcquireDecision {
+    Take,
+    Skip,
+    // Nothing further down the queue could be taken either
+    Stop,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct JobFetch {
     pub job_id: Uuid,
@@ -27,6 +58,18 @@ pub struct JobFetch {
     pub created_at: DateTime<Utc>,
     pub prioritized: bool,
     pub title: String,
+    pub credentials_profile: Option<String>,
+    pub proxy: Option<String>,
+    pub transcode_preset: Option<String>,
+    #[serde(default)]
+    pub is_group: bool,
+    #[serde(default)]
+    pub playlist_path: Option<String>,
+    #[serde(default)]
+    pub preview: bool,
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub selection: SelectionRules,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -39,10 +82,18 @@ pub struct TaskStats {
     pub num_failed: i32,
 }
 
+#[derive(Debug, Clone, FromRow)]
+pub struct TaskCount {
+    pub status: TaskStatus,
+    pub kind: TaskKind,
+    pub count: i64,
+}
+
 #[derive(Debug)]
 pub struct ServerDatabase {
     pool: Pool<Sqlite>,
     rng: Mutex<ChaCha20Rng>,
+    query_durations: Arc<HistogramVec>,
 }
 
 pub struct PendingOperations {
@@ -64,6 +115,40 @@ impl ServerDatabase {
             .create_if_missing(true)
             .disable_statement_logging();
 
+        let res = Self::connect(options).await?;
+        res.init_tables().await?;
+        res.backfill_history().await?;
+        res.reset_state().await?;
+        Ok(res)
+    }
+
+    // For looking at the database without changing anything in it,
+    // e.g. while the server is running. It has to be up to date already.
+    pub async fn open_read_only(fs: &FilesystemDriver) -> anyhow::Result<Self> {
+        let path_string = fs.get_database_file().to_string_lossy();
+        let options = SqliteConnectOptions::from_str(&path_string)?
+            .read_only(true)
+            .disable_statement_logging();
+
+        let res = Self::connect(options).await?;
+        let latest_version = sqlx::migrate!("./migrations")
+            .iter()
+            .map(|x| x.version)
+            .max()
+            .unwrap_or_default();
+        let (version,): (Option<i64>,) = query_as("SELECT MAX(version) FROM _sqlx_migrations")
+            .fetch_one(&res.pool)
+            .await
+            .map_err(|_| anyhow!("file is not a database of this server"))?;
+        if version.unwrap_or_default() < latest_version {
+            return Err(anyhow!(
+                "database is out of date, start the server once to upgrade it"
+            ));
+        }
+        Ok(res)
+    }
+
+    async fn connect(options: SqliteConnectOptions) -> anyhow::Result<Self> {
         let pool = SqlitePoolOptions::new()
             .max_connections(3)
             .connect_with(options)
@@ -71,10 +156,13 @@ impl ServerDatabase {
 
         let rng = Mutex::new(ChaCha20Rng::from_entropy());
 
-        let res = Self { pool, rng };
-        res.init_tables().await?;
-        res.reset_state().await?;
-        Ok(res)
+        let query_durations = Arc::new(ServerMetrics::new_db_query_durations());
+
+        Ok(Self {
+            pool,
+            rng,
+            query_durations,
+        })
     }
 
     async fn init_tables(&self) -> anyhow::Result<()> {
@@ -95,10 +183,11 @@ impl ServerDatabase {
     async fn reset_state(&self) -> anyhow::Result<()> {
         let tx = self.pool.begin().await?;
 
+        // Tasks still processing weren't stopped properly, most likely
+        // the server has crashed. Their partial data is kept for retry.
         sqlx::query!(
-            r#"UPDATE tasks SET status = ?1, finished_at = started_at WHERE status = ?2 OR status = ?3"#,
+            r#"UPDATE tasks SET status = ?1, finished_at = started_at, is_resumed = true WHERE status = ?2"#,
             TaskStatus::Failed,
-            TaskStatus::Waiting,
             TaskStatus::Processing,
         )
             .execute(&self.pool)
@@ -108,27 +197,222 @@ impl ServerDatabase {
         Ok(())
     }
 
-    fn new_args() -> SqliteArguments<'static> {
-        SqliteArguments::default()
+    // Tasks paused on shutdown either continue or stay paused until
+    // the user resumes them.
+    pub async fn restore_interrupted_tasks(&self, auto_resume: bool) -> anyhow::Result<()> {
+        let tx = self.pool.begin().await?;
+
+        if auto_resume {
+            sqlx::query!(
+                r#"
+                UPDATE tasks
+                SET status = ?1, is_resumed = true
+                WHERE interrupted = true AND status = ?2
+                "#,
+                TaskStatus::Waiting,
+                TaskStatus::Paused,
+            )
+            .execute(&self.pool)
+            .await?;
+        }
+        sqlx::query!(
+            r#"
+            UPDATE tasks
+            SET interrupted = false
+            WHERE interrupted = true
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+
+        tx.commit().await?;
+        Ok(())
     }
 
-    pub async fn acquire_tasks(&self, max_tasks: u32) -> anyhow::Result<Vec<Task>> {
-        let tx = self.pool.begin().await?;
+    pub async fn mark_task_interrupted(&self, task_id: Uuid) -> anyhow::Result<()> {
+        query!(
+            r#"
+            UPDATE tasks
+            SET interrupted = true
+            WHERE task_id = ?1
+            "#,
+            task_id,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    // Remembers why the task has failed, or forgets it if it hasn't.
+    // Partial data of the failed run is kept for the retry only if the
+    // failure didn't have anything to do with the data itself, same as
+    // for tasks interrupted by a crash.
+    pub async fn set_task_failure(
+        &self,
+        task_id: Uuid,
+        failure: Option<&TaskFailure>,
+    ) -> anyhow::Result<()> {
+        let kind = failure.map(|x| x.kind);
+        let message = failure.map(|x| x.message.as_str());
+        let keep_partial_data = failure.map(|x| x.kind.keeps_partial_data());
+        query!(
+            r#"
+            UPDATE tasks
+            SET failure_kind = ?2, failure_message = ?3, is_resumed = COALESCE(?4, is_resumed)
+            WHERE task_id = ?1
+            "#,
+            task_id,
+            kind,
+            message,
+            keep_partial_data,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn set_task_hook_failures(
+        &self,
+        task_id: Uuid,
+        failures: &[HookFailure],
+    ) -> anyhow::Result<()> {
+        let failures = (!failures.is_empty()).then_some(Json(failures));
+        query!(
+            r#"
+            UPDATE tasks
+            SET hook_failures = ?2
+            WHERE task_id = ?1
+            "#,
+            task_id,
+            failures,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
 
+    // Failed tasks that still have automatic retries left, and have
+    // waited long enough since the last attempt. Tasks failed only because
+    // of their dependency come back along with it instead.
+    pub async fn get_tasks_to_retry(
+        &self,
+        max_retries: u32,
+        failed_before: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<Task>> {
         let mut args = Self::new_args();
-        args.add(TaskStatus::Waiting);
-        args.add(max_tasks);
+        args.add(TaskStatus::Failed);
+        args.add(max_retries);
+        args.add(failed_before);
+        args.add(TaskStatus::Done);
         let tasks: Vec<Task> = query_as_with(
             r#"
-        SELECT * FROM tasks
-        WHERE status = ?1 AND pending_delete = false
-        ORDER BY created_at
-        LIMIT ?2
+        SELECT tasks.* FROM tasks
+        LEFT JOIN tasks AS dependency ON tasks.depends_on = dependency.task_id
+        WHERE tasks.status = ?1 AND tasks.num_retries < ?2 AND tasks.finished_at <= ?3
+            AND tasks.pending_delete = false
+            AND (dependency.task_id IS NULL OR dependency.status = ?4)
+        ORDER BY tasks.finished_at
         "#,
             args,
         )
         .fetch_all(&self.pool)
         .await?;
+        Ok(tasks)
+    }
+
+    pub async fn retry_task_automatically(&self, task_id: Uuid) -> anyhow::Result<()> {
+        let mut tx = self.pool.begin().await?;
+        query!(
+            r#"
+            UPDATE tasks
+            SET status = ?2, num_retries = num_retries + 1
+            WHERE task_id = ?1 AND status = ?3
+            "#,
+            task_id,
+            TaskStatus::Waiting,
+            TaskStatus::Failed,
+        )
+        .execute(&mut *tx)
+        .await?;
+        Self::retry_dependents(&mut tx, task_id).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    pub fn get_query_durations(&self) -> Arc<HistogramVec> {
+        self.query_durations.clone()
+    }
+
+    fn new_args() -> SqliteArguments<'static> {
+        SqliteArguments::default()
+    }
+
+    // Picks up to `max_tasks` waiting tasks with their dependency done,
+    // in order of creation. Only tasks running in one of `pools` are
+    // looked at, `filter` decides about each of them.
+    pub async fn acquire_tasks<F: FnMut(&Task) -> AcquireDecision>(
+        &self,
+        max_tasks: u32,
+        pools: &[WorkerPool],
+        mut filter: F,
+    ) -> anyhow::Result<Vec<Task>> {
+        let started = Instant::now();
+        let tx = self.pool.begin().await?;
+
+        let kinds: Vec<TaskKind> = TaskKind::ALL
+            .into_iter()
+            .filter(|x| pools.contains(&x.worker_pool()))
+            .collect();
+        let kind_params = (0..kinds.len())
+            .map(|idx| format!("?{}", idx + 5))
+            .collect::<Vec<String>>()
+            .join(", ");
+        let sql = format!(
+            r#"
+            SELECT tasks.* FROM tasks
+            LEFT JOIN tasks AS dependency ON tasks.depends_on = dependency.task_id
+            WHERE tasks.status = ?1 AND tasks.pending_delete = false
+                AND (dependency.task_id IS NULL OR dependency.status = ?2)
+                AND tasks.kind IN ({kind_params})
+            ORDER BY tasks.created_at
+            LIMIT ?3 OFFSET ?4
+            "#
+        );
+
+        // Skipped tasks don't count towards the limit, so the queue is read
+        // a page at a time until enough of them have been found.
+        let mut tasks: Vec<Task> = vec![];
+        let mut offset = 0;
+        'pages: while max_tasks > 0 && !kinds.is_empty() {
+            // Dependency that's been deleted doesn't hold anything back
+            let mut args = Self::new_args();
+            args.add(TaskStatus::Waiting);
+            args.add(TaskStatus::Done);
+            args.add(ACQUIRE_PAGE_SIZE);
+            args.add(offset);
+            for kind in &kinds {
+                args.add(*kind);
+            }
+            let page: Vec<Task> = query_as_with(&sql, args).fetch_all(&self.pool).await?;
+            let is_last_page = page.len() < ACQUIRE_PAGE_SIZE as usize;
+            offset += ACQUIRE_PAGE_SIZE;
+
+            for task in page {
+                match filter(&task) {
+                    AcquireDecision::Take => {
+                        tasks.push(task);
+                        if tasks.len() == max_tasks as usize {
+                            break 'pages;
+                        }
+                    }
+                    AcquireDecision::Skip => {}
+                    AcquireDecision::Stop => break 'pages,
+                }
+            }
+            if is_last_page {
+                break;
+            }
+        }
 
         let started_at = Utc::now();
 
@@ -147,10 +431,12 @@ impl ServerDatabase {
         }
 
         tx.commit().await?;
+        self.query_durations.observe("acquire_tasks", started.elapsed());
         Ok(tasks)
     }
 
     pub async fn get_global_task_stats(&self) -> anyhow::Result<TaskStats> {
+        let started = Instant::now();
         let mut args = Self::new_args();
         args.add(TaskStatus::Waiting);
         args.add(TaskStatus::Cancelled);
@@ -172,6 +458,7 @@ impl ServerDatabase {
         )
         .fetch_one(&self.pool)
         .await?;
+        self.query_durations.observe("get_global_task_stats", started.elapsed());
         Ok(TaskStats {
             num_total: ret.0,
             num_active: ret.5,
@@ -182,6 +469,19 @@ impl ServerDatabase {
         })
     }
 
+    pub async fn get_task_counts(&self) -> anyhow::Result<Vec<TaskCount>> {
+        let counts: Vec<TaskCount> = query_as(
+            r#"
+            SELECT status, kind, COUNT(*) AS count FROM tasks
+            WHERE pending_delete = false
+            GROUP BY status, kind
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(counts)
+    }
+
     pub async fn get_job_task_stats(&self, job_id: Uuid) -> anyhow::Result<TaskStats> {
         let mut args = Self::new_args();
         args.add(TaskStatus::Waiting);
@@ -258,6 +558,26 @@ impl ServerDatabase {
         })
     }
 
+    // All tasks except the ones that have failed before `failed_before`.
+    pub async fn get_tasks_owning_worker_dirs(
+        &self,
+        failed_before: Option<DateTime<Utc>>,
+    ) -> anyhow::Result<HashSet<Uuid>> {
+        let mut args = Self::new_args();
+        args.add(TaskStatus::Failed);
+        args.add(failed_before);
+        let tasks: Vec<(Uuid,)> = query_as_with(
+            r#"
+            SELECT task_id FROM tasks
+            WHERE ?2 IS NULL OR status != ?1 OR COALESCE(finished_at, created_at) >= ?2
+            "#,
+            args,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(tasks.into_iter().map(|(task_id,)| task_id).collect())
+    }
+
     pub async fn confirm_deletion(&self, tasks: &[Uuid]) -> anyhow::Result<()> {
         let tx = self.pool.begin().await?;
 
@@ -312,7 +632,7 @@ impl ServerDatabase {
     }
 
     pub async fn create_job(&self, new_job: &NewJobForm) -> anyhow::Result<Job> {
-        let tx = self.pool.begin().await?;
+        let mut tx = self.pool.begin().await?;
 
         // FIXME: possible id collisions
         let job_id = Uuid::new_v4();
@@ -331,34 +651,60 @@ impl ServerDatabase {
             progress: Default::default(),
             prioritized: false,
             title: "...".to_string(),
+            credentials_profile: new_job.credentials_profile.clone(),
+            proxy: new_job.proxy.clone(),
+            transcode_preset: new_job.transcode_preset.clone(),
+            is_group: false,
+            playlist_path: None,
+            preview: new_job.is_preview(),
+            selection: new_job.selection_rules(),
+            groups: vec![],
         };
 
+        // FIXME: possible id collisions
+        job.tasks.push(Task::new_fetch_url_contents(&job));
+        Self::insert_job(&mut tx, &job).await?;
+        job.status = Job::status_from_tasks(&job.tasks);
+
+        tx.commit().await?;
+        Ok(job)
+    }
+
+    // Job is inserted along with its tasks, in their order.
+    async fn insert_job(tx: &mut Transaction<'_, Sqlite>, job: &Job) -> anyhow::Result<()> {
         sqlx::query!(
             r#"
-                INSERT INTO jobs
-                    (job_id, thumbnail, url, format, created_at, title)
-                VALUES
-                    (?1, ?2, ?3, ?4, ?5, ?6)
-                "#,
+            INSERT INTO jobs
+                (job_id, thumbnail, url, format, created_at, title, credentials_profile, proxy, transcode_preset, is_group, preview, select_first, select_newest, select_title)
+            VALUES
+                (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)
+            "#,
             job.job_id,
             job.thumbnail,
             job.url,
             job.format,
             job.created_at,
             job.title,
+            job.credentials_profile,
+            job.proxy,
+            job.transcode_preset,
+            job.is_group,
+            job.preview,
+            job.selection.select_first,
+            job.selection.select_newest,
+            job.selection.select_title,
         )
-        .execute(&self.pool)
+        .execute(&mut **tx)
         .await?;
 
-        // FIXME: possible id collisions
-        let task = Task::new_fetch_url_contents(&job);
-
-        sqlx::query!(
+        for (task_index, task) in job.tasks.iter().enumerate() {
+            let task_index = task_index as i64;
+            sqlx::query!(
                 r#"
                 INSERT INTO tasks
-                    (task_id, status, kind, thumbnail, owner_job_id, url, format, created_at, finished_at, task_index, title)
+                    (task_id, status, kind, thumbnail, owner_job_id, url, format, created_at, finished_at, task_index, title, credentials_profile, proxy, transcode_preset)
                 VALUES
-                    (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+                    (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)
                 "#,
                 task.task_id,
                 task.status,
@@ -369,17 +715,16 @@ impl ServerDatabase {
                 task.format,
                 task.created_at,
                 task.finished_at,
-                0,
-                task.title
+                task_index,
+                task.title,
+                task.credentials_profile,
+                task.proxy,
+                task.transcode_preset,
             )
-            .execute(&self.pool)
+            .execute(&mut **tx)
             .await?;
-
-        job.tasks.push(task);
-        job.status = Job::status_from_tasks(&job.tasks);
-
-        tx.commit().await?;
-        Ok(job)
+        }
+        Ok(())
     }
 
     pub async fn modify_all_jobs(&self, command: QueueCommand) -> anyhow::Result<()> {
@@ -391,8 +736,9 @@ impl ServerDatabase {
     }
 
     pub async fn modify_job(&self, job_id: Uuid, command: QueueCommand) -> anyhow::Result<()> {
+        let started = Instant::now();
         let finished_at = Utc::now();
-        let tx = self.pool.begin().await?;
+        let mut tx = self.pool.begin().await?;
 
         match command {
             QueueCommand::Pause => {
@@ -443,7 +789,7 @@ impl ServerDatabase {
                 sqlx::query!(
                     r#"
                     UPDATE tasks
-                    SET status = ?2
+                    SET status = ?2, num_retries = 0
                     WHERE owner_job_id = ?1 AND (status = ?3 OR status = ?4)
                     "#,
                     job_id,
@@ -475,63 +821,204 @@ impl ServerDatabase {
                     r#"
                     UPDATE jobs
                     SET title = ?2, thumbnail = ?3
-                    WHERE job_id = ?1
+                    WHERE job_id = ?1 AND is_group = false
                     "#,
                     job_id,
                     job_title,
                     job_thumbnail,
                 )
-                .execute(&self.pool)
+                .execute(&mut *tx)
                 .await?;
 
-                let mut args = Self::new_args();
-                args.add(job_id);
-                let (mut last_task_index,): (i32,) = sqlx::query_as_with(
-                    r#"
-                    SELECT MAX(task_index) FROM tasks
-                    WHERE owner_job_id = ?1
-                    "#,
-                    args,
-                )
-                .fetch_one(&self.pool)
-                .await?;
-
-                for task in new_tasks {
-                    last_task_index += 1;
-                    sqlx::query!(
-                        r#"
-                        INSERT INTO tasks
-                            (task_id, status, kind, thumbnail, owner_job_id, url, format, created_at, finished_at, task_index, title)
-                        VALUES
-                            (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
-                        "#,
-                        task.task_id,
-                        task.status,
-                        task.kind,
-                        task.thumbnail,
-                        task.owner_job_id,
-                        task.url,
-                        task.format,
-                        task.created_at,
-                        task.finished_at,
-                        last_task_index,
-                        task.title
-                    )
-                        .execute(&self.pool)
-                        .await?;
-                }
+                Self::insert_tasks(&mut tx, job_id, &new_tasks).await?;
+            }
+            QueueCommand::TasksAdded(new_tasks) => {
+                Self::insert_tasks(&mut tx, job_id, &new_tasks).await?;
             }
             _ => {}
         }
 
         tx.commit().await?;
 
+        self.query_durations.observe("modify_job", started.elapsed());
         Ok(())
     }
 
+    // Appends tasks to the end of job.
+    async fn insert_tasks(
+        tx: &mut Transaction<'_, Sqlite>,
+        job_id: Uuid,
+        new_tasks: &[Task],
+    ) -> anyhow::Result<()> {
+        let mut args = Self::new_args();
+        args.add(job_id);
+        let (mut last_task_index,): (i32,) = sqlx::query_as_with(
+            r#"
+            SELECT MAX(task_index) FROM tasks
+            WHERE owner_job_id = ?1
+            "#,
+            args,
+        )
+        .fetch_one(&mut **tx)
+        .await?;
+
+        for task in new_tasks {
+            last_task_index += 1;
+            sqlx::query!(
+                r#"
+                INSERT INTO tasks
+                    (task_id, status, kind, thumbnail, owner_job_id, url, format, created_at, finished_at, task_index, title, bytes_estimate, credentials_profile, proxy, transcode_preset, source_path, depends_on, uploader)
+                VALUES
+                    (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18)
+                "#,
+                task.task_id,
+                task.status,
+                task.kind,
+                task.thumbnail,
+                task.owner_job_id,
+                task.url,
+                task.format,
+                task.created_at,
+                task.finished_at,
+                last_task_index,
+                task.title,
+                task.bytes_estimate,
+                task.credentials_profile,
+                task.proxy,
+                task.transcode_preset,
+                task.source_path,
+                task.depends_on,
+                task.uploader
+            )
+            .execute(&mut **tx)
+            .await?;
+            if let Some(metadata) = &task.metadata {
+                Self::set_task_metadata(tx, task.task_id, metadata).await?;
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn set_job_playlist_path(
+        &self,
+        job_id: Uuid,
+        playlist_path: &str,
+    ) -> anyhow::Result<()> {
+        query!(
+            r#"
+            UPDATE jobs
+            SET playlist_path = ?2
+            WHERE job_id = ?1
+            "#,
+            job_id,
+            playlist_path,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn set_task_output(
+        &self,
+        task_id: Uuid,
+        output_path: &str,
+        output_size: i64,
+    ) -> anyhow::Result<()> {
+        query!(
+            r#"
+            UPDATE tasks
+            SET output_path = ?2, output_size = ?3
+            WHERE task_id = ?1
+            "#,
+            task_id,
+            output_path,
+            output_size,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    // Replaces whatever was recorded by previous attempts.
+    pub async fn set_task_artifacts(
+        &self,
+        task_id: Uuid,
+        artifacts: &[TaskArtifact],
+    ) -> anyhow::Result<()> {
+        let mut tx = self.pool.begin().await?;
+        query!(
+            r#"
+            DELETE FROM task_artifacts
+            WHERE task_id = ?1
+            "#,
+            task_id,
+        )
+        .execute(&mut *tx)
+        .await?;
+        for artifact in artifacts {
+            query!(
+                r#"
+                INSERT INTO task_artifacts
+                    (task_id, kind, path)
+                VALUES
+                    (?1, ?2, ?3)
+                "#,
+                task_id,
+                artifact.kind,
+                artifact.path,
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    pub async fn get_task_artifacts(&self, task_id: Uuid) -> anyhow::Result<Vec<TaskArtifact>> {
+        let mut args = Self::new_args();
+        args.add(task_id);
+        let artifacts: Vec<TaskArtifact> = query_as_with(
+            r#"
+            SELECT * FROM task_artifacts
+            WHERE task_id = ?1
+            ORDER BY rowid
+            "#,
+            args,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(artifacts)
+    }
+
+    // Returns the thumbnail URL along with the proxy to fetch it through.
+    // Fetch task stands for its whole job, so it gets the job's thumbnail.
+    pub async fn get_thumbnail_source(
+        &self,
+        task_id: Uuid,
+    ) -> anyhow::Result<Option<(String, Option<String>)>> {
+        let mut args = Self::new_args();
+        args.add(task_id);
+        args.add(TaskKind::FetchUrlContents);
+        let source: Option<(String, Option<String>)> = query_as_with(
+            r#"
+            SELECT
+                CASE WHEN tasks.kind = ?2 AND jobs.thumbnail != '' THEN jobs.thumbnail ELSE tasks.thumbnail END,
+                tasks.proxy
+            FROM tasks
+            INNER JOIN jobs ON jobs.job_id = tasks.owner_job_id
+            WHERE tasks.task_id = ?1
+            "#,
+            args,
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(source.filter(|(url, _)| !url.is_empty()))
+    }
+
     pub async fn modify_task(&self, task_id: Uuid, command: QueueCommand) -> anyhow::Result<()> {
+        let started = Instant::now();
         let finished_at = Utc::now();
-        let tx = self.pool.begin().await?;
+        let mut tx = self.pool.begin().await?;
 
         match command {
             QueueCommand::Pause => {
@@ -552,7 +1039,7 @@ impl ServerDatabase {
                 sqlx::query!(
                     r#"
                     UPDATE tasks
-                    SET status = ?2
+                    SET status = ?2, is_resumed = true
                     WHERE task_id = ?1 AND status = ?3
                     "#,
                     task_id,
@@ -577,12 +1064,14 @@ impl ServerDatabase {
                 )
                 .execute(&self.pool)
                 .await?;
+                self.finish_dependents(task_id, TaskStatus::Cancelled)
+                    .await?;
             }
             QueueCommand::Retry => {
                 sqlx::query!(
                     r#"
                     UPDATE tasks
-                    SET status = ?2
+                    SET status = ?2, num_retries = 0
                     WHERE task_id = ?1 AND (status = ?3 OR status = ?4)
                     "#,
                     task_id,
@@ -590,8 +1079,9 @@ impl ServerDatabase {
                     TaskStatus::Failed,
                     TaskStatus::Cancelled,
                 )
-                .execute(&self.pool)
+                .execute(&mut *tx)
                 .await?;
+                Self::retry_dependents(&mut tx, task_id).await?;
             }
             QueueCommand::Delete => {
                 sqlx::query!(
@@ -627,16 +1117,75 @@ impl ServerDatabase {
                 )
                 .execute(&self.pool)
                 .await?;
+                if status == TaskStatus::Failed || status == TaskStatus::Cancelled {
+                    self.finish_dependents(task_id, status).await?;
+                }
             }
             _ => {}
         }
 
         tx.commit().await?;
 
+        self.query_durations.observe("modify_task", started.elapsed());
+        Ok(())
+    }
+
+    // Tasks that can't run anymore because `task_id` won't be done
+    // end up with the same status.
+    async fn finish_dependents(&self, task_id: Uuid, status: TaskStatus) -> anyhow::Result<()> {
+        let finished_at = Utc::now();
+        sqlx::query!(
+            r#"
+            WITH RECURSIVE dependents(task_id) AS (
+                SELECT task_id FROM tasks WHERE depends_on = ?1
+                UNION
+                SELECT tasks.task_id FROM tasks
+                JOIN dependents ON tasks.depends_on = dependents.task_id
+            )
+            UPDATE tasks
+            SET status = ?2, finished_at = ?3
+            WHERE task_id IN (SELECT task_id FROM dependents) AND (status = ?4 OR status = ?5)
+            "#,
+            task_id,
+            status,
+            finished_at,
+            TaskStatus::Waiting,
+            TaskStatus::Paused,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    // Undoes `finish_dependents` when `task_id` gets another chance.
+    async fn retry_dependents(
+        tx: &mut Transaction<'_, Sqlite>,
+        task_id: Uuid,
+    ) -> anyhow::Result<()> {
+        sqlx::query!(
+            r#"
+            WITH RECURSIVE dependents(task_id) AS (
+                SELECT task_id FROM tasks WHERE depends_on = ?1
+                UNION
+                SELECT tasks.task_id FROM tasks
+                JOIN dependents ON tasks.depends_on = dependents.task_id
+            )
+            UPDATE tasks
+            SET status = ?2
+            WHERE task_id IN (SELECT task_id FROM dependents) AND (status = ?3 OR status = ?4)
+            "#,
+            task_id,
+            TaskStatus::Waiting,
+            TaskStatus::Failed,
+            TaskStatus::Cancelled,
+        )
+        .execute(&mut **tx)
+        .await?;
         Ok(())
     }
 
     pub async fn get_job(&self, job_id: Uuid) -> anyhow::Result<Job> {
+        let started = Instant::now();
         let mut args = Self::new_args();
         args.add(job_id);
         let job: JobFetch = query_as_with(
@@ -651,20 +1200,28 @@ impl ServerDatabase {
 
         let mut args = Self::new_args();
         args.add(job_id);
-        let tasks: Vec<Task> = query_as_with(
+        let mut tasks: Vec<Task> = query_as_with(
             r#"
             SELECT * FROM tasks
             WHERE owner_job_id = ?1
+            ORDER BY task_index
             "#,
             args,
         )
         .fetch_all(&self.pool)
         .await?;
 
+        let mut metadata = self.get_job_metadata(job_id).await?;
+        for task in &mut tasks {
+            task.metadata = metadata.remove(&task.task_id);
+        }
+
+        self.query_durations.observe("get_job", started.elapsed());
         Ok(Job::new(job, tasks))
     }
 
     pub async fn get_all_jobs(&self) -> anyhow::Result<Vec<Job>> {
+        let started = Instant::now();
         let jobs: Vec<JobFetch> = query_as(r#"SELECT * FROM jobs ORDER BY created_at DESC"#)
             .fetch_all(&self.pool)
             .await?;
@@ -673,8 +1230,10 @@ impl ServerDatabase {
             .fetch_all(&self.pool)
             .await?;
 
+        let mut metadata = self.get_all_metadata().await?;
         let mut tasks_sorted = HashMap::<Uuid, Vec<Task>>::new();
-        for task in tasks {
+        for mut task in tasks {
+            task.metadata = metadata.remove(&task.task_id);
             if let Some(v) = tasks_sorted.get_mut(&task.owner_job_id) {
                 v.push(task);
             } else {
@@ -682,6 +1241,7 @@ impl ServerDatabase {
             }
         }
 
+        self.query_durations.observe("get_all_jobs", started.elapsed());
         Ok(jobs
             .into_iter()
             .map(|x| {
@@ -801,4 +1361,301 @@ impl ServerDatabase {
         .await?;
         Ok(ret)
     }
+
+    pub async fn add_webhook_deliveries(&self, deliveries: &[WebhookDelivery]) -> anyhow::Result<()> {
+        let mut tx = self.pool.begin().await?;
+        for delivery in deliveries {
+            query!(
+                r#"
+                INSERT INTO webhook_deliveries
+                    (delivery_id, url, event, payload, status, num_attempts, created_at, next_attempt_at, delivered_at, last_error)
+                VALUES
+                    (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+                "#,
+                delivery.delivery_id,
+                delivery.url,
+                delivery.event,
+                delivery.payload,
+                delivery.status,
+                delivery.num_attempts,
+                delivery.created_at,
+                delivery.next_attempt_at,
+                delivery.delivered_at,
+                delivery.last_error,
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    pub async fn get_due_webhook_deliveries(
+        &self,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<WebhookDelivery>> {
+        let mut args = Self::new_args();
+        args.add(WebhookDeliveryStatus::Pending);
+        args.add(now);
+        let deliveries: Vec<WebhookDelivery> = query_as_with(
+            r#"
+            SELECT * FROM webhook_deliveries
+            WHERE status = ?1 AND next_attempt_at <= ?2
+            ORDER BY created_at
+            "#,
+            args,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(deliveries)
+    }
+
+    pub async fn update_webhook_delivery(&self, delivery: &WebhookDelivery) -> anyhow::Result<()> {
+        query!(
+            r#"
+            UPDATE webhook_deliveries
+            SET status = ?2, num_attempts = ?3, next_attempt_at = ?4, delivered_at = ?5, last_error = ?6
+            WHERE delivery_id = ?1
+            "#,
+            delivery.delivery_id,
+            delivery.status,
+            delivery.num_attempts,
+            delivery.next_attempt_at,
+            delivery.delivered_at,
+            delivery.last_error,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    // Pending deliveries are kept regardless of age.
+    pub async fn prune_webhook_deliveries(&self, before: DateTime<Utc>) -> anyhow::Result<u64> {
+        let pending = WebhookDeliveryStatus::Pending;
+        let result = query!(
+            r#"
+            DELETE FROM webhook_deliveries
+            WHERE status != ?1 AND created_at < ?2
+            "#,
+            pending,
+            before,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    pub async fn get_recent_webhook_deliveries(
+        &self,
+        limit: u32,
+    ) -> anyhow::Result<Vec<WebhookDelivery>> {
+        let mut args = Self::new_args();
+        args.add(limit);
+        let deliveries: Vec<WebhookDelivery> = query_as_with(
+            r#"
+            SELECT * FROM webhook_deliveries
+            ORDER BY created_at DESC
+            LIMIT ?1
+            "#,
+            args,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(deliveries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::playlist::{MediaMetadata, NestedPlaylist, VideoInfo};
+    use crate::task::FailureKind;
+    use crate::test_util::{new_job_form, new_test_server, TempDir};
+
+    async fn get_task(db: &ServerDatabase, task_id: Uuid) -> Task {
+        let mut args = ServerDatabase::new_args();
+        args.add(task_id);
+        query_as_with("SELECT * FROM tasks WHERE task_id = ?1", args)
+            .fetch_one(&db.pool)
+            .await
+            .unwrap()
+    }
+
+    async fn fail_task(db: &ServerDatabase, task_id: Uuid, kind: FailureKind) {
+        db.modify_task(task_id, QueueCommand::TaskStatusChange(TaskStatus::Failed))
+            .await
+            .unwrap();
+        db.set_task_failure(task_id, Some(&TaskFailure::new(kind, "failed")))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn retried_task_keeps_partial_data_of_transient_failure() {
+        let dir = TempDir::new();
+        let (_, db) = new_test_server(&dir, |_| {}).await;
+        let db = db.lock().await;
+        let job = db
+            .create_job(&new_job_form("https://example.com/a"))
+            .await
+            .unwrap();
+        let task_id = job.tasks[0].task_id;
+
+        fail_task(&db, task_id, FailureKind::NetworkError).await;
+        db.retry_task_automatically(task_id).await.unwrap();
+        let task = get_task(&db, task_id).await;
+        assert_eq!(task.status, TaskStatus::Waiting);
+        assert_eq!(task.num_retries, 1);
+        assert!(task.is_resumed);
+
+        fail_task(&db, task_id, FailureKind::Unknown).await;
+        db.retry_task_automatically(task_id).await.unwrap();
+        let task = get_task(&db, task_id).await;
+        assert_eq!(task.num_retries, 2);
+        assert!(!task.is_resumed);
+    }
+
+    #[tokio::test]
+    async fn automatic_retry_brings_back_dependents() {
+        let dir = TempDir::new();
+        let (_, db) = new_test_server(&dir, |_| {}).await;
+        let db = db.lock().await;
+        let job = db
+            .create_job(&new_job_form("https://example.com/a"))
+            .await
+            .unwrap();
+        let task = &job.tasks[0];
+        let playlist = NestedPlaylist {
+            url: "https://example.com/b".to_string(),
+            title: "b".to_string(),
+        };
+        let dependent = Task::new_nested_fetch(task, &playlist);
+        db.modify_job(
+            job.job_id,
+            QueueCommand::TasksAdded(vec![dependent.clone()]),
+        )
+        .await
+        .unwrap();
+
+        fail_task(&db, task.task_id, FailureKind::Throttled).await;
+        assert_eq!(
+            get_task(&db, dependent.task_id).await.status,
+            TaskStatus::Failed
+        );
+        db.retry_task_automatically(task.task_id).await.unwrap();
+        assert_eq!(
+            get_task(&db, task.task_id).await.status,
+            TaskStatus::Waiting
+        );
+        assert_eq!(
+            get_task(&db, dependent.task_id).await.status,
+            TaskStatus::Waiting
+        );
+    }
+
+    #[tokio::test]
+    async fn metadata_is_loaded_with_all_jobs() {
+        let dir = TempDir::new();
+        let (_, db) = new_test_server(&dir, |_| {}).await;
+        let db = db.lock().await;
+        let job = db
+            .create_job(&new_job_form("https://example.com/a"))
+            .await
+            .unwrap();
+        let video = VideoInfo {
+            url: "https://example.com/v".to_string(),
+            thumbnail: None,
+            title: "v".to_string(),
+            bytes_estimate: None,
+            uploader: None,
+            metadata: MediaMetadata {
+                channel: Some("channel".to_string()),
+                view_count: Some(7),
+                ..Default::default()
+            },
+        };
+        let download = Task::new_from_video_info(&job.tasks[0], &video);
+        db.modify_job(job.job_id, QueueCommand::TasksAdded(vec![download.clone()]))
+            .await
+            .unwrap();
+
+        let jobs = db.get_all_jobs().await.unwrap();
+        let task = jobs[0]
+            .tasks
+            .iter()
+            .find(|x| x.task_id == download.task_id)
+            .unwrap();
+        assert_eq!(task.metadata.as_ref(), Some(&video.metadata));
+        assert!(jobs[0].tasks[0].metadata.is_none());
+    }
+
+    #[tokio::test]
+    async fn acquiring_reads_only_free_pools_and_stops_when_told() {
+        let dir = TempDir::new();
+        let (_, db) = new_test_server(&dir, |_| {}).await;
+        let db = db.lock().await;
+        let first = db
+            .create_job(&new_job_form("https://example.com/a"))
+            .await
+            .unwrap();
+        db.create_job(&new_job_form("https://example.com/b"))
+            .await
+            .unwrap();
+        let mut transcode = Task::new_transcode(&first.tasks[0], "/tmp/a.mp4");
+        transcode.depends_on = None;
+        db.modify_job(
+            first.job_id,
+            QueueCommand::TasksAdded(vec![transcode.clone()]),
+        )
+        .await
+        .unwrap();
+
+        let mut seen = vec![];
+        let tasks = db
+            .acquire_tasks(5, &[WorkerPool::Transcode], |task| {
+                seen.push(task.task_id);
+                AcquireDecision::Take
+            })
+            .await
+            .unwrap();
+        assert_eq!(seen, [transcode.task_id]);
+        assert_eq!(tasks.len(), 1);
+
+        let mut seen = vec![];
+        let tasks = db
+            .acquire_tasks(5, &[WorkerPool::Download], |task| {
+                seen.push(task.task_id);
+                AcquireDecision::Stop
+            })
+            .await
+            .unwrap();
+        assert_eq!(seen, [first.tasks[0].task_id]);
+        assert!(tasks.is_empty());
+    }
+
+    #[tokio::test]
+    async fn hook_failures_are_replaced_by_the_latest_run() {
+        let dir = TempDir::new();
+        let (_, db) = new_test_server(&dir, |_| {}).await;
+        let db = db.lock().await;
+        let job = db
+            .create_job(&new_job_form("https://example.com/a"))
+            .await
+            .unwrap();
+        let task_id = job.tasks[0].task_id;
+        assert!(get_task(&db, task_id).await.hook_failures.is_none());
+
+        let failures = vec![HookFailure {
+            name: "notify".to_string(),
+            message: "Process exited with code 1".to_string(),
+        }];
+        db.set_task_hook_failures(task_id, &failures).await.unwrap();
+        let task = get_task(&db, task_id).await;
+        assert_eq!(task.hook_failures.map(|x| x.0), Some(failures));
+        let json = serde_json::to_value(get_task(&db, task_id).await).unwrap();
+        assert_eq!(json["hook_failures"][0]["name"], "notify");
+
+        db.set_task_hook_failures(task_id, &[]).await.unwrap();
+        assert!(get_task(&db, task_id).await.hook_failures.is_none());
+    }
 }
@@ -0,0 +1,204 @@
+use crate::database::ServerDatabase;
+use crate::playlist::VideoInfo;
+use crate::task::{Task, TaskKind};
+use rocket::serde::Serialize;
+use sqlx::{query, query_as_with, Arguments, Executor, FromRow, Sqlite, Transaction};
+use std::collections::BTreeSet;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct PlaylistEntry {
+    pub entry_index: u32,
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub video: VideoInfo,
+    pub selected: bool,
+}
+
+impl ServerDatabase {
+    // Contents of a preview job are kept aside instead of being queued.
+    // Fetching again replaces them, but keeps track of what's been selected.
+    pub async fn store_playlist_entries(
+        &self,
+        job_id: Uuid,
+        job_title: &str,
+        job_thumbnail: &str,
+        videos: &[VideoInfo],
+    ) -> anyhow::Result<()> {
+        let mut tx = self.pool.begin().await?;
+        query!(
+            r#"
+            UPDATE jobs
+            SET title = ?2, thumbnail = ?3
+            WHERE job_id = ?1 AND is_group = false
+            "#,
+            job_id,
+            job_title,
+            job_thumbnail,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let selected: BTreeSet<String> = Self::fetch_playlist_entries(&mut *tx, job_id)
+            .await?
+            .into_iter()
+            .filter(|x| x.selected)
+            .map(|x| x.video.url)
+            .collect();
+        query!(
+            r#"
+            DELETE FROM playlist_entries
+            WHERE job_id = ?1
+            "#,
+            job_id,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        for (idx, video) in videos.iter().enumerate() {
+            let is_selected = selected.contains(&video.url);
+            Self::insert_playlist_entry(&mut tx, job_id, idx as u32 + 1, video, is_selected)
+                .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    // Entries of nested playlists go after the ones already known,
+    // videos listed in several of them are only kept once.
+    pub async fn add_playlist_entries(
+        &self,
+        job_id: Uuid,
+        videos: &[VideoInfo],
+    ) -> anyhow::Result<()> {
+        let mut tx = self.pool.begin().await?;
+        let entries = Self::fetch_playlist_entries(&mut *tx, job_id).await?;
+        let mut known: BTreeSet<String> = entries.iter().map(|x| x.video.url.clone()).collect();
+        let mut entry_index = entries.last().map(|x| x.entry_index).unwrap_or(0);
+        for video in videos {
+            if !known.insert(video.url.clone()) {
+                continue;
+            }
+            entry_index += 1;
+            Self::insert_playlist_entry(&mut tx, job_id, entry_index, video, false).await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn insert_playlist_entry(
+        tx: &mut Transaction<'_, Sqlite>,
+        job_id: Uuid,
+        entry_index: u32,
+        video: &VideoInfo,
+        is_selected: bool,
+    ) -> anyhow::Result<()> {
+        let metadata = &video.metadata;
+        query!(
+            r#"
+            INSERT INTO playlist_entries
+                (job_id, entry_index, url, title, thumbnail, uploader, bytes_estimate, selected, extractor, duration, channel, published_at, view_count, like_count, description, chapters, tags)
+            VALUES
+                (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17)
+            "#,
+            job_id,
+            entry_index,
+            video.url,
+            video.title,
+            video.thumbnail,
+            video.uploader,
+            video.bytes_estimate,
+            is_selected,
+            metadata.extractor,
+            metadata.duration,
+            metadata.channel,
+            metadata.published_at,
+            metadata.view_count,
+            metadata.like_count,
+            metadata.description,
+            metadata.chapters,
+            metadata.tags,
+        )
+        .execute(&mut **tx)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get_playlist_entries(&self, job_id: Uuid) -> anyhow::Result<Vec<PlaylistEntry>> {
+        Self::fetch_playlist_entries(&self.pool, job_id).await
+    }
+
+    async fn fetch_playlist_entries<'e, E: Executor<'e, Database = Sqlite>>(
+        executor: E,
+        job_id: Uuid,
+    ) -> anyhow::Result<Vec<PlaylistEntry>> {
+        let mut args = Self::new_args();
+        args.add(job_id);
+        let entries: Vec<PlaylistEntry> = query_as_with(
+            r#"
+            SELECT * FROM playlist_entries
+            WHERE job_id = ?1
+            ORDER BY entry_index
+            "#,
+            args,
+        )
+        .fetch_all(executor)
+        .await?;
+        Ok(entries)
+    }
+
+    // Queues chosen entries in playlist order, the ones already queued
+    // are skipped. Returns the number of new tasks.
+    pub async fn select_playlist_entries(
+        &self,
+        job_id: Uuid,
+        indices: &BTreeSet<u32>,
+    ) -> anyhow::Result<usize> {
+        let mut tx = self.pool.begin().await?;
+
+        let mut args = Self::new_args();
+        args.add(job_id);
+        args.add(TaskKind::FetchUrlContents);
+        let fetch_task: Task = query_as_with(
+            r#"
+            SELECT * FROM tasks
+            WHERE owner_job_id = ?1 AND kind = ?2
+            ORDER BY task_index
+            LIMIT 1
+            "#,
+            args,
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let entries: Vec<PlaylistEntry> = Self::fetch_playlist_entries(&mut *tx, job_id)
+            .await?
+            .into_iter()
+            .filter(|x| !x.selected && indices.contains(&x.entry_index))
+            .collect();
+        let new_tasks: Vec<Task> = entries
+            .iter()
+            .map(|x| Task::new_from_video_info(&fetch_task, &x.video))
+            .collect();
+        Self::insert_tasks(&mut tx, job_id, &new_tasks).await?;
+
+        for entry in &entries {
+            query!(
+                r#"
+                UPDATE playlist_entries
+                SET selected = true
+                WHERE job_id = ?1 AND entry_index = ?2
+                "#,
+                job_id,
+                entry.entry_index,
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(new_tasks.len())
+    }
+}
@@ -0,0 +1,53 @@
+use sqlx::sqlite::{SqliteConnectOptions, SqliteConnection, SqliteJournalMode};
+use sqlx::{query, ConnectOptions, Connection};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+const SQLITE_BUSY: i32 = 5;
+
+// Held by the running server, so that the database isn't replaced from
+// under it. This is SQLite's own lock on a file next to the database,
+// which goes away along with the process however it ends.
+#[derive(Debug)]
+pub struct ServerLock {
+    conn: SqliteConnection,
+}
+
+impl ServerLock {
+    pub async fn acquire(database_file: &Path) -> anyhow::Result<Self> {
+        let mut lock_file = database_file.as_os_str().to_owned();
+        lock_file.push(".lock");
+        match Self::lock(&PathBuf::from(lock_file)).await {
+            Ok(conn) => Ok(Self { conn }),
+            Err(sqlx::Error::Database(e)) if Self::is_busy(e.code().as_deref()) => {
+                Err(anyhow!("database is in use by a running server"))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    // Dropping the lock releases it too, but only eventually.
+    pub async fn release(self) -> anyhow::Result<()> {
+        self.conn.close().await?;
+        Ok(())
+    }
+
+    async fn lock(lock_file: &Path) -> Result<SqliteConnection, sqlx::Error> {
+        let mut conn = SqliteConnectOptions::from_str(&lock_file.to_string_lossy())?
+            .create_if_missing(true)
+            .journal_mode(SqliteJournalMode::Memory)
+            .busy_timeout(Duration::ZERO)
+            .disable_statement_logging()
+            .connect()
+            .await?;
+        query("BEGIN EXCLUSIVE").execute(&mut conn).await?;
+        Ok(conn)
+    }
+
+    // Extended result codes keep the primary one in the lowest byte.
+    fn is_busy(code: Option<&str>) -> bool {
+        code.and_then(|x| x.parse::<i32>().ok())
+            .is_some_and(|x| x & 0xff == SQLITE_BUSY)
+    }
+}
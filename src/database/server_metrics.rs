@@ -0,0 +1,40 @@
+use crate::database::ServerDatabase;
+use crate::metrics::MetricsSnapshot;
+use sqlx::query;
+
+const SNAPSHOT_NAME: &str = "snapshot";
+
+impl ServerDatabase {
+    pub async fn save_metrics(&self, snapshot: &MetricsSnapshot) -> anyhow::Result<()> {
+        let value = serde_json::to_string(snapshot)?;
+        query!(
+            r#"
+            INSERT OR REPLACE INTO server_metrics
+                (name, value)
+            VALUES
+                (?1, ?2)
+            "#,
+            SNAPSHOT_NAME,
+            value,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn load_metrics(&self) -> anyhow::Result<Option<MetricsSnapshot>> {
+        let row = query!(
+            r#"
+            SELECT value FROM server_metrics
+            WHERE name = ?1
+            "#,
+            SNAPSHOT_NAME,
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        match row {
+            Some(row) => Ok(Some(serde_json::from_str(&row.value)?)),
+            None => Ok(None),
+        }
+    }
+}
@@ -127,3 +127,7 @@ where
 pub fn new_downloader_command() -> Command {
     Command::new("yt-dlp.exe")
 }
+
+pub fn new_transcoder_command() -> Command {
+    Command::new("ffmpeg")
+}
@@ -0,0 +1,61 @@
+use crate::env::Config;
+use crate::url_host::host_of_url;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HostCheck {
+    Allowed,
+    // Host has too many tasks running
+    Saturated,
+    // Previous task for the host has been started too recently,
+    // next one can start at the given time
+    Delayed(Instant),
+}
+
+// Keeps track of how many tasks are running for each host,
+// and when the last one has been started.
+#[derive(Debug, Clone, Default)]
+pub struct HostSchedule {
+    num_active: HashMap<String, u32>,
+    last_start: HashMap<String, Instant>,
+}
+
+impl HostSchedule {
+    pub fn check(&self, config: &Config, url: &str) -> HostCheck {
+        let host = match host_of_url(url) {
+            Some(host) => host,
+            None => return HostCheck::Allowed,
+        };
+        let (max_downloads, min_start_delay) = config.get_host_limits(&host);
+        let num_active = self.num_active.get(&host).copied().unwrap_or_default();
+        if max_downloads != 0 && num_active >= max_downloads {
+            return HostCheck::Saturated;
+        }
+        if let Some(last_start) = self.last_start.get(&host) {
+            let not_before = *last_start + Duration::from_secs(min_start_delay as u64);
+            if Instant::now() < not_before {
+                return HostCheck::Delayed(not_before);
+            }
+        }
+        HostCheck::Allowed
+    }
+
+    pub fn on_task_started(&mut self, url: &str) {
+        if let Some(host) = host_of_url(url) {
+            *self.num_active.entry(host.clone()).or_default() += 1;
+            self.last_start.insert(host, Instant::now());
+        }
+    }
+
+    pub fn on_task_finished(&mut self, url: &str) {
+        if let Some(host) = host_of_url(url) {
+            if let Some(num_active) = self.num_active.get_mut(&host) {
+                *num_active = num_active.saturating_sub(1);
+                if *num_active == 0 {
+                    self.num_active.remove(&host);
+                }
+            }
+        }
+    }
+}
@@ -1,11 +1,22 @@
+use crate::credentials::CredentialStore;
 use crate::env::EnvironmentManager;
 use crate::filesystem::FilesystemDriver;
+use crate::metrics::ServerMetrics;
 use crate::queue_command::QueueCommand;
-use crate::task::{run_task, Task, TaskProgress, TaskResult, TaskResultData, TaskStatus};
+use crate::task::{
+    run_task, FailureKind, Task, TaskFailure, TaskProgress, TaskResult, TaskResultData, TaskStatus,
+    WorkerPool,
+};
+use chrono::Utc;
 use command::WorkerError;
+pub use host_schedule::HostCheck;
+use host_schedule::HostSchedule;
 use std::borrow::Borrow;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::io::SeekFrom;
 use std::sync::Arc;
+use std::time::Instant;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
 use tokio::sync::Mutex;
 use tokio::task::JoinHandle;
 use tracing::{error, info};
@@ -14,6 +25,7 @@ use worker::DownloadWorker;
 pub use worker_handle::WorkerControlHandle;
 
 pub mod command;
+mod host_schedule;
 mod worker;
 mod worker_handle;
 
@@ -23,8 +35,11 @@ pub struct DownloadManager {
     progress_by_id: HashMap<Uuid, Arc<Mutex<TaskProgress>>>,
     handle_by_task_id: HashMap<Uuid, WorkerControlHandle>,
     task_by_job_id: HashMap<Uuid, Vec<Uuid>>,
+    host_schedule: HostSchedule,
     env: Arc<EnvironmentManager>,
     fs: Arc<FilesystemDriver>,
+    credentials: Arc<CredentialStore>,
+    metrics: Arc<ServerMetrics>,
 }
 
 enum WorkerCollectResult {
@@ -54,19 +69,73 @@ impl WorkerCollectResult {
 }
 
 impl DownloadManager {
-    pub fn new(env: Arc<EnvironmentManager>, fs: Arc<FilesystemDriver>) -> Self {
+    pub fn new(
+        env: Arc<EnvironmentManager>,
+        fs: Arc<FilesystemDriver>,
+        credentials: Arc<CredentialStore>,
+        metrics: Arc<ServerMetrics>,
+    ) -> Self {
         Self {
             workers: Default::default(),
             progress_by_id: Default::default(),
             handle_by_task_id: Default::default(),
             task_by_job_id: Default::default(),
+            host_schedule: Default::default(),
             env,
             fs,
+            credentials,
+            metrics,
         }
     }
 
-    pub fn num_free_workers(&self) -> u32 {
-        self.env.config.num_download_workers - self.workers.len() as u32
+    pub fn num_max_workers(&self, pool: WorkerPool) -> u32 {
+        match pool {
+            WorkerPool::Download => self.env.config.num_download_workers,
+            WorkerPool::Transcode => self.env.config.num_transcode_workers,
+        }
+    }
+
+    pub fn num_busy_workers(&self, pool: WorkerPool) -> u32 {
+        self.workers
+            .iter()
+            .filter(|x| x.task.kind.worker_pool() == pool)
+            .count() as u32
+    }
+
+    pub fn num_free_workers(&self, pool: WorkerPool) -> u32 {
+        self.num_max_workers(pool)
+            .saturating_sub(self.num_busy_workers(pool))
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.workers.is_empty()
+    }
+
+    pub fn get_active_task_ids(&self) -> HashSet<Uuid> {
+        self.handle_by_task_id.keys().copied().collect()
+    }
+
+    pub fn is_task_active(&self, task_id: Uuid) -> bool {
+        self.handle_by_task_id.contains_key(&task_id)
+    }
+
+    pub fn get_host_schedule(&self) -> &HostSchedule {
+        &self.host_schedule
+    }
+
+    // Amount of bytes active workers are still expected to write to disk.
+    pub async fn bytes_reserved(&self) -> u64 {
+        let mut total = 0u64;
+        for worker in &self.workers {
+            let progress = worker.progress.lock().await;
+            let remaining = if progress.bytes_estimate > 0 {
+                (progress.bytes_estimate - progress.bytes_downloaded) as i64
+            } else {
+                worker.task.bytes_estimate.unwrap_or_default()
+            };
+            total += remaining.max(0) as u64;
+        }
+        total
     }
 
     fn register_worker_handle(
@@ -109,8 +178,18 @@ impl DownloadManager {
     ) -> JoinHandle<anyhow::Result<TaskResultData>> {
         let task = task.clone();
         let fs = self.fs.clone();
-        let ytdlp = self.env.ytdlp.clone();
-        tokio::task::spawn(async move { run_task(task, ytdlp, fs, progress, control_handle).await })
+        let env = self.env.clone();
+        let credentials = self.credentials.clone();
+        // Proxy chosen for the job takes priority over the one from config
+        let proxy = task.proxy.clone().or_else(|| {
+            self.env
+                .config
+                .get_proxy_for_url(&task.url)
+                .map(str::to_string)
+        });
+        tokio::task::spawn(async move {
+            run_task(task, env, fs, credentials, proxy, progress, control_handle).await
+        })
     }
 
     pub fn start_task(&mut self, task: Task) {
@@ -119,7 +198,20 @@ impl DownloadManager {
 
         self.register_worker_handle(task.task_id, task.owner_job_id, control_handle.clone());
         self.register_worker_progress(task.task_id, progress.clone());
+        if task.kind.worker_pool() == WorkerPool::Download {
+            self.host_schedule.on_task_started(&task.url);
+        }
+        // Later runs would count time spent failed or paused as waiting
+        if task.num_retries == 0 && !task.is_resumed {
+            let queue_wait = (Utc::now() - task.created_at).to_std().unwrap_or_default();
+            self.metrics
+                .on_task_started(&format!("{:?}", task.kind), queue_wait);
+        }
 
+        // Log is appended to by every run of the task
+        let stderr_offset = std::fs::metadata(self.fs.get_ytdlp_stderr_file(task.task_id))
+            .map(|x| x.len())
+            .unwrap_or_default();
         let join_handle = self.spawn_worker_thread(&task, progress.clone(), control_handle.clone());
 
         self.workers.push(DownloadWorker {
@@ -127,6 +219,8 @@ impl DownloadManager {
             progress,
             join_handle,
             control_handle,
+            stderr_offset,
+            started: Instant::now(),
         });
     }
 
@@ -178,9 +272,67 @@ impl DownloadManager {
         }
     }
 
+    async fn read_stderr_since(&self, task_id: Uuid, offset: u64) -> anyhow::Result<String> {
+        let mut file = tokio::fs::File::open(self.fs.get_ytdlp_stderr_file(task_id)).await?;
+        file.seek(SeekFrom::Start(offset)).await?;
+        let mut data = vec![];
+        file.read_to_end(&mut data).await?;
+        Ok(String::from_utf8_lossy(&data).to_string())
+    }
+
+    async fn get_failure(
+        &self,
+        task: &Task,
+        stderr_offset: u64,
+        result: &WorkerCollectResult,
+    ) -> Option<TaskFailure> {
+        let e = match result {
+            WorkerCollectResult::Failed(e) => e,
+            WorkerCollectResult::Crashed(e) => {
+                return Some(TaskFailure::new(FailureKind::Unknown, &e.to_string()))
+            }
+            _ => return None,
+        };
+        if let Some(WorkerError::BadExitCode(_)) = e.downcast_ref::<WorkerError>() {
+            let stderr = self
+                .read_stderr_since(task.task_id, stderr_offset)
+                .await
+                .unwrap_or_default();
+            return Some(TaskFailure::from_stderr(&stderr, e));
+        }
+        let kind = match e.downcast_ref::<std::io::Error>() {
+            // ENOSPC, e.g. when moving output file to its final place
+            Some(io_error) if io_error.raw_os_error() == Some(28) => FailureKind::DiskFull,
+            _ => FailureKind::Unknown,
+        };
+        Some(TaskFailure::new(kind, &e.to_string()))
+    }
+
+    async fn record_metrics(
+        &self,
+        task: &Task,
+        started: Instant,
+        result: &WorkerCollectResult,
+        failure: Option<&TaskFailure>,
+    ) {
+        self.metrics
+            .on_task_finished(&format!("{:?}", task.kind), started.elapsed());
+        if let Some(failure) = failure {
+            self.metrics.on_task_failed(&format!("{:?}", failure.kind));
+        }
+        // Transcoding only rewrites what's already been downloaded
+        if let WorkerCollectResult::Ok(TaskResultData::DownloadAndConvert(files)) = result {
+            self.metrics.on_bytes_downloaded(files.total_size().await);
+        }
+    }
+
     async fn collect_worker(&mut self, idx: usize) -> TaskResult {
         let DownloadWorker {
-            task, join_handle, ..
+            task,
+            join_handle,
+            stderr_offset,
+            started,
+            ..
         } = self.workers.remove(idx);
         let task_id = task.task_id;
         let owner_job_id = task.owner_job_id;
@@ -188,13 +340,20 @@ impl DownloadManager {
         let worker_result = Self::join_worker_thread(join_handle).await;
 
         Self::report_worker_result(task_id, &worker_result);
+        let failure = self.get_failure(&task, stderr_offset, &worker_result).await;
+        self.record_metrics(&task, started, &worker_result, failure.as_ref())
+            .await;
         self.unregister_worker_handle(task_id, owner_job_id);
         self.unregister_worker_progress(task_id);
+        if task.kind.worker_pool() == WorkerPool::Download {
+            self.host_schedule.on_task_finished(&task.url);
+        }
 
         TaskResult {
             task,
             status: worker_result.task_status(),
             data: worker_result.task_data(),
+            failure,
         }
     }
 
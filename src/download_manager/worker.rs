@@ -1,6 +1,7 @@
 use crate::download_manager::worker_handle::WorkerControlHandle;
 use crate::task::{Task, TaskProgress, TaskResultData};
 use std::sync::Arc;
+use std::time::Instant;
 use tokio::sync::Mutex;
 use tokio::task::JoinHandle;
 
@@ -10,4 +11,7 @@ pub struct DownloadWorker {
     pub progress: Arc<Mutex<TaskProgress>>,
     pub join_handle: JoinHandle<anyhow::Result<TaskResultData>>,
     pub control_handle: WorkerControlHandle,
+    // Where this run's output starts in the stderr log
+    pub stderr_offset: u64,
+    pub started: Instant,
 }
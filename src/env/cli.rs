@@ -26,7 +26,7 @@ pub struct Cli {
     pub command: CliCommand,
 }
 
-#[derive(Subcommand, Debug, Clone, Copy)]
+#[derive(Subcommand, Debug, Clone)]
 pub enum CliCommand {
     /// Run server
     Run {
@@ -36,6 +36,20 @@ pub enum CliCommand {
     },
     /// Print API token and exit
     GetToken,
+    /// Save a consistent copy of the database, safe to run while server is up
+    Export {
+        /// Where to save the copy
+        path: PathBuf,
+        /// Save jobs, tasks and users as JSON instead
+        #[arg(long)]
+        json: bool,
+    },
+    /// Restore database from a copy or JSON made by `export`. Server must be stopped
+    Import {
+        /// Database copy replaces current database,
+        /// JSON contents are added to it
+        path: PathBuf,
+    },
     /// Install itself as Windows service
     InstallService {
         /// As a service, write log files
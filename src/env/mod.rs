@@ -7,12 +7,14 @@ mod cli;
 mod config;
 mod config_trait;
 mod paths;
+mod secret;
 mod ytdlp;
 
 use crate::env::config_trait::ConfigTrait;
 pub use cli::{Cli, CliCommand};
-pub use config::Config;
+pub use config::{Config, HookConfig, TranscodePreset, WebhookConfig};
 pub use paths::Paths;
+pub use secret::Secret;
 pub use ytdlp::{DownloadFormat, YtdlpConfig};
 
 #[derive(Debug)]
@@ -44,10 +46,13 @@ impl EnvironmentManager {
 
         let paths = Paths {
             database_file: Self::get_database_file(dev_mode),
+            backups_dir: Self::get_backups_dir(dev_mode),
             server_config_file,
             ytdlp_config_file,
+            credentials_dir: Self::get_credentials_dir(dev_mode),
             worker_dir: Self::get_worker_dir(&config, dev_mode),
             logs_dir: Self::get_logs_dir(&config, dev_mode),
+            thumbnails_dir: Self::get_thumbnails_dir(&config, dev_mode),
             output_dir: Self::get_output_dir(&config),
         };
         Self {
@@ -59,6 +64,31 @@ impl EnvironmentManager {
         }
     }
 
+    // Keeps everything under `root`, starting from the default config.
+    #[cfg(test)]
+    pub fn new_for_tests(root: &std::path::Path, edit_config: impl FnOnce(&mut Config)) -> Self {
+        let mut config = Config::new(root.join("download"), root.join("temp"));
+        edit_config(&mut config);
+        let paths = Paths {
+            database_file: root.join("db/state.db"),
+            backups_dir: root.join("db/backups"),
+            server_config_file: root.join("config/server.yaml"),
+            ytdlp_config_file: root.join("config/ytdlp.yaml"),
+            credentials_dir: root.join("config/credentials"),
+            worker_dir: root.join("temp/workers"),
+            logs_dir: root.join("temp/logs"),
+            thumbnails_dir: root.join("temp/thumbnails"),
+            output_dir: root.join("download"),
+        };
+        Self {
+            cli: Cli::parse_from(["youtube-dl-server", "run"]),
+            config,
+            ytdlp: Arc::new(YtdlpConfig::new()),
+            dev_mode: true,
+            paths,
+        }
+    }
+
     fn get_project_dirs() -> Option<ProjectDirs> {
         ProjectDirs::from("com", "youtube-dl-server", "youtube-dl-server")
     }
@@ -79,6 +109,10 @@ impl EnvironmentManager {
         Self::get_config_dir(dev_mode).join("ytdlp.yaml")
     }
 
+    fn get_credentials_dir(dev_mode: bool) -> PathBuf {
+        Self::get_config_dir(dev_mode).join("credentials")
+    }
+
     fn get_default_userconf_download_dir(dev_mode: bool) -> PathBuf {
         if dev_mode {
             PathBuf::from("download")
@@ -111,6 +145,17 @@ impl EnvironmentManager {
         }
     }
 
+    fn get_thumbnails_dir(config: &Config, dev_mode: bool) -> PathBuf {
+        if dev_mode {
+            config.temp_folder.join("debug/thumbnails")
+        } else {
+            Self::get_project_dirs()
+                .unwrap()
+                .cache_dir()
+                .join("thumbnails")
+        }
+    }
+
     fn get_worker_dir(config: &Config, dev_mode: bool) -> PathBuf {
         if dev_mode {
             config.temp_folder.join("workers")
@@ -135,6 +180,10 @@ impl EnvironmentManager {
         Self::get_database_dir(dev_mode).join("state.db")
     }
 
+    fn get_backups_dir(dev_mode: bool) -> PathBuf {
+        Self::get_database_dir(dev_mode).join("backups")
+    }
+
     pub async fn check_config_validity(new_config: &Config) -> bool {
         Config::check_validity(new_config).await
     }
@@ -0,0 +1,46 @@
+use rocket::serde::{Deserialize, Serialize};
+use std::fmt::{Debug, Formatter};
+
+const PLACEHOLDER: &str = "<redacted>";
+
+// Password or token kept in config. It never shows up in logs, and API
+// clients only get a placeholder, which means "keep the current one"
+// when it's sent back.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    pub fn redact(&mut self) {
+        self.0 = PLACEHOLDER.to_string();
+    }
+
+    pub fn is_redacted(&self) -> bool {
+        self.0 == PLACEHOLDER
+    }
+
+    // Placeholder without anything to stand for is left as is, and will
+    // fail wherever it's used instead of silently becoming empty.
+    pub fn restore(&mut self, current: Option<&Secret>) {
+        match current {
+            Some(current) if self.is_redacted() => *self = current.clone(),
+            _ => {}
+        }
+    }
+}
+
+impl From<&str> for Secret {
+    fn from(value: &str) -> Self {
+        Self(value.to_string())
+    }
+}
+
+impl Debug for Secret {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{PLACEHOLDER:?}")
+    }
+}
@@ -14,21 +14,32 @@ pub struct DownloadFormat {
     pub display: String,
     pub ext: String,
     pub args: Vec<String>,
+    // Extensions of extra files produced by yt-dlp that should be kept
+    // alongside the media file, e.g. "vtt" or "info.json".
+    #[serde(default)]
+    pub sidecars: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct YtdlpConfig {
     command_fetch_url: CommandTemplate,
+    #[serde(default = "default_playlist_extractors")]
+    playlist_extractors: Vec<String>,
     command_download: CommandTemplate,
     formats: Vec<DownloadFormat>,
 }
 
+// Configs saved before the list was added get the built-in one.
+fn default_playlist_extractors() -> Vec<String> {
+    YtdlpConfig::new().playlist_extractors
+}
+
 impl YtdlpConfig {
     pub(super) fn new() -> Self {
         serde_yaml::from_str(include_str!("ytdlp.yaml")).unwrap()
     }
 
-    fn try_get_format(&self, format: &str) -> Option<&DownloadFormat> {
+    pub fn try_get_format(&self, format: &str) -> Option<&DownloadFormat> {
         self.formats.iter().find(|x| x.id == format)
     }
 
@@ -41,17 +52,44 @@ impl YtdlpConfig {
         &self.formats
     }
 
-    pub fn render_fetch_url_command<S: AsRef<OsStr>>(&self, source_url: S) -> Vec<OsString> {
-        self.command_fetch_url
-            .args
+    pub fn is_playlist_extractor(&self, ie_key: &str) -> bool {
+        self.playlist_extractors.iter().any(|x| x == ie_key)
+    }
+
+    fn render_proxy_args(proxy: Option<&str>) -> Vec<OsString> {
+        match proxy {
+            Some(proxy) => vec![OsString::from("--proxy"), OsString::from(proxy)],
+            None => vec![],
+        }
+    }
+
+    // Templates written before proxies were supported lack the placeholder,
+    // proxy goes in front of the other arguments for them.
+    fn render_missing_proxy_args(template: &CommandTemplate, proxy: Option<&str>) -> Vec<OsString> {
+        if template.args.iter().any(|x| x == "{{proxy}}") {
+            return vec![];
+        }
+        Self::render_proxy_args(proxy)
+    }
+
+    // `extra_args` are put in front of the arguments from config.
+    pub fn render_fetch_url_command<S: AsRef<OsStr>>(
+        &self,
+        source_url: S,
+        proxy: Option<&str>,
+        extra_args: &[OsString],
+    ) -> Vec<OsString> {
+        let args = self.command_fetch_url.args.iter().flat_map(|x| match x.as_str() {
+            "{{source_url}}" => vec![source_url.as_ref().to_os_string()],
+            "{{proxy}}" => Self::render_proxy_args(proxy),
+            x => vec![OsString::from(x)],
+        });
+        let proxy_args = Self::render_missing_proxy_args(&self.command_fetch_url, proxy);
+        extra_args
             .iter()
-            .map(|x| {
-                if x == "{{source_url}}" {
-                    source_url.as_ref().to_os_string()
-                } else {
-                    OsString::from(x)
-                }
-            })
+            .cloned()
+            .chain(proxy_args)
+            .chain(args)
             .collect()
     }
 
@@ -60,16 +98,22 @@ impl YtdlpConfig {
         source_url: S1,
         format: &DownloadFormat,
         destination_file: S2,
+        proxy: Option<&str>,
+        extra_args: &[OsString],
     ) -> Vec<OsString> {
-        self.command_download
-            .args
+        let args = self.command_download.args.iter().flat_map(|x| match x.as_str() {
+            "{{source_url}}" => vec![OsString::from(source_url.as_ref())],
+            "{{proxy}}" => Self::render_proxy_args(proxy),
+            "{{format_args}}" => format.args.iter().map(OsString::from).collect(),
+            "{{destination_file}}" => vec![OsString::from(destination_file.as_ref())],
+            x => vec![OsString::from(x)],
+        });
+        let proxy_args = Self::render_missing_proxy_args(&self.command_download, proxy);
+        extra_args
             .iter()
-            .flat_map(|x| match x.as_str() {
-                "{{source_url}}" => vec![OsString::from(source_url.as_ref())],
-                "{{format_args}}" => format.args.iter().map(OsString::from).collect(),
-                "{{destination_file}}" => vec![OsString::from(destination_file.as_ref())],
-                x => vec![OsString::from(x)],
-            })
+            .cloned()
+            .chain(proxy_args)
+            .chain(args)
             .collect()
     }
 }
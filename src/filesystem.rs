@@ -1,16 +1,32 @@
 use crate::env::EnvironmentManager;
-use crate::task::Task;
+use crate::task::{OutputFiles, Task};
 use anyhow::Context;
+use chrono::{DateTime, Utc};
+use rocket::serde::Serialize;
+use std::collections::HashSet;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 use tokio::fs::File;
+use tokio::io::AsyncWriteExt;
+use tracing::warn;
 use uuid::Uuid;
 
 const MAIN_FILE_NAME: &str = "main";
 const WORKER_DATA_DIR: &str = "data";
-const WORKER_LOG_DIR: &str = "log";
+const TASK_LOGS_DIR: &str = "tasks";
 const WORKER_STDOUT_FILE: &str = "stdout.log";
 const WORKER_STDERR_FILE: &str = "stderr.log";
+const WORKER_HOOKS_LOG_FILE: &str = "hooks.log";
+const BACKUP_FILE_PREFIX: &str = "state-";
+const BACKUP_FILE_EXT: &str = "db";
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SweepStats {
+    pub num_dirs_removed: u64,
+    pub bytes_reclaimed: u64,
+    pub last_sweep_at: Option<DateTime<Utc>>,
+}
 
 #[derive(Debug)]
 pub struct FilesystemDriver {
@@ -29,16 +45,93 @@ impl FilesystemDriver {
         Ok(())
     }
 
+    // Returns free space on the emptiest of the volumes we write to.
+    pub async fn get_free_space(&self) -> anyhow::Result<u64> {
+        let output_dir_space = available_space(&self.env.paths.output_dir).await?;
+        let worker_dir_space = available_space(&self.env.paths.worker_dir).await?;
+        Ok(output_dir_space.min(worker_dir_space))
+    }
+
     pub fn get_database_file(&self) -> &Path {
         &self.env.paths.database_file
     }
 
+    // Picks a name for a new database backup, named after current time.
+    pub async fn new_backup_file(&self) -> anyhow::Result<PathBuf> {
+        let dir = &self.env.paths.backups_dir;
+        ensure_writable_dir_exists(dir).await?;
+        let timestamp = Utc::now().format("%Y%m%d-%H%M%S");
+        let name = format!("{BACKUP_FILE_PREFIX}{timestamp}.{BACKUP_FILE_EXT}");
+        Ok(pick_free_file_name(&dir.join(name)).await)
+    }
+
+    // Database backups made so far, oldest first.
+    async fn list_backups(&self) -> Vec<(SystemTime, PathBuf)> {
+        let mut backups = vec![];
+        let mut entries = match tokio::fs::read_dir(&self.env.paths.backups_dir).await {
+            Ok(entries) => entries,
+            Err(_) => return backups,
+        };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().to_string();
+            let is_backup = name.starts_with(BACKUP_FILE_PREFIX)
+                && path.extension().unwrap_or_default() == BACKUP_FILE_EXT;
+            if !is_backup {
+                continue;
+            }
+            if let Ok(modified) = entry.metadata().await.and_then(|x| x.modified()) {
+                backups.push((modified, path));
+            }
+        }
+        backups.sort();
+        backups
+    }
+
+    pub async fn get_last_backup_time(&self) -> Option<SystemTime> {
+        self.list_backups()
+            .await
+            .pop()
+            .map(|(modified, _)| modified)
+    }
+
+    // Removes the oldest backups, so that only `num_kept` latest remain.
+    pub async fn rotate_backups(&self, num_kept: usize) -> anyhow::Result<()> {
+        let backups = self.list_backups().await;
+        let num_removed = backups.len().saturating_sub(num_kept);
+        for (_, path) in &backups[..num_removed] {
+            tokio::fs::remove_file(path).await?;
+        }
+        Ok(())
+    }
+
     pub async fn remove_worker_root_dir(&self, task_id: Uuid) -> anyhow::Result<()> {
         let path = self.get_worker_root_dir_for_task(task_id);
         tokio::fs::remove_dir_all(&path).await?;
         Ok(())
     }
 
+    // Removes worker directories which don't belong to any of `tasks_to_keep`,
+    // e.g. left behind by a crash. Anything not named after a task is ignored.
+    pub async fn sweep_worker_dirs(
+        &self,
+        tasks_to_keep: &HashSet<Uuid>,
+    ) -> anyhow::Result<SweepStats> {
+        sweep_task_dirs(&self.env.paths.worker_dir, tasks_to_keep).await
+    }
+
+    // Same for thumbnails, whose directory only exists once one is requested.
+    pub async fn sweep_thumbnail_dirs(
+        &self,
+        tasks_to_keep: &HashSet<Uuid>,
+    ) -> anyhow::Result<SweepStats> {
+        let dir = &self.env.paths.thumbnails_dir;
+        if !path_exists(dir).await {
+            return Ok(SweepStats::default());
+        }
+        sweep_task_dirs(dir, tasks_to_keep).await
+    }
+
     fn get_worker_root_dir_for_task(&self, task_id: Uuid) -> PathBuf {
         self.env.paths.worker_dir.join(task_id.to_string())
     }
@@ -48,9 +141,27 @@ impl FilesystemDriver {
             .join(WORKER_DATA_DIR)
     }
 
-    fn get_worker_log_dir_for_task(&self, task_id: Uuid) -> PathBuf {
-        self.get_worker_root_dir_for_task(task_id)
-            .join(WORKER_LOG_DIR)
+    // Logs are kept apart from worker directories, so they outlive the
+    // temporary files and are removed along with the task.
+    fn get_task_logs_root_dir(&self) -> PathBuf {
+        self.env.paths.logs_dir.join(TASK_LOGS_DIR)
+    }
+
+    fn get_task_log_dir(&self, task_id: Uuid) -> PathBuf {
+        self.get_task_logs_root_dir().join(task_id.to_string())
+    }
+
+    // Each task gets a directory with its thumbnail in different sizes.
+    pub fn get_thumbnail_dir(&self, task_id: Uuid) -> PathBuf {
+        self.env.paths.thumbnails_dir.join(task_id.to_string())
+    }
+
+    pub async fn remove_thumbnail_dir(&self, task_id: Uuid) -> anyhow::Result<()> {
+        let path = self.get_thumbnail_dir(task_id);
+        if path_exists(&path).await {
+            tokio::fs::remove_dir_all(&path).await?;
+        }
+        Ok(())
     }
 
     pub async fn create_worker_data_dir(&self, task_id: Uuid) -> anyhow::Result<()> {
@@ -59,21 +170,105 @@ impl FilesystemDriver {
         Ok(())
     }
 
-    pub async fn create_worker_log_dir(&self, task_id: Uuid) -> anyhow::Result<()> {
-        let path = self.get_worker_log_dir_for_task(task_id);
+    pub async fn create_task_log_dir(&self, task_id: Uuid) -> anyhow::Result<()> {
+        let path = self.get_task_log_dir(task_id);
         tokio::fs::create_dir_all(&path).await?;
         Ok(())
     }
 
+    pub async fn remove_task_log_dir(&self, task_id: Uuid) -> anyhow::Result<()> {
+        let path = self.get_task_log_dir(task_id);
+        tokio::fs::remove_dir_all(&path).await?;
+        Ok(())
+    }
+
+    // Removes logs of tasks that no longer exist, the ones not touched
+    // for longer than `retention`, and then the oldest ones until the rest
+    // fit into `max_bytes` (0 means no limit). Logs of `active_tasks` stay.
+    pub async fn prune_task_logs(
+        &self,
+        existing_tasks: &HashSet<Uuid>,
+        active_tasks: &HashSet<Uuid>,
+        retention: Option<Duration>,
+        max_bytes: u64,
+    ) -> anyhow::Result<SweepStats> {
+        let mut stats = SweepStats::default();
+        let root_dir = self.get_task_logs_root_dir();
+        if !path_exists(&root_dir).await {
+            return Ok(stats);
+        }
+
+        let mut total_bytes = 0u64;
+        let mut candidates = vec![];
+        let mut entries = tokio::fs::read_dir(&root_dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let task_id = match Uuid::parse_str(&entry.file_name().to_string_lossy()) {
+                Ok(task_id) => task_id,
+                Err(_) => continue,
+            };
+            if !entry.file_type().await?.is_dir() {
+                continue;
+            }
+            let num_bytes = dir_size(&entry.path()).await;
+            if active_tasks.contains(&task_id) {
+                total_bytes += num_bytes;
+                continue;
+            }
+            let modified = last_modified(&entry.path()).await;
+            let is_expired = match retention {
+                Some(retention) => modified.elapsed().unwrap_or_default() > retention,
+                None => false,
+            };
+            if !existing_tasks.contains(&task_id) || is_expired {
+                if tokio::fs::remove_dir_all(entry.path()).await.is_ok() {
+                    stats.num_dirs_removed += 1;
+                    stats.bytes_reclaimed += num_bytes;
+                }
+                continue;
+            }
+            total_bytes += num_bytes;
+            candidates.push((modified, entry.path(), num_bytes));
+        }
+
+        candidates.sort();
+        for (_, path, num_bytes) in candidates {
+            if max_bytes == 0 || total_bytes <= max_bytes {
+                break;
+            }
+            if tokio::fs::remove_dir_all(&path).await.is_ok() {
+                total_bytes -= num_bytes;
+                stats.num_dirs_removed += 1;
+                stats.bytes_reclaimed += num_bytes;
+            }
+        }
+        Ok(stats)
+    }
+
     pub async fn remove_worker_data_dir(&self, task_id: Uuid) -> anyhow::Result<()> {
         let path = self.get_worker_data_dir_for_task(task_id);
         tokio::fs::remove_dir_all(&path).await?;
         Ok(())
     }
 
-    pub async fn remove_worker_output_file(&self, task: &Task) -> anyhow::Result<()> {
-        let path = self.get_ytdlp_output_file(task);
-        tokio::fs::remove_file(&path).await?;
+    // Removes files which could've been cut short while post-processing:
+    // the final output which hasn't been moved yet, and ffmpeg's temporary
+    // files. Partially downloaded data is left for yt-dlp to continue.
+    pub async fn remove_unfinished_output_files(&self, task: &Task) -> anyhow::Result<()> {
+        let output_file_name = self
+            .get_ytdlp_output_file(task)
+            .file_name()
+            .unwrap_or_default()
+            .to_owned();
+        let mut entries =
+            tokio::fs::read_dir(self.get_worker_data_dir_for_task(task.task_id)).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let file_name = entry.file_name();
+            let is_unfinished =
+                file_name == output_file_name || file_name.to_string_lossy().contains(".temp.");
+            if is_unfinished {
+                tokio::fs::remove_file(entry.path()).await?;
+            }
+        }
         Ok(())
     }
 
@@ -88,17 +283,26 @@ impl FilesystemDriver {
             .join(format!("{MAIN_FILE_NAME}.{}", format.ext))
     }
 
+    pub fn get_transcode_output_file(&self, task_id: Uuid, ext: &str) -> PathBuf {
+        self.get_worker_data_dir_for_task(task_id)
+            .join(format!("{MAIN_FILE_NAME}.{ext}"))
+    }
+
     pub fn get_ytdlp_stdout_file(&self, task_id: Uuid) -> PathBuf {
-        self.get_worker_log_dir_for_task(task_id)
-            .join(WORKER_STDOUT_FILE)
+        self.get_task_log_dir(task_id).join(WORKER_STDOUT_FILE)
     }
 
     pub fn get_ytdlp_stderr_file(&self, task_id: Uuid) -> PathBuf {
-        self.get_worker_log_dir_for_task(task_id)
-            .join(WORKER_STDERR_FILE)
+        self.get_task_log_dir(task_id).join(WORKER_STDERR_FILE)
+    }
+
+    pub fn get_hooks_log_file(&self, task_id: Uuid) -> PathBuf {
+        self.get_task_log_dir(task_id).join(WORKER_HOOKS_LOG_FILE)
     }
 
-    pub async fn move_output_file(&self, task: &Task) -> anyhow::Result<()> {
+    // Moves the media file along with sidecars declared by its format.
+    // Returns where the files ended up.
+    pub async fn move_output_file(&self, task: &Task) -> anyhow::Result<OutputFiles> {
         let filename_unsafe: &str = if task.title.is_empty() {
             &task.url
         } else {
@@ -116,9 +320,106 @@ impl FilesystemDriver {
         let destination_path_unsafe = self.env.paths.output_dir.join(filename + "." + source_ext);
         let destination_path = pick_free_file_name(&destination_path_unsafe).await;
 
-        tokio::fs::rename(source_path, destination_path).await?;
+        tokio::fs::rename(&source_path, &destination_path).await?;
 
-        Ok(())
+        let destination_stem = destination_path
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        let mut sidecars = vec![];
+        for (sidecar_path, suffix) in self.find_sidecar_files(task).await? {
+            let destination_path_unsafe = self
+                .env
+                .paths
+                .output_dir
+                .join(format!("{destination_stem}{suffix}"));
+            let destination_path = pick_free_file_name(&destination_path_unsafe).await;
+            tokio::fs::rename(&sidecar_path, &destination_path).await?;
+            sidecars.push(destination_path);
+        }
+
+        Ok(OutputFiles {
+            media: destination_path,
+            sidecars,
+        })
+    }
+
+    // Playlist keeps its name when rewritten, e.g. after a retry.
+    pub async fn write_playlist(
+        &self,
+        existing_path: Option<&str>,
+        title: &str,
+        contents: &str,
+    ) -> anyhow::Result<PathBuf> {
+        let path = match existing_path {
+            Some(path) => PathBuf::from(path),
+            None => {
+                let file_name = filenamify::filenamify(title) + ".m3u8";
+                pick_free_file_name(&self.env.paths.output_dir.join(file_name)).await
+            }
+        };
+        tokio::fs::write(&path, contents).await?;
+        Ok(path)
+    }
+
+    pub fn get_output_dir(&self) -> &Path {
+        &self.env.paths.output_dir
+    }
+
+    // Lists files next to the main output which have one of the extensions
+    // the format asks to keep, along with their name minus the main stem,
+    // e.g. ".en.vtt" for "main.en.vtt".
+    async fn find_sidecar_files(&self, task: &Task) -> anyhow::Result<Vec<(PathBuf, String)>> {
+        let format = self.env.ytdlp.get_format(&task.format);
+        if format.sidecars.is_empty() {
+            return Ok(vec![]);
+        }
+        let main_file_name = format!("{MAIN_FILE_NAME}.{}", format.ext);
+        let mut res = vec![];
+        let mut entries =
+            tokio::fs::read_dir(self.get_worker_data_dir_for_task(task.task_id)).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            if file_name == main_file_name {
+                continue;
+            }
+            let suffix = match file_name.strip_prefix(MAIN_FILE_NAME) {
+                Some(suffix) if suffix.starts_with('.') => suffix,
+                _ => continue,
+            };
+            let is_wanted = format
+                .sidecars
+                .iter()
+                .any(|ext| suffix.ends_with(&format!(".{ext}")));
+            if is_wanted && entry.file_type().await?.is_file() {
+                res.push((entry.path(), suffix.to_string()));
+            }
+        }
+        res.sort();
+        Ok(res)
+    }
+
+    // Replaces `original_path` with `transcoded_path`, keeping the name
+    // but changing extension. Returns where the file ended up.
+    pub async fn move_transcoded_file(
+        &self,
+        transcoded_path: &Path,
+        original_path: &Path,
+    ) -> anyhow::Result<PathBuf> {
+        let ext = transcoded_path
+            .extension()
+            .context("expected file produced by ffmpeg to have extension")?;
+        if let Err(e) = tokio::fs::remove_file(original_path).await {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                return Err(e.into());
+            }
+        }
+        let destination_path = pick_free_file_name(&original_path.with_extension(ext)).await;
+
+        tokio::fs::rename(transcoded_path, &destination_path).await?;
+
+        Ok(destination_path)
     }
 
     pub async fn make_log_files(&self, task_id: Uuid) -> anyhow::Result<(File, File)> {
@@ -136,6 +437,13 @@ impl FilesystemDriver {
         };
         Ok((stdout_file, stderr_file))
     }
+
+    pub async fn make_hooks_log_file(&self, task_id: Uuid) -> anyhow::Result<File> {
+        let path = self.get_hooks_log_file(task_id);
+        let mut opts = tokio::fs::OpenOptions::new();
+        opts.create(true).append(true);
+        Ok(opts.open(path).await?)
+    }
 }
 
 pub async fn pick_free_file_name(original_path: &Path) -> PathBuf {
@@ -166,6 +474,66 @@ pub async fn pick_free_file_name(original_path: &Path) -> PathBuf {
     }
 }
 
+// Latest modification time of files directly within directory.
+async fn last_modified(path: &Path) -> SystemTime {
+    let mut res = SystemTime::UNIX_EPOCH;
+    if let Ok(mut entries) = tokio::fs::read_dir(path).await {
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            if let Ok(modified) = entry.metadata().await.and_then(|x| x.modified()) {
+                res = res.max(modified);
+            }
+        }
+    }
+    res
+}
+
+// Total size of files within directory, skipping whatever can't be read.
+pub async fn dir_size(path: &Path) -> u64 {
+    let mut total = 0u64;
+    let mut pending = vec![path.to_owned()];
+    while let Some(dir) = pending.pop() {
+        let mut entries = match tokio::fs::read_dir(&dir).await {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            match entry.metadata().await {
+                Ok(metadata) if metadata.is_dir() => pending.push(entry.path()),
+                Ok(metadata) => total += metadata.len(),
+                Err(_) => {}
+            }
+        }
+    }
+    total
+}
+
+// Directories named after a task, other than the ones of `tasks_to_keep`.
+async fn sweep_task_dirs(root: &Path, tasks_to_keep: &HashSet<Uuid>) -> anyhow::Result<SweepStats> {
+    let mut stats = SweepStats {
+        last_sweep_at: Some(Utc::now()),
+        ..Default::default()
+    };
+    let mut entries = tokio::fs::read_dir(root).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let task_id = match Uuid::parse_str(&entry.file_name().to_string_lossy()) {
+            Ok(task_id) => task_id,
+            Err(_) => continue,
+        };
+        if tasks_to_keep.contains(&task_id) || !entry.file_type().await?.is_dir() {
+            continue;
+        }
+        let num_bytes = dir_size(&entry.path()).await;
+        match tokio::fs::remove_dir_all(entry.path()).await {
+            Ok(()) => {
+                stats.num_dirs_removed += 1;
+                stats.bytes_reclaimed += num_bytes;
+            }
+            Err(e) => warn!("Failed to remove {:?}: {e}", entry.path()),
+        }
+    }
+    Ok(stats)
+}
+
 pub async fn ensure_writable_dir_exists(path: &Path) -> anyhow::Result<()> {
     if tokio::fs::metadata(path).await.is_err() {
         // Path does not exist, is ill-formed OR we don't have permissions.
@@ -175,6 +543,139 @@ pub async fn ensure_writable_dir_exists(path: &Path) -> anyhow::Result<()> {
     Ok(())
 }
 
+// Same as above, but makes sure nobody else can look inside (on Unix).
+pub async fn ensure_private_dir_exists(path: &Path) -> anyhow::Result<()> {
+    ensure_writable_dir_exists(path).await?;
+    #[cfg(windows)]
+    restrict_to_owner(path).await?;
+    #[cfg(not(windows))]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        tokio::fs::set_permissions(path, std::fs::Permissions::from_mode(0o700)).await?;
+    }
+    Ok(())
+}
+
+// Writes file that's only accessible by the current user (on Unix).
+pub async fn write_private_file(path: &Path, contents: &[u8]) -> anyhow::Result<()> {
+    let mut opts = tokio::fs::OpenOptions::new();
+    opts.create(true).write(true).truncate(true);
+    #[cfg(not(windows))]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        opts.mode(0o600);
+        // Mode only applies to newly created files
+        if path_exists(path).await {
+            tokio::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600)).await?;
+        }
+    }
+    let mut file = opts.open(path).await?;
+    // Nothing has been written yet, files created before may have other ACLs
+    #[cfg(windows)]
+    restrict_to_owner(path).await?;
+    file.write_all(contents).await?;
+    file.flush().await?;
+    Ok(())
+}
+
 pub async fn path_exists(path: &Path) -> bool {
     matches!(tokio::fs::try_exists(path).await, Ok(true))
 }
+
+// Replaces the DACL with one granting full access to the owner and SYSTEM
+// only, inherited by whatever gets created inside. It's protected, so
+// nothing is inherited from parent directories either.
+#[cfg(windows)]
+async fn restrict_to_owner(path: &Path) -> anyhow::Result<()> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Security::Authorization::{
+        ConvertStringSecurityDescriptorToSecurityDescriptorW, SDDL_REVISION_1,
+    };
+    use windows_sys::Win32::Security::{
+        SetFileSecurityW, DACL_SECURITY_INFORMATION, PROTECTED_DACL_SECURITY_INFORMATION,
+        PSECURITY_DESCRIPTOR,
+    };
+    use windows_sys::Win32::System::Memory::LocalFree;
+
+    let sddl: Vec<u16> = "D:P(A;OICI;FA;;;OW)(A;OICI;FA;;;SY)"
+        .encode_utf16()
+        .chain(std::iter::once(0))
+        .collect();
+    let path_wide: Vec<u16> = path
+        .as_os_str()
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect();
+    tokio::task::spawn_blocking(move || -> std::io::Result<()> {
+        let mut descriptor: PSECURITY_DESCRIPTOR = std::ptr::null_mut();
+        let ret = unsafe {
+            ConvertStringSecurityDescriptorToSecurityDescriptorW(
+                sddl.as_ptr(),
+                SDDL_REVISION_1,
+                &mut descriptor,
+                std::ptr::null_mut(),
+            )
+        };
+        if ret == 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let ret = unsafe {
+            SetFileSecurityW(
+                path_wide.as_ptr(),
+                DACL_SECURITY_INFORMATION | PROTECTED_DACL_SECURITY_INFORMATION,
+                descriptor,
+            )
+        };
+        let result = if ret == 0 {
+            Err(std::io::Error::last_os_error())
+        } else {
+            Ok(())
+        };
+        unsafe { LocalFree(descriptor as isize) };
+        result
+    })
+    .await??;
+    Ok(())
+}
+
+#[cfg(windows)]
+pub async fn available_space(path: &Path) -> anyhow::Result<u64> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+
+    let path_wide: Vec<u16> = path
+        .as_os_str()
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect();
+    let free_bytes = tokio::task::spawn_blocking(move || -> std::io::Result<u64> {
+        let mut free_bytes = 0u64;
+        let ret = unsafe {
+            GetDiskFreeSpaceExW(
+                path_wide.as_ptr(),
+                &mut free_bytes,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+            )
+        };
+        if ret == 0 {
+            Err(std::io::Error::last_os_error())
+        } else {
+            Ok(free_bytes)
+        }
+    })
+    .await??;
+    Ok(free_bytes)
+}
+
+#[cfg(not(windows))]
+pub async fn available_space(path: &Path) -> anyhow::Result<u64> {
+    use nix::sys::statvfs::statvfs;
+
+    let path = path.to_owned();
+    let stat = tokio::task::spawn_blocking(move || statvfs(&path)).await??;
+    // Field types differ between platforms, hence the casts.
+    #[allow(clippy::unnecessary_cast)]
+    let free_bytes = stat.blocks_available() as u64 * stat.fragment_size() as u64;
+    Ok(free_bytes)
+}
@@ -0,0 +1,24 @@
+use crate::job::JobStatus;
+use rocket::serde::{Deserialize, Serialize};
+
+// Noteworthy things that happen to jobs, which the user may want to know about.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, sqlx::Type, PartialEq)]
+pub enum JobEvent {
+    JobDone,
+    JobPartiallyDone,
+    JobFailed,
+    FetchFailed,
+    PlaylistEmpty,
+}
+
+impl JobEvent {
+    // Event for a job that has no more tasks to process.
+    pub fn from_finished_job_status(status: JobStatus) -> Option<Self> {
+        match status {
+            JobStatus::Done => Some(JobEvent::JobDone),
+            JobStatus::PartiallyDone => Some(JobEvent::JobPartiallyDone),
+            JobStatus::Failed => Some(JobEvent::JobFailed),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,219 @@
+use crate::job::Job;
+use crate::task::{Task, TaskKind, TaskStatus};
+use chrono::{DateTime, Utc};
+use rocket::serde::Serialize;
+use std::path::Path;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ManifestFormat {
+    Json,
+    Csv,
+    M3u,
+}
+
+impl ManifestFormat {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "json" => Some(ManifestFormat::Json),
+            "csv" => Some(ManifestFormat::Csv),
+            "m3u" | "m3u8" => Some(ManifestFormat::M3u),
+            _ => None,
+        }
+    }
+
+    pub fn ext(self) -> &'static str {
+        match self {
+            ManifestFormat::Json => "json",
+            ManifestFormat::Csv => "csv",
+            ManifestFormat::M3u => "m3u8",
+        }
+    }
+}
+
+// One downloaded video, with the file it finally ended up in.
+#[derive(Debug, Clone, Serialize)]
+pub struct ManifestItem {
+    pub task_id: Uuid,
+    pub title: String,
+    pub url: String,
+    pub uploader: Option<String>,
+    pub format: String,
+    pub status: TaskStatus,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub path: Option<String>,
+    pub size: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JobManifest {
+    pub job_id: Uuid,
+    pub title: String,
+    pub url: String,
+    pub created_at: DateTime<Utc>,
+    pub items: Vec<ManifestItem>,
+}
+
+impl JobManifest {
+    // Expects tasks in `task_index` order. Transcoded file replaces the
+    // downloaded one, so it's what the item points to once it's done.
+    pub fn new(job: &Job) -> Self {
+        let items = job
+            .tasks
+            .iter()
+            .filter(|x| x.kind == TaskKind::DownloadAndConvert)
+            .map(|download| {
+                let task = final_task_of(&job.tasks, download);
+                let is_done = task.status == TaskStatus::Done;
+                ManifestItem {
+                    task_id: download.task_id,
+                    title: download.title.clone(),
+                    url: download.url.clone(),
+                    uploader: download.uploader.clone(),
+                    format: download.format.clone(),
+                    status: task.status,
+                    finished_at: task.finished_at,
+                    path: task.output_path.clone().filter(|_| is_done),
+                    size: task.output_size.filter(|_| is_done),
+                }
+            })
+            .collect();
+        Self {
+            job_id: job.job_id,
+            title: job.title.clone(),
+            url: job.url.clone(),
+            created_at: job.created_at,
+            items,
+        }
+    }
+
+    // Files inside `base_dir` are referenced by relative paths,
+    // so the playlist survives moving the whole folder.
+    pub fn to_m3u(&self, base_dir: Option<&Path>) -> String {
+        let mut ret = "#EXTM3U\n".to_string();
+        ret += &format!("#PLAYLIST:{}\n", one_line(&self.title));
+        for item in &self.items {
+            let path = match &item.path {
+                Some(x) => Path::new(x),
+                None => continue,
+            };
+            let path = base_dir
+                .and_then(|dir| path.strip_prefix(dir).ok())
+                .unwrap_or(path);
+            ret += &format!("#EXTINF:-1,{}\n", one_line(&item.title));
+            ret += &format!("{}\n", path.to_string_lossy());
+        }
+        ret
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut ret =
+            "task_id,title,url,uploader,format,status,finished_at,path,size\n".to_string();
+        for item in &self.items {
+            let fields = [
+                item.task_id.to_string(),
+                item.title.clone(),
+                item.url.clone(),
+                item.uploader.clone().unwrap_or_default(),
+                item.format.clone(),
+                format!("{:?}", item.status),
+                item.finished_at.map(|x| x.to_rfc3339()).unwrap_or_default(),
+                item.path.clone().unwrap_or_default(),
+                item.size.map(|x| x.to_string()).unwrap_or_default(),
+            ];
+            let fields: Vec<String> = fields.iter().map(|x| csv_field(x)).collect();
+            ret += &fields.join(",");
+            ret += "\n";
+        }
+        ret
+    }
+}
+
+fn final_task_of<'a>(tasks: &'a [Task], download: &'a Task) -> &'a Task {
+    tasks
+        .iter()
+        .find(|x| {
+            x.kind == TaskKind::Transcode
+                && x.depends_on == Some(download.task_id)
+                && x.status == TaskStatus::Done
+        })
+        .unwrap_or(download)
+}
+
+fn one_line(value: &str) -> String {
+    value.replace(['\r', '\n'], " ")
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_item(title: &str, path: Option<&str>) -> ManifestItem {
+        ManifestItem {
+            task_id: Uuid::nil(),
+            title: title.to_string(),
+            url: "https://example.com/watch?v=1".to_string(),
+            uploader: None,
+            format: "mp3".to_string(),
+            status: TaskStatus::Done,
+            finished_at: None,
+            path: path.map(str::to_string),
+            size: path.map(|_| 1000),
+        }
+    }
+
+    fn new_manifest(items: Vec<ManifestItem>) -> JobManifest {
+        JobManifest {
+            job_id: Uuid::nil(),
+            title: "Best of\r\n2026".to_string(),
+            url: "https://example.com/playlist?list=1".to_string(),
+            created_at: Utc::now(),
+            items,
+        }
+    }
+
+    #[test]
+    fn csv_quotes_only_fields_that_need_it() {
+        let manifest = new_manifest(vec![
+            new_item("Plain", Some("/music/plain.mp3")),
+            new_item("One, \"Two\"\nThree", None),
+        ]);
+        let nil = Uuid::nil();
+        let url = "https://example.com/watch?v=1";
+        assert_eq!(
+            manifest.to_csv(),
+            format!(
+                "task_id,title,url,uploader,format,status,finished_at,path,size\n\
+                 {nil},Plain,{url},,mp3,Done,,/music/plain.mp3,1000\n\
+                 {nil},\"One, \"\"Two\"\"\nThree\",{url},,mp3,Done,,,\n"
+            )
+        );
+    }
+
+    #[test]
+    fn m3u_flattens_titles_and_strips_base_dir() {
+        let manifest = new_manifest(vec![
+            new_item("First\nline", Some("/music/a/first.mp3")),
+            new_item("Missing", None),
+            new_item("Elsewhere", Some("/other/second.mp3")),
+        ]);
+        assert_eq!(
+            manifest.to_m3u(Some(Path::new("/music"))),
+            "#EXTM3U\n\
+             #PLAYLIST:Best of  2026\n\
+             #EXTINF:-1,First line\n\
+             a/first.mp3\n\
+             #EXTINF:-1,Elsewhere\n\
+             /other/second.mp3\n"
+        );
+        assert!(manifest.to_m3u(None).contains("\n/music/a/first.mp3\n"));
+    }
+}
@@ -1,12 +1,22 @@
 use crate::database::JobFetch;
+use crate::playlist::NestedPlaylist;
 use crate::task::{Task, TaskKind, TaskProgress, TaskStatus};
+use crate::url_host::serialize_redacted_proxy;
 use chrono::{DateTime, Utc};
+pub use event::JobEvent;
+pub use manifest::{JobManifest, ManifestFormat};
+pub use selection::{parse_entry_indices, SelectionRules};
 use rocket::serde::Serialize;
 use serde::Deserialize;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::default::Default;
+use tracing::warn;
 use uuid::Uuid;
 
+mod event;
+mod manifest;
+mod selection;
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, sqlx::Type)]
 pub enum JobStatus {
     Waiting,
@@ -16,6 +26,18 @@ pub enum JobStatus {
     Paused,
     Failed,
     Cancelled,
+    AwaitingSelection,
+}
+
+// Playlist found inside of the job's one, e.g. a tab of a channel.
+// Whatever was found in it depends on the task which fetched it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobGroup {
+    pub task_id: Uuid,
+    // `None` if found directly in the job's playlist
+    pub parent_id: Option<Uuid>,
+    pub title: String,
+    pub url: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -32,49 +54,50 @@ pub struct Job {
     pub progress: HashMap<Uuid, TaskProgress>,
     pub prioritized: bool,
     pub title: String,
+    pub credentials_profile: Option<String>,
+    #[serde(serialize_with = "serialize_redacted_proxy")]
+    pub proxy: Option<String>,
+    pub transcode_preset: Option<String>,
+    pub is_group: bool,
+    pub playlist_path: Option<String>,
+    pub preview: bool,
+    #[serde(flatten)]
+    pub selection: SelectionRules,
+    #[serde(default)]
+    pub groups: Vec<JobGroup>,
 }
 
 impl Job {
     pub fn status_from_tasks(tasks: &[Task]) -> JobStatus {
-        // HACK: since we know there's only 1 FetchUrlContents task,
-        //       and it's executed before all other tasks,
-        //       we can short-circuit most cases here.
-        if let Some(fetch_task) = tasks.iter().find(|x| x.kind == TaskKind::FetchUrlContents) {
-            match fetch_task.status {
-                TaskStatus::Waiting => return JobStatus::Waiting,
-                TaskStatus::Processing => return JobStatus::Processing,
-                TaskStatus::Paused => return JobStatus::Paused,
-                TaskStatus::Failed => return JobStatus::Failed,
-                TaskStatus::Cancelled => return JobStatus::Cancelled,
-                TaskStatus::Done => {}
-            }
+        let statuses: Vec<TaskStatus> = tasks.iter().map(|x| x.status).collect();
+        if statuses.contains(&TaskStatus::Processing) {
+            return JobStatus::Processing;
+        } else if statuses.contains(&TaskStatus::Waiting) {
+            return JobStatus::Waiting;
+        } else if statuses.contains(&TaskStatus::Paused) {
+            return JobStatus::Paused;
         }
 
-        // HACK: FetchUrlContents task is something of a phony task.
-        //       We don't care about it succeeding if everything else
-        //       has failed, so it shouldn't count for 'PartiallyDone' jobs.
-        let statuses: Vec<TaskStatus> = tasks
+        // Everything has finished, so the outcome is decided by the last
+        // stages of the pipeline. Intermediate tasks (e.g. fetching the playlist)
+        // being done doesn't matter if whatever depended on them has failed.
+        let dependencies: HashSet<Uuid> = tasks.iter().filter_map(|x| x.depends_on).collect();
+        let final_statuses: Vec<TaskStatus> = tasks
             .iter()
-            .filter_map(|x| match x.kind {
-                TaskKind::FetchUrlContents => None,
-                TaskKind::DownloadAndConvert => Some(x.status),
-            })
+            .filter(|x| !dependencies.contains(&x.task_id))
+            .map(|x| x.status)
             .collect();
-        if statuses.is_empty() {
+        if final_statuses.is_empty() {
             JobStatus::Done
-        } else if statuses.contains(&TaskStatus::Processing) {
-            JobStatus::Processing
-        } else if statuses.contains(&TaskStatus::Waiting) {
-            JobStatus::Waiting
-        } else if statuses.contains(&TaskStatus::Paused) {
-            JobStatus::Paused
-        } else if statuses.contains(&TaskStatus::Done) {
-            if statuses.contains(&TaskStatus::Cancelled) || statuses.contains(&TaskStatus::Failed) {
+        } else if final_statuses.contains(&TaskStatus::Done) {
+            if final_statuses.contains(&TaskStatus::Cancelled)
+                || final_statuses.contains(&TaskStatus::Failed)
+            {
                 JobStatus::PartiallyDone
             } else {
                 JobStatus::Done
             }
-        } else if statuses.contains(&TaskStatus::Cancelled) {
+        } else if final_statuses.contains(&TaskStatus::Cancelled) {
             JobStatus::Cancelled
         } else {
             JobStatus::Failed
@@ -92,10 +115,74 @@ impl Job {
         }
     }
 
+    fn groups_from_tasks(tasks: &[Task]) -> Vec<JobGroup> {
+        let nested: HashSet<Uuid> = tasks
+            .iter()
+            .filter(|x| x.kind == TaskKind::FetchUrlContents && x.depends_on.is_some())
+            .map(|x| x.task_id)
+            .collect();
+        tasks
+            .iter()
+            .filter(|x| nested.contains(&x.task_id))
+            .map(|x| JobGroup {
+                task_id: x.task_id,
+                parent_id: x.depends_on.filter(|id| nested.contains(id)),
+                title: x.title.clone(),
+                url: x.url.clone(),
+            })
+            .collect()
+    }
+
+    // How many playlists deep the task was found, 0 for the job's own ones.
+    pub fn playlist_depth(&self, task: &Task) -> u32 {
+        let mut depth = 0;
+        let mut parent_id = task.depends_on;
+        while let Some(id) = parent_id {
+            parent_id = self
+                .tasks
+                .iter()
+                .find(|x| x.task_id == id)
+                .and_then(|x| x.depends_on);
+            depth += 1;
+        }
+        depth
+    }
+
+    // Each nested playlist found by `task` is fetched by a task of its own,
+    // unless `task` is already `max_depth` playlists deep.
+    pub fn nested_fetch_tasks(
+        &self,
+        task: &Task,
+        playlists: &[NestedPlaylist],
+        max_depth: u32,
+    ) -> Vec<Task> {
+        if playlists.is_empty() {
+            return vec![];
+        }
+        if self.playlist_depth(task) >= max_depth {
+            warn!(
+                "Skipping {} nested playlists of job {}, they're too deep",
+                playlists.len(),
+                self.job_id
+            );
+            return vec![];
+        }
+        playlists
+            .iter()
+            .map(|playlist| Task::new_nested_fetch(task, playlist))
+            .collect()
+    }
+
     pub fn new(fetch: JobFetch, tasks: Vec<Task>) -> Self {
-        let status = Self::status_from_tasks(&tasks);
+        let mut status = Self::status_from_tasks(&tasks);
+        // Contents are known, but nothing has been picked for download yet
+        let is_only_fetched = tasks.iter().all(|x| x.kind == TaskKind::FetchUrlContents);
+        if fetch.preview && is_only_fetched && matches!(status, JobStatus::Done) {
+            status = JobStatus::AwaitingSelection;
+        }
         let started_at = Self::started_at_from_tasks(&tasks);
         let finished_at = Self::finished_at_from_tasks(status, &tasks);
+        let groups = Self::groups_from_tasks(&tasks);
         Job {
             tasks,
             status,
@@ -109,6 +196,62 @@ impl Job {
             progress: Default::default(),
             prioritized: fetch.prioritized,
             title: fetch.title,
+            credentials_profile: fetch.credentials_profile,
+            proxy: fetch.proxy,
+            transcode_preset: fetch.transcode_preset,
+            is_group: fetch.is_group,
+            playlist_path: fetch.playlist_path,
+            preview: fetch.preview,
+            selection: fetch.selection,
+            groups,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::queue_command::QueueCommand;
+    use crate::test_util::{new_job_form, new_test_server, TempDir};
+
+    fn playlist(url: &str) -> NestedPlaylist {
+        NestedPlaylist {
+            url: url.to_string(),
+            title: url.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn nested_playlists_stop_at_max_depth() {
+        let dir = TempDir::new();
+        let (_, db) = new_test_server(&dir, |_| {}).await;
+        let db = db.lock().await;
+        let job = db
+            .create_job(&new_job_form("https://example.com/channel"))
+            .await
+            .unwrap();
+        let fetch = job.tasks[0].clone();
+        let tab = Task::new_nested_fetch(&fetch, &playlist("https://example.com/tab"));
+        let list = Task::new_nested_fetch(&tab, &playlist("https://example.com/list"));
+        db.modify_job(
+            job.job_id,
+            QueueCommand::TasksAdded(vec![tab.clone(), list.clone()]),
+        )
+        .await
+        .unwrap();
+        let job = db.get_job(job.job_id).await.unwrap();
+        assert_eq!(job.playlist_depth(&fetch), 0);
+        assert_eq!(job.playlist_depth(&tab), 1);
+        assert_eq!(job.playlist_depth(&list), 2);
+
+        let found = [
+            playlist("https://example.com/x"),
+            playlist("https://example.com/y"),
+        ];
+        let nested = job.nested_fetch_tasks(&tab, &found, 2);
+        assert_eq!(nested.len(), 2);
+        assert!(nested.iter().all(|x| x.depends_on == Some(tab.task_id)));
+        assert!(job.nested_fetch_tasks(&list, &found, 2).is_empty());
+        assert!(job.nested_fetch_tasks(&fetch, &found, 0).is_empty());
+    }
+}
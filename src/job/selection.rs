@@ -0,0 +1,199 @@
+use crate::database::PlaylistEntry;
+use regex::{Regex, RegexBuilder};
+use rocket::serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use std::collections::BTreeSet;
+
+// Picks entries of a preview job as soon as its contents are known.
+// Entries matching any of the rules get selected.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, FromRow)]
+pub struct SelectionRules {
+    #[serde(default)]
+    pub select_first: Option<u32>,
+    #[serde(default)]
+    pub select_newest: Option<u32>,
+    #[serde(default)]
+    pub select_title: Option<String>,
+}
+
+impl SelectionRules {
+    pub fn is_empty(&self) -> bool {
+        self.select_first.is_none() && self.select_newest.is_none() && self.select_title.is_none()
+    }
+
+    pub fn title_regex(&self) -> anyhow::Result<Option<Regex>> {
+        match &self.select_title {
+            Some(pattern) => Ok(Some(
+                RegexBuilder::new(pattern).case_insensitive(true).build()?,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    // Entries without a known date are considered older than the rest,
+    // among themselves playlist order is kept.
+    pub fn select(&self, entries: &[PlaylistEntry]) -> anyhow::Result<BTreeSet<u32>> {
+        let mut ret = BTreeSet::new();
+        if let Some(num) = self.select_first {
+            ret.extend(entries.iter().take(num as usize).map(|x| x.entry_index));
+        }
+        if let Some(num) = self.select_newest {
+            let mut by_date: Vec<&PlaylistEntry> = entries.iter().collect();
+            by_date.sort_by(|a, b| {
+                let date_of = |x: &PlaylistEntry| x.video.metadata.published_at;
+                date_of(b).cmp(&date_of(a))
+            });
+            ret.extend(by_date.iter().take(num as usize).map(|x| x.entry_index));
+        }
+        if let Some(regex) = self.title_regex()? {
+            ret.extend(
+                entries
+                    .iter()
+                    .filter(|x| regex.is_match(&x.video.title))
+                    .map(|x| x.entry_index),
+            );
+        }
+        Ok(ret)
+    }
+}
+
+// Same syntax as yt-dlp's `--playlist-items`, e.g. "1-3,7,10-".
+// Indices start at 1, ranges past the end are cut short.
+pub fn parse_entry_indices(spec: &str, num_entries: u32) -> Option<BTreeSet<u32>> {
+    let mut ret = BTreeSet::new();
+    for item in spec.split(',').map(str::trim).filter(|x| !x.is_empty()) {
+        let (start, end) = match item.split_once('-') {
+            Some((start, "")) => (start.trim().parse().ok()?, num_entries),
+            Some((start, end)) => (start.trim().parse().ok()?, end.trim().parse().ok()?),
+            None => {
+                let idx = item.parse().ok()?;
+                (idx, idx)
+            }
+        };
+        if start == 0 || start > num_entries || end < start {
+            return None;
+        }
+        ret.extend(start..=end.min(num_entries));
+    }
+    Some(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::playlist::{MediaMetadata, VideoInfo};
+    use chrono::{TimeZone, Utc};
+
+    fn entry(entry_index: u32, title: &str, published_day: Option<u32>) -> PlaylistEntry {
+        PlaylistEntry {
+            entry_index,
+            video: VideoInfo {
+                url: format!("https://example.com/{entry_index}"),
+                thumbnail: None,
+                title: title.to_string(),
+                bytes_estimate: None,
+                uploader: None,
+                metadata: MediaMetadata {
+                    published_at: published_day
+                        .map(|day| Utc.with_ymd_and_hms(2026, 1, day, 0, 0, 0).unwrap()),
+                    ..Default::default()
+                },
+            },
+            selected: false,
+        }
+    }
+
+    fn indices(x: &[u32]) -> BTreeSet<u32> {
+        x.iter().copied().collect()
+    }
+
+    #[test]
+    fn first_entries_are_taken_in_playlist_order() {
+        let entries = [
+            entry(1, "a", None),
+            entry(2, "b", None),
+            entry(3, "c", None),
+        ];
+        let rules = SelectionRules {
+            select_first: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(rules.select(&entries).unwrap(), indices(&[1, 2]));
+    }
+
+    #[test]
+    fn newest_entries_skip_the_ones_without_a_date() {
+        let entries = [
+            entry(1, "a", Some(3)),
+            entry(2, "b", None),
+            entry(3, "c", Some(9)),
+            entry(4, "d", Some(5)),
+        ];
+        let rules = SelectionRules {
+            select_newest: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(rules.select(&entries).unwrap(), indices(&[3, 4]));
+
+        let rules = SelectionRules {
+            select_newest: Some(4),
+            ..Default::default()
+        };
+        assert_eq!(rules.select(&entries).unwrap(), indices(&[1, 2, 3, 4]));
+    }
+
+    #[test]
+    fn titles_match_case_insensitively_and_rules_add_up() {
+        let entries = [
+            entry(1, "Intro", None),
+            entry(2, "Live Session", None),
+            entry(3, "Outro", None),
+            entry(4, "another live", None),
+        ];
+        let rules = SelectionRules {
+            select_first: Some(1),
+            select_title: Some("LIVE".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(rules.select(&entries).unwrap(), indices(&[1, 2, 4]));
+    }
+
+    #[test]
+    fn invalid_title_pattern_is_an_error() {
+        let rules = SelectionRules {
+            select_title: Some("(".to_string()),
+            ..Default::default()
+        };
+        assert!(rules.select(&[entry(1, "a", None)]).is_err());
+    }
+
+    #[test]
+    fn no_rules_select_nothing() {
+        let rules = SelectionRules::default();
+        assert!(rules.is_empty());
+        assert!(rules.select(&[entry(1, "a", None)]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn entry_indices_accept_ranges_and_open_ends() {
+        assert_eq!(
+            parse_entry_indices("1-3,7,10-", 12),
+            Some(indices(&[1, 2, 3, 7, 10, 11, 12]))
+        );
+        assert_eq!(
+            parse_entry_indices(" 2 , 4 - 5 ", 5),
+            Some(indices(&[2, 4, 5]))
+        );
+        assert_eq!(parse_entry_indices("3-100", 5), Some(indices(&[3, 4, 5])));
+        assert_eq!(parse_entry_indices("", 5), Some(indices(&[])));
+    }
+
+    #[test]
+    fn entry_indices_reject_what_does_not_exist() {
+        assert_eq!(parse_entry_indices("0", 5), None);
+        assert_eq!(parse_entry_indices("6", 5), None);
+        assert_eq!(parse_entry_indices("4-2", 5), None);
+        assert_eq!(parse_entry_indices("a-b", 5), None);
+        assert_eq!(parse_entry_indices("1,,x", 5), None);
+    }
+}
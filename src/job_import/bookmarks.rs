@@ -0,0 +1,46 @@
+use crate::job_import::ParsedImport;
+
+// Netscape bookmark file, which is what browsers export. Each bookmark
+// is an `<A HREF="...">` tag, folders and everything else are ignored.
+pub fn parse(contents: &str, parsed: &mut ParsedImport) {
+    let lowercase = contents.to_ascii_lowercase();
+    let mut pos = 0;
+    // Lines are counted as we go, up to the start of the current tag
+    let mut line = 1;
+    let mut line_counted_to = 0;
+    while let Some(offset) = lowercase[pos..].find("<a ") {
+        let tag_start = pos + offset;
+        let tag_end = lowercase[tag_start..]
+            .find('>')
+            .map_or(lowercase.len(), |x| tag_start + x);
+        pos = tag_end;
+
+        line += contents[line_counted_to..tag_start].matches('\n').count();
+        line_counted_to = tag_start;
+        match find_href(&contents[tag_start..tag_end]) {
+            Some(href) => parsed.add(line, &decode_entities(href), None),
+            None => parsed.error(line, "bookmark without HREF".to_string()),
+        }
+    }
+}
+
+fn find_href(tag: &str) -> Option<&str> {
+    let attr_start = tag.to_ascii_lowercase().find("href=")? + "href=".len();
+    let value = &tag[attr_start..];
+    match value.chars().next()? {
+        quote @ ('"' | '\'') => {
+            let value = &value[1..];
+            value.find(quote).map(|end| &value[..end])
+        }
+        _ => value.split(|c: char| c.is_whitespace()).next(),
+    }
+}
+
+fn decode_entities(value: &str) -> String {
+    value
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
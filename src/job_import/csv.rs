@@ -0,0 +1,88 @@
+use crate::job_import::{youtube_video_url, ParsedImport};
+
+const URL_COLUMNS: &[&str] = &["url", "video url", "link"];
+// Watch later list from Google Takeout only has video ids
+const VIDEO_ID_COLUMNS: &[&str] = &["video id", "video_id"];
+const FORMAT_COLUMNS: &[&str] = &["format"];
+
+enum UrlColumn {
+    Url(usize),
+    VideoId(usize),
+}
+
+pub fn is_header(first_line: &str) -> bool {
+    let fields = split_record(first_line);
+    fields.iter().any(|x| {
+        let x = x.trim().to_lowercase();
+        URL_COLUMNS.contains(&x.as_str()) || VIDEO_ID_COLUMNS.contains(&x.as_str())
+    })
+}
+
+fn find_column(header: &[String], names: &[&str]) -> Option<usize> {
+    header
+        .iter()
+        .position(|x| names.contains(&x.trim().to_lowercase().as_str()))
+}
+
+// Without a header first column is the URL, second one is the format.
+// Quoted fields can't span several lines.
+pub fn parse(contents: &str, parsed: &mut ParsedImport) {
+    let mut lines = contents
+        .lines()
+        .enumerate()
+        .map(|(idx, line)| (idx + 1, line))
+        .filter(|(_, line)| !line.trim().is_empty())
+        .peekable();
+
+    let mut url_column = UrlColumn::Url(0);
+    let mut format_column = Some(1);
+    if let Some((_, first_line)) = lines.peek() {
+        if is_header(&first_line.to_lowercase()) {
+            let header = split_record(first_line);
+            url_column = match find_column(&header, URL_COLUMNS) {
+                Some(idx) => UrlColumn::Url(idx),
+                None => UrlColumn::VideoId(find_column(&header, VIDEO_ID_COLUMNS).unwrap()),
+            };
+            format_column = find_column(&header, FORMAT_COLUMNS);
+            lines.next();
+        }
+    }
+
+    for (line, record) in lines {
+        let fields = split_record(record);
+        let format = format_column
+            .and_then(|idx| fields.get(idx))
+            .map(String::as_str);
+        match url_column {
+            UrlColumn::Url(idx) => match fields.get(idx) {
+                Some(url) => parsed.add(line, url, format),
+                None => parsed.error(line, "missing URL column".to_string()),
+            },
+            UrlColumn::VideoId(idx) => match fields.get(idx).map(|x| x.trim()) {
+                Some(id) if !id.is_empty() => parsed.add(line, &youtube_video_url(id), format),
+                _ => parsed.error(line, "missing video id".to_string()),
+            },
+        }
+    }
+}
+
+// Splits a line by commas, fields can be quoted with `"` and use `""` inside.
+fn split_record(line: &str) -> Vec<String> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
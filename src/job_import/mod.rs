@@ -0,0 +1,274 @@
+use crate::url_host::host_of_url;
+use rocket::serde::Serialize;
+use std::collections::HashSet;
+
+mod bookmarks;
+mod csv;
+mod takeout;
+mod text;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum ImportFormat {
+    Text,
+    Csv,
+    Bookmarks,
+    Takeout,
+}
+
+impl ImportFormat {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "text" => Some(ImportFormat::Text),
+            "csv" => Some(ImportFormat::Csv),
+            "bookmarks" => Some(ImportFormat::Bookmarks),
+            "takeout" => Some(ImportFormat::Takeout),
+            _ => None,
+        }
+    }
+
+    // CSV can only be told apart from a plain list by its header.
+    pub fn detect(contents: &str) -> Self {
+        let contents = contents.trim_start_matches('\u{feff}').trim_start();
+        if contents.starts_with('[') || contents.starts_with('{') {
+            return ImportFormat::Takeout;
+        }
+        if contents.starts_with('<') {
+            return ImportFormat::Bookmarks;
+        }
+        let first_line = contents.lines().next().unwrap_or_default().to_lowercase();
+        if csv::is_header(&first_line) {
+            return ImportFormat::Csv;
+        }
+        ImportFormat::Text
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ImportEntry {
+    pub line: usize,
+    pub url: String,
+    pub format: Option<String>,
+}
+
+// For JSON input `line` is the position of the entry in the list.
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportLineError {
+    pub line: usize,
+    pub message: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ImportOptions {
+    pub format: String,
+    pub credentials_profile: Option<String>,
+    pub proxy: Option<String>,
+    pub transcode_preset: Option<String>,
+    pub group: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct ParsedImport {
+    pub entries: Vec<ImportEntry>,
+    pub errors: Vec<ImportLineError>,
+    pub num_duplicates: u32,
+    seen_urls: HashSet<String>,
+}
+
+impl ParsedImport {
+    fn add(&mut self, line: usize, url: &str, format: Option<&str>) {
+        let url = url.trim();
+        if !is_importable_url(url) {
+            self.error(line, format!("not a valid URL: {url:?}"));
+            return;
+        }
+        if !self.seen_urls.insert(url_key(url)) {
+            self.num_duplicates += 1;
+            return;
+        }
+        self.entries.push(ImportEntry {
+            line,
+            url: url.to_string(),
+            format: format
+                .map(str::trim)
+                .filter(|x| !x.is_empty())
+                .map(str::to_string),
+        });
+    }
+
+    fn error(&mut self, line: usize, message: String) {
+        self.errors.push(ImportLineError { line, message });
+    }
+}
+
+pub fn parse_import<F: Fn(&str) -> bool>(
+    contents: &str,
+    format: ImportFormat,
+    is_known_format: F,
+) -> ParsedImport {
+    let contents = contents.trim_start_matches('\u{feff}');
+    let mut parsed = ParsedImport::default();
+    match format {
+        ImportFormat::Text => text::parse(contents, &mut parsed),
+        ImportFormat::Csv => csv::parse(contents, &mut parsed),
+        ImportFormat::Bookmarks => bookmarks::parse(contents, &mut parsed),
+        ImportFormat::Takeout => takeout::parse(contents, &mut parsed),
+    }
+
+    let (entries, rejected): (Vec<_>, Vec<_>) = parsed
+        .entries
+        .into_iter()
+        .partition(|x| x.format.as_deref().is_none_or(&is_known_format));
+    parsed.entries = entries;
+    for entry in rejected {
+        let format = entry.format.unwrap_or_default();
+        parsed.error(entry.line, format!("unknown format {format:?}"));
+    }
+    parsed.errors.sort_by_key(|x| x.line);
+    parsed
+}
+
+// Whatever yt-dlp would accept is fine, but only web URLs are
+// expected to come out of a list or bookmarks.
+fn is_importable_url(url: &str) -> bool {
+    let lowercase = url.to_lowercase();
+    (lowercase.starts_with("http://") || lowercase.starts_with("https://"))
+        && !url.chars().any(|c| c.is_whitespace() || c.is_control())
+        && host_of_url(url).is_some()
+}
+
+// URLs which only differ in the fragment point to the same video.
+pub fn url_key(url: &str) -> String {
+    let url = url.trim();
+    url.split('#').next().unwrap_or(url).to_string()
+}
+
+fn youtube_video_url(video_id: &str) -> String {
+    format!("https://www.youtube.com/watch?v={video_id}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(contents: &str) -> ParsedImport {
+        parse_import(contents, ImportFormat::detect(contents), |x| x != "bad")
+    }
+
+    fn urls(parsed: &ParsedImport) -> Vec<(usize, &str, Option<&str>)> {
+        parsed
+            .entries
+            .iter()
+            .map(|x| (x.line, x.url.as_str(), x.format.as_deref()))
+            .collect()
+    }
+
+    fn errors(parsed: &ParsedImport) -> Vec<usize> {
+        parsed.errors.iter().map(|x| x.line).collect()
+    }
+
+    #[test]
+    fn format_is_detected_from_contents() {
+        assert_eq!(ImportFormat::detect("https://a.com/1"), ImportFormat::Text);
+        assert_eq!(
+            ImportFormat::detect("\u{feff}URL,Format\n"),
+            ImportFormat::Csv
+        );
+        assert_eq!(
+            ImportFormat::detect("  <!DOCTYPE NETSCAPE"),
+            ImportFormat::Bookmarks
+        );
+        assert_eq!(ImportFormat::detect("[{}]"), ImportFormat::Takeout);
+    }
+
+    #[test]
+    fn text_skips_comments_and_duplicates() {
+        let parsed = parse(
+            "# list\n\
+             https://a.com/1\n\
+             \n\
+             https://a.com/1#t=10\n\
+             ftp://a.com/2\n\
+             https://a.com/3\n",
+        );
+        assert_eq!(
+            urls(&parsed),
+            [(2, "https://a.com/1", None), (6, "https://a.com/3", None)]
+        );
+        assert_eq!(parsed.num_duplicates, 1);
+        assert_eq!(errors(&parsed), [5]);
+    }
+
+    #[test]
+    fn csv_reads_columns_by_header() {
+        let parsed = parse(
+            "Title,Link,Format\n\
+             \"Some, title\",https://a.com/1,mp3\n\
+             Other,https://a.com/2,\n\
+             Third,https://a.com/3,bad\n",
+        );
+        assert_eq!(
+            urls(&parsed),
+            [
+                (2, "https://a.com/1", Some("mp3")),
+                (3, "https://a.com/2", None)
+            ]
+        );
+        assert_eq!(errors(&parsed), [4]);
+    }
+
+    #[test]
+    fn csv_with_video_ids_makes_youtube_urls() {
+        let parsed = parse("Video ID,Playlist Video Creation Timestamp\nabc,2023\n,2023\n");
+        assert_eq!(
+            urls(&parsed),
+            [(2, "https://www.youtube.com/watch?v=abc", None)]
+        );
+        assert_eq!(errors(&parsed), [3]);
+    }
+
+    #[test]
+    fn bookmarks_report_lines_of_their_tags() {
+        let parsed = parse(
+            "<!DOCTYPE NETSCAPE-Bookmark-file-1>\n\
+             <DL><p>\n\
+             <DT><A HREF=\"https://a.com/1?x=1&amp;y=2\" ADD_DATE=\"1\">One</A>\n\
+             <DT><H3>Folder</H3>\n\
+             <DT><a name=\"no link\">Two</a>\n\
+             <DT><A HREF='https://a.com/3'>Three</A> <A HREF=https://a.com/4>Four</A>\n",
+        );
+        assert_eq!(
+            urls(&parsed),
+            [
+                (3, "https://a.com/1?x=1&y=2", None),
+                (6, "https://a.com/3", None),
+                (6, "https://a.com/4", None),
+            ]
+        );
+        assert_eq!(errors(&parsed), [5]);
+    }
+
+    #[test]
+    fn takeout_reads_history_and_playlists() {
+        let history = parse(r#"[{"titleUrl": "https://a.com/1"}, {"title": "removed"}]"#);
+        assert_eq!(urls(&history), [(1, "https://a.com/1", None)]);
+        assert_eq!(errors(&history), [2]);
+
+        let playlist = parse(
+            r#"{"items": [
+                {"contentDetails": {"videoId": "abc"}},
+                {"snippet": {"resourceId": {"videoId": "def"}}}
+            ]}"#,
+        );
+        assert_eq!(
+            urls(&playlist),
+            [
+                (1, "https://www.youtube.com/watch?v=abc", None),
+                (2, "https://www.youtube.com/watch?v=def", None),
+            ]
+        );
+
+        let broken = parse("[{\"titleUrl\": ");
+        assert!(broken.entries.is_empty());
+        assert_eq!(broken.errors.len(), 1);
+    }
+}
@@ -0,0 +1,54 @@
+use crate::job_import::{youtube_video_url, ParsedImport};
+use serde_json::Value;
+
+// Google Takeout exports of YouTube lists. Watch history is a list of
+// entries with `titleUrl`, playlists (including watch later) follow the
+// YouTube API and keep video id under `contentDetails` or `snippet`.
+pub fn parse(contents: &str, parsed: &mut ParsedImport) {
+    let value: Value = match serde_json::from_str(contents) {
+        Ok(x) => x,
+        Err(e) => {
+            parsed.error(e.line(), format!("invalid JSON: {e}"));
+            return;
+        }
+    };
+    let items = match &value {
+        Value::Array(items) => items,
+        Value::Object(object) => match object.get("items") {
+            Some(Value::Array(items)) => items,
+            _ => {
+                parsed.error(1, "expected a list of videos".to_string());
+                return;
+            }
+        },
+        _ => {
+            parsed.error(1, "expected a list of videos".to_string());
+            return;
+        }
+    };
+
+    for (idx, item) in items.iter().enumerate() {
+        match url_of_item(item) {
+            Some(url) => parsed.add(idx + 1, &url, None),
+            None => parsed.error(idx + 1, "entry has no video URL".to_string()),
+        }
+    }
+}
+
+fn url_of_item(item: &Value) -> Option<String> {
+    for pointer in ["/titleUrl", "/url"] {
+        if let Some(url) = item.pointer(pointer).and_then(Value::as_str) {
+            return Some(url.to_string());
+        }
+    }
+    for pointer in [
+        "/contentDetails/videoId",
+        "/snippet/resourceId/videoId",
+        "/videoId",
+    ] {
+        if let Some(id) = item.pointer(pointer).and_then(Value::as_str) {
+            return Some(youtube_video_url(id));
+        }
+    }
+    None
+}
@@ -0,0 +1,12 @@
+use crate::job_import::ParsedImport;
+
+// One URL per line, empty lines and `#` comments are skipped.
+pub fn parse(contents: &str, parsed: &mut ParsedImport) {
+    for (idx, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        parsed.add(idx + 1, line, None);
+    }
+}
@@ -1,8 +1,10 @@
 use crate::announce::AnnounceSystem;
+use crate::credentials::CredentialStore;
 use crate::database::ServerDatabase;
 use crate::env::EnvironmentManager;
 use crate::filesystem::FilesystemDriver;
 use crate::job_manager::JobManager;
+use crate::metrics::ServerMetrics;
 use std::ops::Deref;
 use std::sync::Arc;
 use tokio::sync::Mutex;
@@ -25,10 +27,12 @@ impl JobManagerHandle {
         announcements: Arc<AnnounceSystem>,
         env: Arc<EnvironmentManager>,
         fs: Arc<FilesystemDriver>,
+        credentials: Arc<CredentialStore>,
         db: Arc<Mutex<ServerDatabase>>,
+        metrics: Arc<ServerMetrics>,
     ) -> anyhow::Result<Self> {
         Ok(Self {
-            ptr: Arc::new(JobManager::new(announcements, env, fs, db).await?),
+            ptr: Arc::new(JobManager::new(announcements, env, fs, credentials, db, metrics).await?),
         })
     }
 }
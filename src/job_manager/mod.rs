@@ -1,32 +1,64 @@
 use crate::announce::AnnounceSystem;
-use crate::database::{ServerDatabase, TaskStats};
-use crate::download_manager::DownloadManager;
+use crate::credentials::CredentialStore;
+use crate::database::{
+    AcquireDecision, HistoryBucket, HistoryStats, ImportedJobs, PlaylistEntry, ServerDatabase,
+    TaskStats,
+};
+use crate::download_manager::{DownloadManager, HostCheck, WorkerControlHandle};
 use crate::env::EnvironmentManager;
-use crate::filesystem::FilesystemDriver;
-use crate::job::Job;
+use crate::filesystem::{FilesystemDriver, SweepStats};
+use crate::job::{Job, JobManifest, JobStatus};
+use crate::job_import::{ImportEntry, ImportOptions};
+use crate::metrics::{MetricsWriter, ServerMetrics};
+use crate::playlist::VideoOrPlaylist;
 use crate::process::GenericStopHandle;
 use crate::queue_command::QueueCommand;
-use crate::task::{Task, TaskKind, TaskResult, TaskResultData, TaskStatus};
+use crate::task::{
+    run_post_download_hooks, FailureKind, Task, TaskArtifact, TaskKind, TaskResult, TaskResultData,
+    TaskStatus, WorkerPool,
+};
 use crate::web_api::NewJobForm;
+use chrono::{DateTime, Utc};
 use dirty_marker::DirtyMarker;
 pub use handle::JobManagerHandle;
+pub use pause_reason::PauseReason;
+use retention::select_jobs_to_prune;
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::sync::{Mutex, MutexGuard};
 use tracing::{info, warn};
 use uuid::Uuid;
 
 mod dirty_marker;
 mod handle;
+mod pause_reason;
+mod retention;
+
+const DISK_SPACE_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+const WORKER_DIR_SWEEP_INTERVAL: Duration = Duration::from_secs(3600);
+const HISTORY_PRUNE_INTERVAL: Duration = Duration::from_secs(600);
+const RETRY_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+const BACKUP_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+const METRICS_SAVE_INTERVAL: Duration = Duration::from_secs(10);
 
 #[derive(Debug)]
 pub struct JobManager {
     announcements: Arc<AnnounceSystem>,
+    env: Arc<EnvironmentManager>,
+    fs: Arc<FilesystemDriver>,
     db: Arc<Mutex<ServerDatabase>>,
     dload_manager: (Mutex<DownloadManager>,),
     stop_handle: GenericStopHandle,
     jobs_dirty: DirtyMarker,
     cleanup_dirty: DirtyMarker,
+    pause_reason: Mutex<Option<PauseReason>>,
+    held_back_tasks: Mutex<HashMap<Uuid, PauseReason>>,
+    sweep_stats: Mutex<SweepStats>,
+    metrics: Arc<ServerMetrics>,
+    hooks_control_handle: WorkerControlHandle,
 }
 
 impl JobManager {
@@ -34,15 +66,33 @@ impl JobManager {
         announcements: Arc<AnnounceSystem>,
         env: Arc<EnvironmentManager>,
         fs: Arc<FilesystemDriver>,
+        credentials: Arc<CredentialStore>,
         db: Arc<Mutex<ServerDatabase>>,
+        metrics: Arc<ServerMetrics>,
     ) -> anyhow::Result<Self> {
+        db.lock()
+            .await
+            .restore_interrupted_tasks(env.config.auto_resume_on_start)
+            .await?;
         Ok(Self {
             announcements,
+            env: env.clone(),
+            fs: fs.clone(),
             db,
-            dload_manager: (Mutex::new(DownloadManager::new(env.clone(), fs)),),
+            dload_manager: (Mutex::new(DownloadManager::new(
+                env,
+                fs,
+                credentials,
+                metrics.clone(),
+            )),),
             stop_handle: GenericStopHandle::new(),
             jobs_dirty: DirtyMarker::new(),
             cleanup_dirty: DirtyMarker::new(),
+            pause_reason: Mutex::new(None),
+            held_back_tasks: Mutex::new(HashMap::new()),
+            sweep_stats: Mutex::new(SweepStats::default()),
+            metrics,
+            hooks_control_handle: WorkerControlHandle::new(),
         })
     }
 
@@ -60,7 +110,38 @@ impl JobManager {
         ret
     }
 
+    pub async fn import_jobs(
+        &self,
+        entries: &[ImportEntry],
+        options: &ImportOptions,
+    ) -> anyhow::Result<ImportedJobs> {
+        let db_lock = self.db.lock().await;
+
+        let ret = db_lock.import_jobs(entries, options).await;
+        self.mark_dirty();
+        ret
+    }
+
+    pub async fn get_playlist_entries(&self, job_id: Uuid) -> anyhow::Result<Vec<PlaylistEntry>> {
+        self.db.lock().await.get_playlist_entries(job_id).await
+    }
+
+    pub async fn select_playlist_entries(
+        &self,
+        job_id: Uuid,
+        indices: &BTreeSet<u32>,
+    ) -> anyhow::Result<Job> {
+        self.db
+            .lock()
+            .await
+            .select_playlist_entries(job_id, indices)
+            .await?;
+        self.mark_dirty();
+        self.get_job(job_id).await
+    }
+
     pub async fn modify_all_jobs(&self, command: QueueCommand) -> anyhow::Result<()> {
+        self.on_queue_command(&command).await;
         let (mut dload_manager, db_lock) = self.lock_downloads().await;
 
         dload_manager.modify_all_tasks(&command);
@@ -71,6 +152,7 @@ impl JobManager {
     }
 
     pub async fn modify_job(&self, id: Uuid, command: QueueCommand) -> anyhow::Result<()> {
+        self.on_queue_command(&command).await;
         let (mut dload_manager, db_lock) = self.lock_downloads().await;
 
         dload_manager.modify_tasks_by_job(id, &command);
@@ -81,6 +163,7 @@ impl JobManager {
     }
 
     pub async fn modify_task(&self, id: Uuid, command: QueueCommand) -> anyhow::Result<()> {
+        self.on_queue_command(&command).await;
         let (mut dload_manager, db_lock) = self.lock_downloads().await;
 
         dload_manager.modify_task(id, &command);
@@ -119,23 +202,399 @@ impl JobManager {
         Ok(jobs)
     }
 
+    pub async fn get_task_artifacts(&self, task_id: Uuid) -> anyhow::Result<Vec<TaskArtifact>> {
+        let db_lock = self.db.lock().await;
+        db_lock.get_task_artifacts(task_id).await
+    }
+
+    pub async fn is_task_active(&self, task_id: Uuid) -> bool {
+        self.dload_manager.0.lock().await.is_task_active(task_id)
+    }
+
     pub async fn get_overall_stats(&self) -> anyhow::Result<TaskStats> {
         let db_lock = self.db.lock().await;
         db_lock.get_global_task_stats().await
     }
 
+    pub async fn get_history_stats(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        bucket: HistoryBucket,
+    ) -> anyhow::Result<HistoryStats> {
+        self.db
+            .lock()
+            .await
+            .get_history_stats(from, to, bucket)
+            .await
+    }
+
+    pub async fn get_pause_reason(&self) -> Option<PauseReason> {
+        self.pause_reason.lock().await.clone()
+    }
+
+    pub async fn get_held_back_tasks(&self) -> Vec<PauseReason> {
+        self.held_back_tasks
+            .lock()
+            .await
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    pub async fn get_sweep_stats(&self) -> SweepStats {
+        self.sweep_stats.lock().await.clone()
+    }
+
+    pub async fn render_metrics(&self) -> anyhow::Result<String> {
+        let task_counts = self.db.lock().await.get_task_counts().await?;
+        let mut writer = MetricsWriter::default();
+        writer.header("ytdl_tasks", "gauge", "Number of tasks by status and kind.");
+        // Every series is there even when empty, so it doesn't go missing
+        // from graphs once the last such task is gone.
+        for status in TaskStatus::ALL {
+            for kind in TaskKind::ALL {
+                let count = task_counts
+                    .iter()
+                    .find(|x| x.status == status && x.kind == kind)
+                    .map_or(0, |x| x.count);
+                let status = format!("{status:?}");
+                let kind = format!("{kind:?}");
+                writer.sample(
+                    "ytdl_tasks",
+                    &[("status", &status), ("kind", &kind)],
+                    count as f64,
+                );
+            }
+        }
+
+        {
+            let dload_manager = self.dload_manager.0.lock().await;
+            let pools = [
+                (WorkerPool::Download, "Download"),
+                (WorkerPool::Transcode, "Transcode"),
+            ];
+            writer.header("ytdl_workers_active", "gauge", "Number of busy workers.");
+            for (pool, name) in pools {
+                let value = dload_manager.num_busy_workers(pool) as f64;
+                writer.sample("ytdl_workers_active", &[("pool", name)], value);
+            }
+            writer.header("ytdl_workers_max", "gauge", "Configured number of workers.");
+            for (pool, name) in pools {
+                let value = dload_manager.num_max_workers(pool) as f64;
+                writer.sample("ytdl_workers_max", &[("pool", name)], value);
+            }
+        }
+
+        self.metrics.write_to(&mut writer);
+        Ok(writer.finish())
+    }
+
+    async fn save_metrics(&self) {
+        let snapshot = self.metrics.snapshot();
+        if let Err(e) = self.db.lock().await.save_metrics(&snapshot).await {
+            warn!("Failed to save metrics: {e}");
+        }
+    }
+
+    pub async fn create_backup(&self) -> anyhow::Result<PathBuf> {
+        let path = self.fs.new_backup_file().await?;
+        self.db.lock().await.backup_to(&path).await?;
+        self.fs
+            .rotate_backups(self.env.config.num_backups_kept as usize)
+            .await?;
+        info!("Saved database backup to {path:?}");
+        Ok(path)
+    }
+
+    async fn is_backup_due(&self) -> bool {
+        let Some(interval) = self.env.config.backup_interval() else {
+            return false;
+        };
+        match self.fs.get_last_backup_time().await {
+            Some(x) => !matches!(x.elapsed(), Ok(elapsed) if elapsed < interval),
+            None => true,
+        }
+    }
+
+    // Old jobs are deleted the same way as if the user asked for it.
+    async fn prune_history(&self) -> anyhow::Result<()> {
+        let db_lock = self.db.lock().await;
+        let jobs = db_lock.get_all_jobs().await?;
+        let job_ids = select_jobs_to_prune(&self.env.config, &jobs, Utc::now());
+        if job_ids.is_empty() {
+            return Ok(());
+        }
+        info!("Pruning {} jobs from history.", job_ids.len());
+        for job_id in job_ids {
+            db_lock.modify_job(job_id, QueueCommand::Delete).await?;
+        }
+        self.cleanup_dirty.mark_dirty();
+        Ok(())
+    }
+
+    // Worker directories are normally removed along with their tasks,
+    // this catches the ones left behind and the ones of old failures.
+    async fn sweep_worker_dirs(&self) -> anyhow::Result<()> {
+        let failed_before = self
+            .env
+            .config
+            .failed_task_retention()
+            .map(|retention| Utc::now() - retention);
+        let tasks_to_keep = {
+            let db_lock = self.db.lock().await;
+            db_lock.get_tasks_owning_worker_dirs(failed_before).await?
+        };
+        let stats = self.fs.sweep_worker_dirs(&tasks_to_keep).await?;
+        if stats.num_dirs_removed > 0 {
+            info!(
+                "Removed {} stale worker directories, reclaimed {} bytes.",
+                stats.num_dirs_removed, stats.bytes_reclaimed
+            );
+        }
+        self.add_sweep_stats(&stats).await;
+        Ok(())
+    }
+
+    // Thumbnails are kept for as long as their task is.
+    async fn sweep_thumbnail_dirs(&self) -> anyhow::Result<()> {
+        let tasks_to_keep = {
+            let db_lock = self.db.lock().await;
+            db_lock.get_tasks_owning_worker_dirs(None).await?
+        };
+        let stats = self.fs.sweep_thumbnail_dirs(&tasks_to_keep).await?;
+        if stats.num_dirs_removed > 0 {
+            info!(
+                "Removed {} orphaned thumbnail directories, reclaimed {} bytes.",
+                stats.num_dirs_removed, stats.bytes_reclaimed
+            );
+        }
+        self.add_sweep_stats(&stats).await;
+        Ok(())
+    }
+
+    fn is_retryable(&self, failure_kind: Option<FailureKind>) -> bool {
+        match failure_kind {
+            Some(kind) if kind.is_permanent() => self.env.config.retry_permanent_failures,
+            _ => true,
+        }
+    }
+
+    // Task will be retried automatically, so it hasn't failed for good yet.
+    fn will_retry(&self, result: &TaskResult) -> bool {
+        result.status == TaskStatus::Failed
+            && !self.stop_handle.is_stopped()
+            && result.task.num_retries < self.env.config.num_automatic_retries
+            && self.is_retryable(result.failure.as_ref().map(|x| x.kind))
+    }
+
+    // Returns `true` if some tasks have been put back into the queue.
+    async fn retry_failed_tasks(&self) -> anyhow::Result<bool> {
+        let max_retries = self.env.config.num_automatic_retries;
+        if max_retries == 0 {
+            return Ok(false);
+        }
+        let timeout = chrono::Duration::seconds(self.env.config.timeout_before_retry as i64);
+        let db_lock = self.db.lock().await;
+        let tasks = db_lock
+            .get_tasks_to_retry(max_retries, Utc::now() - timeout)
+            .await?;
+        let mut did_retry = false;
+        for task in tasks {
+            if !self.is_retryable(task.failure_kind) {
+                continue;
+            }
+            info!(
+                "Retrying task {} ({}/{max_retries})...",
+                task.task_id,
+                task.num_retries + 1
+            );
+            db_lock.retry_task_automatically(task.task_id).await?;
+            did_retry = true;
+        }
+        Ok(did_retry)
+    }
+
+    async fn prune_task_logs(&self) -> anyhow::Result<()> {
+        let existing_tasks = {
+            let db_lock = self.db.lock().await;
+            db_lock.get_tasks_owning_worker_dirs(None).await?
+        };
+        let active_tasks = self.dload_manager.0.lock().await.get_active_task_ids();
+        let stats = self
+            .fs
+            .prune_task_logs(
+                &existing_tasks,
+                &active_tasks,
+                self.env.config.task_log_retention(),
+                self.env.config.max_task_logs_bytes(),
+            )
+            .await?;
+        if stats.num_dirs_removed > 0 {
+            info!(
+                "Removed logs of {} tasks, reclaimed {} bytes.",
+                stats.num_dirs_removed, stats.bytes_reclaimed
+            );
+        }
+        self.add_sweep_stats(&stats).await;
+        Ok(())
+    }
+
+    async fn add_sweep_stats(&self, stats: &SweepStats) {
+        let mut total = self.sweep_stats.lock().await;
+        total.num_dirs_removed += stats.num_dirs_removed;
+        total.bytes_reclaimed += stats.bytes_reclaimed;
+        if stats.last_sweep_at.is_some() {
+            total.last_sweep_at = stats.last_sweep_at;
+        }
+    }
+
+    async fn on_queue_command(&self, command: &QueueCommand) {
+        // User explicitly asked to continue, so forget why we've stopped.
+        // If the reason still holds, we'll pause again on the next check.
+        if *command == QueueCommand::Resume {
+            *self.pause_reason.lock().await = None;
+        }
+    }
+
+    async fn pause_queue(&self, reason: PauseReason) -> anyhow::Result<()> {
+        let is_new_reason = {
+            let mut pause_reason = self.pause_reason.lock().await;
+            let is_new_reason = pause_reason.is_none();
+            *pause_reason = Some(reason.clone());
+            is_new_reason
+        };
+        let has_active_workers = !self.dload_manager.0.lock().await.is_idle();
+        if is_new_reason || has_active_workers {
+            warn!("Pausing the queue: {reason}");
+            self.modify_all_jobs(QueueCommand::Pause).await?;
+        }
+        if is_new_reason {
+            self.announcements.on_queue_paused(&reason).await?;
+        }
+        Ok(())
+    }
+
+    // Returns free disk space, or `None` if there's too little of it
+    // to continue (in which case the queue gets paused).
+    async fn check_disk_space(&self) -> anyhow::Result<Option<u64>> {
+        let min_free_bytes = self.env.config.min_free_space_bytes();
+        if min_free_bytes == 0 {
+            return Ok(Some(u64::MAX));
+        }
+        // Not knowing is no reason to stop everything, the check is just skipped
+        let free_bytes = match self.fs.get_free_space().await {
+            Ok(x) => x,
+            Err(e) => {
+                warn!("Failed to check free disk space: {e}");
+                return Ok(Some(u64::MAX));
+            }
+        };
+        if free_bytes < min_free_bytes {
+            self.pause_queue(PauseReason::LowDiskSpace {
+                free_bytes,
+                min_free_bytes,
+            })
+            .await?;
+            Ok(None)
+        } else {
+            Ok(Some(free_bytes))
+        }
+    }
+
     pub async fn run(&self) -> anyhow::Result<()> {
         info!("Started job manager.");
-        let mut do_stop = false;
         let mut did_send_stop_signals = false;
-        while !do_stop {
+        let mut last_disk_space_check = Instant::now();
+        let mut stop_requested_at = Instant::now();
+        let mut last_worker_dir_sweep: Option<Instant> = None;
+        let mut last_history_prune: Option<Instant> = None;
+        let mut last_retry_check = Instant::now();
+        let mut last_backup_check: Option<Instant> = None;
+        let mut delayed_until: Option<Instant> = None;
+        let mut last_metrics_save = Instant::now();
+        loop {
             rocket::tokio::time::sleep(Duration::from_millis(100)).await;
 
-            do_stop = self.stop_handle.is_stopped();
+            let do_stop = self.stop_handle.is_stopped();
+
+            // First sweep happens right at startup
+            let is_sweep_due = match last_worker_dir_sweep {
+                Some(x) => x.elapsed() >= WORKER_DIR_SWEEP_INTERVAL,
+                None => true,
+            };
+            if !do_stop && is_sweep_due {
+                last_worker_dir_sweep = Some(Instant::now());
+                if let Err(e) = self.sweep_worker_dirs().await {
+                    warn!("Failed to sweep worker directories: {e}");
+                }
+                if let Err(e) = self.sweep_thumbnail_dirs().await {
+                    warn!("Failed to sweep thumbnail directories: {e}");
+                }
+                if let Err(e) = self.prune_task_logs().await {
+                    warn!("Failed to prune task logs: {e}");
+                }
+            }
 
+            let is_prune_due = match last_history_prune {
+                Some(x) => x.elapsed() >= HISTORY_PRUNE_INTERVAL,
+                None => true,
+            };
+            if !do_stop && is_prune_due {
+                last_history_prune = Some(Instant::now());
+                if let Err(e) = self.prune_history().await {
+                    warn!("Failed to prune job history: {e}");
+                }
+            }
+
+            if !do_stop && last_retry_check.elapsed() >= RETRY_CHECK_INTERVAL {
+                last_retry_check = Instant::now();
+                match self.retry_failed_tasks().await {
+                    Ok(true) => self.jobs_dirty.mark_dirty(),
+                    Ok(false) => {}
+                    Err(e) => warn!("Failed to retry tasks: {e}"),
+                }
+            }
+
+            let is_backup_check_due = match last_backup_check {
+                Some(x) => x.elapsed() >= BACKUP_CHECK_INTERVAL,
+                None => true,
+            };
+            if !do_stop && is_backup_check_due {
+                last_backup_check = Some(Instant::now());
+                if self.is_backup_due().await {
+                    if let Err(e) = self.create_backup().await {
+                        warn!("Failed to back up database: {e}");
+                    }
+                }
+            }
+
+            if last_metrics_save.elapsed() >= METRICS_SAVE_INTERVAL {
+                last_metrics_save = Instant::now();
+                self.save_metrics().await;
+            }
+
+            if !do_stop && last_disk_space_check.elapsed() >= DISK_SPACE_CHECK_INTERVAL {
+                last_disk_space_check = Instant::now();
+                // Space could've been freed up, so retry tasks we've held back
+                match self.check_disk_space().await {
+                    Ok(Some(_)) => self.jobs_dirty.mark_dirty(),
+                    Ok(None) => {}
+                    Err(e) => warn!("Failed to pause the queue: {e}"),
+                }
+            }
+
+            // Running tasks are paused so they can continue on next start,
+            // the rest of the queue is left as is.
             if do_stop && !did_send_stop_signals {
                 did_send_stop_signals = true;
-                self.modify_all_jobs(QueueCommand::Cancel).await?;
+                stop_requested_at = Instant::now();
+                self.hooks_control_handle.stop();
+                self.dload_manager
+                    .0
+                    .lock()
+                    .await
+                    .modify_all_tasks(QueueCommand::Pause);
             }
 
             if self.cleanup_dirty.is_dirty() && !self.poll_pending_operations().await? {
@@ -147,27 +606,46 @@ impl JobManager {
                 self.jobs_dirty.mark_dirty();
             }
 
+            // Queue is looked at again once a delayed task is allowed to start
+            if delayed_until.is_some_and(|x| Instant::now() >= x) {
+                delayed_until = None;
+                self.jobs_dirty.mark_dirty();
+            }
             if !do_stop && self.jobs_dirty.is_dirty() {
-                self.poll_start().await?;
+                delayed_until = self.poll_start().await?;
                 self.jobs_dirty.mark_clean();
             }
+
+            if do_stop {
+                if self.dload_manager.0.lock().await.is_idle() {
+                    break;
+                }
+                if stop_requested_at.elapsed() >= SHUTDOWN_TIMEOUT {
+                    warn!("Some tasks didn't stop in time, they'll be marked as failed.");
+                    break;
+                }
+            }
         }
+        self.save_metrics().await;
         info!("Shutdown job manager.");
         Ok(())
     }
 
-    async fn handle_result_fetch_url_contents(&self, result: TaskResult) -> anyhow::Result<()> {
-        let format = result.task.format;
-        let content = if let Some(TaskResultData::FetchUrlContents(content)) = result.data {
+    async fn handle_result_fetch_url_contents(&self, result: &TaskResult) -> anyhow::Result<()> {
+        let content = if let Some(TaskResultData::FetchUrlContents(content)) = &result.data {
             content
         } else {
             return Ok(());
         };
 
+        // Nested playlists coming up empty don't say anything about the job
+        let is_nested = result.task.depends_on.is_some();
         if content.is_empty() {
-            self.announcements
-                .on_contents_empty(result.task.owner_job_id)
-                .await?;
+            if !is_nested {
+                self.announcements
+                    .on_contents_empty(result.task.owner_job_id)
+                    .await?;
+            }
             return Ok(());
         }
 
@@ -175,28 +653,218 @@ impl JobManager {
         let job_title = content.title().to_string();
         let job_thumbnail = content.thumbnail().unwrap_or_default().to_string();
 
-        let new_tasks: Vec<Task> = content
-            .videos()
-            .iter()
-            .map(|video| Task::new_from_video_info(job_id, video, format.clone()))
-            .collect();
+        let db_lock = self.db.lock().await;
+        let job = db_lock.get_job(job_id).await?;
+
+        // Videos of nested playlists end up in the same job
+        let mut new_tasks = job.nested_fetch_tasks(
+            &result.task,
+            content.playlists(),
+            self.env.config.max_playlist_depth,
+        );
+
+        // Preview jobs wait for entries to be picked, single videos
+        // have nothing to pick from and get queued right away.
+        if let VideoOrPlaylist::Playlist(playlist) = content {
+            if job.preview {
+                if is_nested {
+                    db_lock
+                        .add_playlist_entries(job_id, &playlist.videos)
+                        .await?;
+                } else {
+                    db_lock
+                        .store_playlist_entries(
+                            job_id,
+                            &job_title,
+                            &job_thumbnail,
+                            &playlist.videos,
+                        )
+                        .await?;
+                }
+                if !new_tasks.is_empty() {
+                    db_lock
+                        .modify_job(job_id, QueueCommand::TasksAdded(new_tasks))
+                        .await?;
+                }
+                return Ok(());
+            }
+        }
 
-        let command = QueueCommand::JobUpdated {
-            job_title,
-            job_thumbnail,
-            new_tasks,
+        new_tasks.extend(
+            content
+                .videos()
+                .iter()
+                .map(|video| Task::new_from_video_info(&result.task, video)),
+        );
+
+        // Only the job's own playlist gets to name it
+        let command = if is_nested {
+            QueueCommand::TasksAdded(new_tasks)
+        } else {
+            QueueCommand::JobUpdated {
+                job_title,
+                job_thumbnail,
+                new_tasks,
+            }
         };
 
-        self.db.lock().await.modify_job(job_id, command).await?;
+        db_lock.modify_job(job_id, command).await?;
 
         Ok(())
     }
 
-    async fn handle_task_result(&self, result: TaskResult) -> anyhow::Result<()> {
-        self.announcements.on_task_result(&result).await?;
+    // Preview jobs pick their entries once all nested playlists are known,
+    // so that rules like "newest 5" see all of them.
+    async fn apply_selection_rules(&self, job_id: Uuid) -> anyhow::Result<()> {
+        let db_lock = self.db.lock().await;
+        let job = db_lock.get_job(job_id).await?;
+        if !job.preview || job.selection.is_empty() {
+            return Ok(());
+        }
+        let is_fetching = job.tasks.iter().any(|x| {
+            x.kind == TaskKind::FetchUrlContents
+                && matches!(
+                    x.status,
+                    TaskStatus::Waiting | TaskStatus::Processing | TaskStatus::Paused
+                )
+        });
+        if is_fetching {
+            return Ok(());
+        }
+        let entries = db_lock.get_playlist_entries(job_id).await?;
+        match job.selection.select(&entries) {
+            Ok(indices) => {
+                db_lock.select_playlist_entries(job_id, &indices).await?;
+            }
+            Err(e) => warn!("Failed to apply selection rules of job {job_id}: {e}"),
+        }
+        Ok(())
+    }
+
+    // Remembers where the file went, and queues transcoding if the job asked for it.
+    async fn handle_result_output_file(&self, result: &TaskResult) -> anyhow::Result<()> {
+        let output_files = match result.data.as_ref().and_then(|x| x.output_files()) {
+            Some(files) => files,
+            None => return Ok(()),
+        };
+        let output_path = output_files.media.to_string_lossy().to_string();
+        let output_size = output_files.total_size().await as i64;
+
+        let db_lock = self.db.lock().await;
+        db_lock
+            .set_task_output(result.task.task_id, &output_path, output_size)
+            .await?;
+        db_lock
+            .set_task_artifacts(
+                result.task.task_id,
+                &output_files.to_artifacts(result.task.task_id),
+            )
+            .await?;
+
+        if result.task.kind == TaskKind::DownloadAndConvert
+            && result.task.transcode_preset.is_some()
+        {
+            let task = Task::new_transcode(&result.task, &output_path);
+            let command = QueueCommand::TasksAdded(vec![task]);
+            db_lock
+                .modify_job(result.task.owner_job_id, command)
+                .await?;
+            self.jobs_dirty.mark_dirty();
+        } else {
+            self.spawn_post_download_hooks(&result.task, &output_files.media);
+        }
+
+        Ok(())
+    }
 
+    // Hooks get the file once its task is marked as done, and run outside of
+    // worker pools so they don't hold up the queue. Only shutdown stops them.
+    fn spawn_post_download_hooks(&self, task: &Task, output_path: &Path) {
+        let hooks = self.env.config.post_download_hooks.clone();
+        if hooks.is_empty() {
+            return;
+        }
+        let task = task.clone();
+        let output_path = output_path.to_path_buf();
+        let fs = self.fs.clone();
+        let db = self.db.clone();
+        let control_handle = self.hooks_control_handle.clone();
+        tokio::spawn(async move {
+            let failures =
+                run_post_download_hooks(&task, &output_path, &hooks, &fs, control_handle).await;
+            let result = db
+                .lock()
+                .await
+                .set_task_hook_failures(task.task_id, &failures)
+                .await;
+            if let Err(e) = result {
+                warn!("Failed to save hook results of task {}: {e}", task.task_id);
+            }
+        });
+    }
+
+    // Only jobs which have downloaded several videos get a playlist.
+    async fn update_playlist(&self, job_id: Uuid) -> anyhow::Result<()> {
+        if !self.env.config.write_playlists {
+            return Ok(());
+        }
+        let db_lock = self.db.lock().await;
+        let job = db_lock.get_job(job_id).await?;
+        if !matches!(job.status, JobStatus::Done | JobStatus::PartiallyDone) {
+            return Ok(());
+        }
+        let manifest = JobManifest::new(&job);
+        if manifest.items.iter().filter(|x| x.path.is_some()).count() < 2 {
+            return Ok(());
+        }
+        let contents = manifest.to_m3u(Some(self.fs.get_output_dir()));
+        let path = self
+            .fs
+            .write_playlist(job.playlist_path.as_deref(), &job.title, &contents)
+            .await?;
+        if job.playlist_path.is_none() {
+            info!("Saved playlist for job {job_id} to {path:?}");
+            db_lock
+                .set_job_playlist_path(job_id, &path.to_string_lossy())
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn handle_task_result(&self, result: TaskResult) -> anyhow::Result<()> {
+        // Follow-up tasks must be queued before the job is considered finished
+        self.handle_result_output_file(&result).await?;
         if result.task.kind == TaskKind::FetchUrlContents {
-            self.handle_result_fetch_url_contents(result).await?;
+            self.handle_result_fetch_url_contents(&result).await?;
+        }
+
+        // Work continues later, nothing to announce yet
+        if !self.is_interrupted(&result) && !self.will_retry(&result) {
+            if let Err(e) = self
+                .db
+                .lock()
+                .await
+                .record_task_history(result.task.task_id)
+                .await
+            {
+                warn!(
+                    "Failed to record task {} in history: {e}",
+                    result.task.task_id
+                );
+            }
+            if result.task.kind == TaskKind::FetchUrlContents {
+                self.apply_selection_rules(result.task.owner_job_id).await?;
+            }
+            self.announcements.on_task_result(&result).await?;
+        }
+
+        if result.task.kind != TaskKind::FetchUrlContents {
+            if let Err(e) = self.update_playlist(result.task.owner_job_id).await {
+                warn!(
+                    "Failed to write playlist for job {}: {e}",
+                    result.task.owner_job_id
+                );
+            }
         }
 
         Ok(())
@@ -219,6 +887,14 @@ impl JobManager {
             for task in &pending_ops.cleanup {
                 warn!("Cleaning up after {task}...");
                 dload_manager.clean_up_after_worker(*task).await;
+                let _ = self.fs.remove_thumbnail_dir(*task).await;
+            }
+            for task in &pending_ops.delete {
+                let _ = self.fs.remove_task_log_dir(*task).await;
+            }
+            let mut held_back_tasks = self.held_back_tasks.lock().await;
+            for task in pending_ops.cleanup.iter().chain(&pending_ops.delete) {
+                held_back_tasks.remove(task);
             }
             db_lock.confirm_cleanup(&pending_ops.cleanup).await?;
             db_lock.confirm_deletion(&pending_ops.delete).await?;
@@ -226,6 +902,11 @@ impl JobManager {
         Ok(pending_ops.num_busy > 0)
     }
 
+    // Task has been paused because the server is shutting down.
+    fn is_interrupted(&self, result: &TaskResult) -> bool {
+        result.status == TaskStatus::Paused && self.stop_handle.is_stopped()
+    }
+
     async fn collect_finished_tasks(&self) -> anyhow::Result<Vec<TaskResult>> {
         let (mut dload_manager, db_lock) = self.lock_downloads().await;
         let task_results = dload_manager.poll_done().await;
@@ -234,6 +915,14 @@ impl JobManager {
             db_lock
                 .modify_task(task_result.task.task_id, db_command)
                 .await?;
+            db_lock
+                .set_task_failure(task_result.task.task_id, task_result.failure.as_ref())
+                .await?;
+            if self.is_interrupted(task_result) {
+                db_lock
+                    .mark_task_interrupted(task_result.task.task_id)
+                    .await?;
+            }
         }
         Ok(task_results)
     }
@@ -255,19 +944,98 @@ impl JobManager {
         }
     }
 
-    async fn poll_start(&self) -> anyhow::Result<()> {
+    // Returns when the earliest of tasks held back by their host's
+    // start delay can go, if there are any.
+    async fn poll_start(&self) -> anyhow::Result<Option<Instant>> {
+        let free_bytes = match self.check_disk_space().await? {
+            Some(free_bytes) => free_bytes,
+            None => return Ok(None),
+        };
+
         let (mut dload_manager, db_lock) = self.lock_downloads().await;
 
-        let free_slots = dload_manager.num_free_workers();
-        let tasks = db_lock.acquire_tasks(free_slots).await?;
-        if tasks.is_empty() {
-            return Ok(());
+        // Don't start tasks that are known to not fit, accounting for
+        // what's going to be written by the tasks that are already running.
+        // Tasks which won't fit even once those are done get reported.
+        let min_free_bytes = self.env.config.min_free_space_bytes();
+        let usable_bytes = free_bytes.saturating_sub(min_free_bytes);
+        let mut bytes_budget = usable_bytes.saturating_sub(dload_manager.bytes_reserved().await);
+        let mut too_large: Vec<Task> = vec![];
+        // Also, don't hammer any single site with too many downloads at once.
+        let mut host_schedule = dload_manager.get_host_schedule().clone();
+        let mut delayed_until: Option<Instant> = None;
+        let mut free_download_slots = dload_manager.num_free_workers(WorkerPool::Download);
+        let mut free_transcode_slots = dload_manager.num_free_workers(WorkerPool::Transcode);
+        let free_slots = free_download_slots + free_transcode_slots;
+        let mut pools = vec![];
+        if free_download_slots > 0 {
+            pools.push(WorkerPool::Download);
         }
+        if free_transcode_slots > 0 {
+            pools.push(WorkerPool::Transcode);
+        }
+        let can_start = |task: &Task| -> AcquireDecision {
+            if bytes_budget == 0 || (free_download_slots == 0 && free_transcode_slots == 0) {
+                return AcquireDecision::Stop;
+            }
+            // Transcoding doesn't touch the network, only has its own limit
+            if task.kind.worker_pool() == WorkerPool::Transcode {
+                if free_transcode_slots == 0 {
+                    return AcquireDecision::Skip;
+                }
+                free_transcode_slots -= 1;
+                return AcquireDecision::Take;
+            }
+            if free_download_slots == 0 {
+                return AcquireDecision::Skip;
+            }
+            let bytes_estimate = task.bytes_estimate.unwrap_or_default().max(0) as u64;
+            if bytes_estimate > bytes_budget {
+                if bytes_estimate > usable_bytes {
+                    too_large.push(task.clone());
+                }
+                return AcquireDecision::Skip;
+            }
+            match host_schedule.check(&self.env.config, &task.url) {
+                HostCheck::Allowed => {}
+                HostCheck::Saturated => return AcquireDecision::Skip,
+                HostCheck::Delayed(not_before) => {
+                    delayed_until = Some(delayed_until.map_or(not_before, |x| x.min(not_before)));
+                    return AcquireDecision::Skip;
+                }
+            }
+            bytes_budget -= bytes_estimate;
+            host_schedule.on_task_started(&task.url);
+            free_download_slots -= 1;
+            AcquireDecision::Take
+        };
+
+        let tasks = db_lock.acquire_tasks(free_slots, &pools, can_start).await?;
+        let mut held_back_tasks = self.held_back_tasks.lock().await;
         for task in tasks {
+            held_back_tasks.remove(&task.task_id);
             dload_manager.start_task(task);
         }
+        drop(dload_manager);
+        drop(db_lock);
 
-        Ok(())
+        // Nothing else is going to tell the user, so it's announced once per task
+        for task in too_large {
+            let reason = PauseReason::InsufficientSpaceForTask {
+                task_id: task.task_id,
+                bytes_estimate: task.bytes_estimate.unwrap_or_default().max(0) as u64,
+                bytes_budget: usable_bytes,
+            };
+            let is_new = held_back_tasks
+                .insert(task.task_id, reason.clone())
+                .is_none();
+            if is_new {
+                warn!("Holding back task {}: {reason}", task.task_id);
+                self.announcements.on_task_held_back(&task, &reason).await?;
+            }
+        }
+
+        Ok(delayed_until)
     }
 
     pub fn get_stop_handle(&self) -> GenericStopHandle {
@@ -0,0 +1,41 @@
+use rocket::serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use uuid::Uuid;
+
+// Reason for the queue, or a single task in it, to be held back
+// by the server itself, not by the user.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum PauseReason {
+    LowDiskSpace { free_bytes: u64, min_free_bytes: u64 },
+    InsufficientSpaceForTask { task_id: Uuid, bytes_estimate: u64, bytes_budget: u64 },
+}
+
+impl Display for PauseReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PauseReason::LowDiskSpace {
+                free_bytes,
+                min_free_bytes,
+            } => {
+                let free_mb = free_bytes / (1024 * 1024);
+                let min_free_mb = min_free_bytes / (1024 * 1024);
+                write!(
+                    f,
+                    "low disk space: {free_mb} MB left, at least {min_free_mb} MB required"
+                )
+            }
+            PauseReason::InsufficientSpaceForTask {
+                task_id,
+                bytes_estimate,
+                bytes_budget,
+            } => {
+                let estimate_mb = bytes_estimate / (1024 * 1024);
+                let budget_mb = bytes_budget / (1024 * 1024);
+                write!(
+                    f,
+                    "not enough disk space for task {task_id}: {estimate_mb} MB needed, {budget_mb} MB usable"
+                )
+            }
+        }
+    }
+}
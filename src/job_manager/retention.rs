@@ -0,0 +1,158 @@
+use crate::env::Config;
+use crate::job::{Job, JobStatus};
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+// Picks finished jobs which are past their retention period, or don't fit
+// under the cap on the number of jobs. Unfinished jobs are never picked,
+// but still count towards the cap.
+// Expects `jobs` to be sorted from newest to oldest.
+pub fn select_jobs_to_prune(config: &Config, jobs: &[Job], now: DateTime<Utc>) -> Vec<Uuid> {
+    let mut res = vec![];
+    let mut num_kept = 0u32;
+    for job in jobs {
+        // Already on its way out
+        if job.tasks.iter().any(|x| x.pending_delete) {
+            continue;
+        }
+        let retention = match job.status {
+            JobStatus::Done => config.done_job_retention(),
+            JobStatus::PartiallyDone | JobStatus::Failed | JobStatus::Cancelled => {
+                config.failed_job_retention()
+            }
+            JobStatus::Waiting
+            | JobStatus::Processing
+            | JobStatus::Paused
+            | JobStatus::AwaitingSelection => {
+                num_kept += 1;
+                continue;
+            }
+        };
+        let is_expired = match (retention, job.finished_at) {
+            (Some(retention), Some(finished_at)) => finished_at + retention < now,
+            _ => false,
+        };
+        let is_over_cap = config.max_jobs != 0 && num_kept >= config.max_jobs;
+        if is_expired || is_over_cap {
+            res.push(job.job_id);
+        } else {
+            num_kept += 1;
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env::EnvironmentManager;
+    use crate::job::SelectionRules;
+    use crate::task::Task;
+    use std::collections::HashMap;
+    use std::path::Path;
+
+    fn new_config(edit_config: impl FnOnce(&mut Config)) -> Config {
+        EnvironmentManager::new_for_tests(Path::new(""), edit_config).config
+    }
+
+    fn new_job(status: JobStatus, finished_at: Option<DateTime<Utc>>) -> Job {
+        Job {
+            job_id: Uuid::new_v4(),
+            status,
+            tasks: vec![],
+            thumbnail: String::new(),
+            url: "https://example.com/watch?v=1".to_string(),
+            format: "mp3".to_string(),
+            created_at: Utc::now(),
+            started_at: None,
+            finished_at,
+            progress: HashMap::new(),
+            prioritized: false,
+            title: String::new(),
+            credentials_profile: None,
+            proxy: None,
+            transcode_preset: None,
+            is_group: false,
+            playlist_path: None,
+            preview: false,
+            selection: SelectionRules::default(),
+            groups: vec![],
+        }
+    }
+
+    fn days_ago(now: DateTime<Utc>, days: i64) -> Option<DateTime<Utc>> {
+        Some(now - chrono::Duration::days(days))
+    }
+
+    #[test]
+    fn done_and_failed_jobs_have_their_own_retention() {
+        let config = new_config(|config| {
+            config.done_job_retention_days = 7;
+            config.failed_job_retention_days = 30;
+            config.max_jobs = 0;
+        });
+        let now = Utc::now();
+        let jobs = [
+            new_job(JobStatus::Done, days_ago(now, 3)),
+            new_job(JobStatus::Done, days_ago(now, 10)),
+            new_job(JobStatus::Failed, days_ago(now, 10)),
+            new_job(JobStatus::PartiallyDone, days_ago(now, 40)),
+            new_job(JobStatus::Cancelled, days_ago(now, 40)),
+        ];
+        let picked = select_jobs_to_prune(&config, &jobs, now);
+        assert_eq!(picked, [jobs[1].job_id, jobs[3].job_id, jobs[4].job_id]);
+    }
+
+    #[test]
+    fn unfinished_jobs_count_towards_cap_but_are_never_picked() {
+        let config = new_config(|config| {
+            config.done_job_retention_days = 0;
+            config.failed_job_retention_days = 0;
+            config.max_jobs = 2;
+        });
+        let now = Utc::now();
+        let jobs = [
+            new_job(JobStatus::Waiting, None),
+            new_job(JobStatus::Done, days_ago(now, 1)),
+            new_job(JobStatus::Processing, None),
+            new_job(JobStatus::Failed, days_ago(now, 2)),
+            new_job(JobStatus::Paused, None),
+            new_job(JobStatus::AwaitingSelection, None),
+        ];
+        let picked = select_jobs_to_prune(&config, &jobs, now);
+        assert_eq!(picked, [jobs[3].job_id]);
+    }
+
+    #[test]
+    fn jobs_pending_delete_are_neither_picked_nor_counted() {
+        let config = new_config(|config| {
+            config.done_job_retention_days = 7;
+            config.max_jobs = 1;
+        });
+        let now = Utc::now();
+        let mut deleted = new_job(JobStatus::Done, days_ago(now, 10));
+        let mut task = Task::new_fetch_url_contents(&deleted);
+        task.pending_delete = true;
+        deleted.tasks.push(task);
+        let jobs = [
+            deleted,
+            new_job(JobStatus::Done, days_ago(now, 1)),
+            new_job(JobStatus::Done, days_ago(now, 2)),
+        ];
+        let picked = select_jobs_to_prune(&config, &jobs, now);
+        assert_eq!(picked, [jobs[2].job_id]);
+    }
+
+    #[test]
+    fn zero_max_jobs_means_no_cap() {
+        let config = new_config(|config| {
+            config.done_job_retention_days = 0;
+            config.max_jobs = 0;
+        });
+        let now = Utc::now();
+        let jobs: Vec<Job> = (0..100)
+            .map(|x| new_job(JobStatus::Done, days_ago(now, x)))
+            .collect();
+        assert!(select_jobs_to_prune(&config, &jobs, now).is_empty());
+    }
+}
@@ -2,19 +2,27 @@
 
 mod announce;
 mod auth;
+mod credentials;
 mod database;
 mod download_manager;
 mod env;
 mod exit_status;
 mod filesystem;
 mod job;
+mod job_import;
 mod job_manager;
+mod metrics;
 mod playlist;
 mod process;
 mod queue_command;
 mod run_server;
 mod task;
+#[cfg(test)]
+mod test_util;
+mod thumbnails;
+mod url_host;
 mod web_api;
+mod webhooks;
 
 #[macro_use]
 extern crate anyhow;
@@ -43,6 +51,36 @@ async fn async_main(
         let db = ServerDatabase::open(&fs).await?;
         let user = db.get_user_by_name(User::admin_user_name()).await?.unwrap();
         println!("{}", user.get_api_token());
+    } else if let CliCommand::Export { path, json } = &env.cli.command {
+        let fs = Arc::new(FilesystemDriver::new(env.clone()));
+        let db = ServerDatabase::open_read_only(&fs).await?;
+        if *json {
+            let data = db.export_json().await?;
+            tokio::fs::write(path, serde_json::to_vec_pretty(&data)?).await?;
+        } else {
+            db.backup_to(path).await?;
+        }
+        println!("Exported to {}", path.display());
+    } else if let CliCommand::Import { path } = &env.cli.command {
+        // Importing is how a fresh install gets its state
+        let fs = Arc::new(FilesystemDriver::new(env.clone()));
+        fs.init_directories().await?;
+        if database::is_database_snapshot(path).await? {
+            let replaced = database::restore_snapshot(path, &env.paths.database_file).await?;
+            println!(
+                "Database restored, previous one moved to {}",
+                replaced.display()
+            );
+        } else {
+            let data: database::DatabaseExport =
+                serde_json::from_slice(&tokio::fs::read(path).await?)?;
+            let db = ServerDatabase::open(&fs).await?;
+            let summary = db.import_json(&data).await?;
+            println!(
+                "Imported {} users, {} jobs, {} tasks, {} artifacts",
+                summary.num_users, summary.num_jobs, summary.num_tasks, summary.num_artifacts
+            );
+        }
     } else if let CliCommand::Run { .. } = env.cli.command {
         let mut env = env;
         loop {
@@ -105,7 +143,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 main_sync_wrapper(None)?;
             }
         }
-        CliCommand::GetToken => {
+        CliCommand::GetToken | CliCommand::Export { .. } | CliCommand::Import { .. } => {
             main_sync_wrapper(None)?;
         }
         #[allow(unused_variables)]
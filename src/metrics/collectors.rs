@@ -0,0 +1,130 @@
+use rocket::serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Histogram {
+    // Number of observations that fell into each bucket, not cumulative
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn new(num_buckets: usize) -> Self {
+        Self {
+            counts: vec![0; num_buckets],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    fn observe(&mut self, bounds: &[f64], value: f64) {
+        if let Some(idx) = bounds.iter().position(|x| value <= *x) {
+            self.counts[idx] += 1;
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+
+    // Cumulative counts for each of the bucket bounds.
+    pub fn cumulative_counts(&self) -> Vec<u64> {
+        self.counts
+            .iter()
+            .scan(0, |total, x| {
+                *total += x;
+                Some(*total)
+            })
+            .collect()
+    }
+
+    pub fn sum(&self) -> f64 {
+        self.sum
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+}
+
+// Histograms of durations in seconds, one per label value.
+#[derive(Debug)]
+pub struct HistogramVec {
+    label: &'static str,
+    bounds: &'static [f64],
+    histograms: Mutex<BTreeMap<String, Histogram>>,
+}
+
+impl HistogramVec {
+    pub fn new(label: &'static str, bounds: &'static [f64]) -> Self {
+        Self {
+            label,
+            bounds,
+            histograms: Default::default(),
+        }
+    }
+
+    pub fn observe(&self, label_value: &str, duration: Duration) {
+        let mut histograms = self.histograms.lock().unwrap();
+        histograms
+            .entry(label_value.to_string())
+            .or_insert_with(|| Histogram::new(self.bounds.len()))
+            .observe(self.bounds, duration.as_secs_f64());
+    }
+
+    pub fn label(&self) -> &'static str {
+        self.label
+    }
+
+    pub fn bounds(&self) -> &'static [f64] {
+        self.bounds
+    }
+
+    pub fn snapshot(&self) -> BTreeMap<String, Histogram> {
+        self.histograms.lock().unwrap().clone()
+    }
+
+    // Histograms saved with different bucket bounds can't be continued.
+    pub fn restore(&self, mut histograms: BTreeMap<String, Histogram>) {
+        histograms.retain(|_, x| x.counts.len() == self.bounds.len());
+        *self.histograms.lock().unwrap() = histograms;
+    }
+}
+
+// Monotonic counters, one per label value.
+#[derive(Debug)]
+pub struct CounterVec {
+    label: &'static str,
+    counters: Mutex<BTreeMap<String, u64>>,
+}
+
+impl CounterVec {
+    pub fn new(label: &'static str) -> Self {
+        Self {
+            label,
+            counters: Default::default(),
+        }
+    }
+
+    pub fn inc(&self, label_value: &str) {
+        *self
+            .counters
+            .lock()
+            .unwrap()
+            .entry(label_value.to_string())
+            .or_default() += 1;
+    }
+
+    pub fn label(&self) -> &'static str {
+        self.label
+    }
+
+    pub fn snapshot(&self) -> BTreeMap<String, u64> {
+        self.counters.lock().unwrap().clone()
+    }
+
+    pub fn restore(&self, counters: BTreeMap<String, u64>) {
+        *self.counters.lock().unwrap() = counters;
+    }
+}
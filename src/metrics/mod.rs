@@ -0,0 +1,155 @@
+pub use collectors::HistogramVec;
+use collectors::{CounterVec, Histogram};
+use rocket::serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+pub use text_format::MetricsWriter;
+
+mod collectors;
+mod text_format;
+
+const TASK_DURATION_BOUNDS: &[f64] = &[
+    1.0, 5.0, 15.0, 30.0, 60.0, 120.0, 300.0, 600.0, 1800.0, 3600.0, 7200.0,
+];
+const QUEUE_WAIT_BOUNDS: &[f64] = &[
+    0.5, 1.0, 5.0, 15.0, 60.0, 300.0, 900.0, 3600.0, 14400.0, 86400.0,
+];
+const DB_QUERY_BOUNDS: &[f64] = &[
+    0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0,
+];
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct MetricsSnapshot {
+    bytes_downloaded: u64,
+    task_durations: BTreeMap<String, Histogram>,
+    queue_wait: BTreeMap<String, Histogram>,
+    failures: BTreeMap<String, u64>,
+}
+
+// Counters which can't be recomputed from the database, they're saved
+// there from time to time instead to carry on after restart.
+#[derive(Debug)]
+pub struct ServerMetrics {
+    bytes_downloaded: AtomicU64,
+    task_durations: HistogramVec,
+    queue_wait: HistogramVec,
+    failures: CounterVec,
+    db_query_durations: Arc<HistogramVec>,
+}
+
+impl ServerMetrics {
+    pub fn new(db_query_durations: Arc<HistogramVec>) -> Self {
+        Self {
+            bytes_downloaded: AtomicU64::new(0),
+            task_durations: HistogramVec::new("kind", TASK_DURATION_BOUNDS),
+            queue_wait: HistogramVec::new("kind", QUEUE_WAIT_BOUNDS),
+            failures: CounterVec::new("reason"),
+            db_query_durations,
+        }
+    }
+
+    // Query latency is only interesting for the running server,
+    // so it isn't saved.
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            bytes_downloaded: self.bytes_downloaded.load(Ordering::Relaxed),
+            task_durations: self.task_durations.snapshot(),
+            queue_wait: self.queue_wait.snapshot(),
+            failures: self.failures.snapshot(),
+        }
+    }
+
+    pub fn restore(&self, snapshot: MetricsSnapshot) {
+        self.bytes_downloaded
+            .store(snapshot.bytes_downloaded, Ordering::Relaxed);
+        self.task_durations.restore(snapshot.task_durations);
+        self.queue_wait.restore(snapshot.queue_wait);
+        self.failures.restore(snapshot.failures);
+    }
+
+    pub fn new_db_query_durations() -> HistogramVec {
+        HistogramVec::new("query", DB_QUERY_BOUNDS)
+    }
+
+    pub fn on_bytes_downloaded(&self, bytes: u64) {
+        self.bytes_downloaded.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn on_task_started(&self, kind: &str, queue_wait: Duration) {
+        self.queue_wait.observe(kind, queue_wait);
+    }
+
+    pub fn on_task_finished(&self, kind: &str, duration: Duration) {
+        self.task_durations.observe(kind, duration);
+    }
+
+    pub fn on_task_failed(&self, reason: &str) {
+        self.failures.inc(reason);
+    }
+
+    pub fn write_to(&self, writer: &mut MetricsWriter) {
+        writer.header(
+            "ytdl_downloaded_bytes_total",
+            "counter",
+            "Size of files produced by finished downloads.",
+        );
+        writer.sample(
+            "ytdl_downloaded_bytes_total",
+            &[],
+            self.bytes_downloaded.load(Ordering::Relaxed) as f64,
+        );
+        writer.histograms(
+            "ytdl_task_duration_seconds",
+            "Time workers spent running tasks.",
+            &self.task_durations,
+        );
+        writer.histograms(
+            "ytdl_queue_wait_seconds",
+            "Time tasks spent in queue before first start.",
+            &self.queue_wait,
+        );
+        writer.counters(
+            "ytdl_task_failures_total",
+            "Failed task runs by reason.",
+            &self.failures,
+        );
+        writer.histograms(
+            "ytdl_db_query_duration_seconds",
+            "Latency of database queries.",
+            &self.db_query_durations,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{new_test_server, TempDir};
+
+    fn render(metrics: &ServerMetrics) -> String {
+        let mut writer = MetricsWriter::default();
+        metrics.write_to(&mut writer);
+        writer.finish()
+    }
+
+    #[tokio::test]
+    async fn metrics_carry_on_after_restart() {
+        let dir = TempDir::new();
+        let (_, db) = new_test_server(&dir, |_| {}).await;
+        let db = db.lock().await;
+
+        let metrics = ServerMetrics::new(db.get_query_durations());
+        metrics.on_bytes_downloaded(1000);
+        metrics.on_task_started("DownloadAndConvert", Duration::from_secs(2));
+        metrics.on_task_finished("DownloadAndConvert", Duration::from_secs(40));
+        metrics.on_task_failed("NetworkError");
+        db.save_metrics(&metrics.snapshot()).await.unwrap();
+
+        let restarted = ServerMetrics::new(db.get_query_durations());
+        restarted.restore(db.load_metrics().await.unwrap().unwrap());
+        assert_eq!(render(&restarted), render(&metrics));
+        assert!(render(&restarted).contains("ytdl_task_failures_total{reason=\"NetworkError\"} 1"));
+    }
+}
@@ -0,0 +1,77 @@
+use crate::metrics::collectors::{CounterVec, HistogramVec};
+use std::fmt::Write;
+
+// Renders metrics in Prometheus text exposition format.
+#[derive(Debug, Default)]
+pub struct MetricsWriter {
+    out: String,
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+impl MetricsWriter {
+    pub fn header(&mut self, name: &str, kind: &str, help: &str) {
+        let _ = writeln!(self.out, "# HELP {name} {help}");
+        let _ = writeln!(self.out, "# TYPE {name} {kind}");
+    }
+
+    pub fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: f64) {
+        self.out.push_str(name);
+        if !labels.is_empty() {
+            let labels: Vec<String> = labels
+                .iter()
+                .map(|(key, value)| format!("{key}=\"{}\"", escape_label_value(value)))
+                .collect();
+            let _ = write!(self.out, "{{{}}}", labels.join(","));
+        }
+        let _ = writeln!(self.out, " {value}");
+    }
+
+    pub fn counters(&mut self, name: &str, help: &str, counters: &CounterVec) {
+        self.header(name, "counter", help);
+        for (label_value, value) in counters.snapshot() {
+            self.sample(name, &[(counters.label(), &label_value)], value as f64);
+        }
+    }
+
+    pub fn histograms(&mut self, name: &str, help: &str, histograms: &HistogramVec) {
+        self.header(name, "histogram", help);
+        let label = histograms.label();
+        let bucket_name = format!("{name}_bucket");
+        for (label_value, histogram) in histograms.snapshot() {
+            let cumulative = histogram.cumulative_counts();
+            for (bound, count) in histograms.bounds().iter().zip(cumulative) {
+                let le = bound.to_string();
+                self.sample(
+                    &bucket_name,
+                    &[(label, &label_value), ("le", &le)],
+                    count as f64,
+                );
+            }
+            self.sample(
+                &bucket_name,
+                &[(label, &label_value), ("le", "+Inf")],
+                histogram.count() as f64,
+            );
+            self.sample(
+                &format!("{name}_sum"),
+                &[(label, &label_value)],
+                histogram.sum(),
+            );
+            self.sample(
+                &format!("{name}_count"),
+                &[(label, &label_value)],
+                histogram.count() as f64,
+            );
+        }
+    }
+
+    pub fn finish(self) -> String {
+        self.out
+    }
+}
@@ -1,10 +1,48 @@
+use chrono::{DateTime, Utc};
+use rocket::serde::{Deserialize, Serialize};
+use sqlx::types::Json;
+use sqlx::FromRow;
 use std::slice;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Chapter {
+    pub start_time: f64,
+    pub end_time: f64,
+    #[serde(default)]
+    pub title: String,
+}
+
+// Whatever the extractor knew about a video, none of it is guaranteed.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct MediaMetadata {
+    pub extractor: Option<String>,
+    pub duration: Option<f64>,
+    pub channel: Option<String>,
+    pub published_at: Option<DateTime<Utc>>,
+    pub view_count: Option<i64>,
+    pub like_count: Option<i64>,
+    pub description: Option<String>,
+    pub chapters: Option<Json<Vec<Chapter>>>,
+    pub tags: Option<Json<Vec<String>>>,
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
 pub struct VideoInfo {
     pub url: String,
     pub thumbnail: Option<String>,
     pub title: String,
+    pub bytes_estimate: Option<i64>,
+    pub uploader: Option<String>,
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub metadata: MediaMetadata,
+}
+
+// Entry which is a playlist of its own, e.g. a tab of a channel.
+#[derive(Debug, Clone)]
+pub struct NestedPlaylist {
+    pub url: String,
+    pub title: String,
 }
 
 #[derive(Debug)]
@@ -13,17 +51,18 @@ pub struct PlaylistInfo {
     pub thumbnail: Option<String>,
     pub title: String,
     pub videos: Vec<VideoInfo>,
+    pub playlists: Vec<NestedPlaylist>,
 }
 
 #[derive(Debug)]
 pub enum VideoOrPlaylist {
-    Video(VideoInfo),
+    Video(Box<VideoInfo>),
     Playlist(PlaylistInfo),
 }
 
 impl VideoOrPlaylist {
     pub fn is_empty(&self) -> bool {
-        self.videos().is_empty()
+        self.videos().is_empty() && self.playlists().is_empty()
     }
 
     pub fn title(&self) -> &str {
@@ -42,8 +81,15 @@ impl VideoOrPlaylist {
 
     pub fn videos(&self) -> &[VideoInfo] {
         match self {
-            VideoOrPlaylist::Video(v) => slice::from_ref(v),
+            VideoOrPlaylist::Video(v) => slice::from_ref(v.as_ref()),
             VideoOrPlaylist::Playlist(p) => &p.videos,
         }
     }
+
+    pub fn playlists(&self) -> &[NestedPlaylist] {
+        match self {
+            VideoOrPlaylist::Video(_) => &[],
+            VideoOrPlaylist::Playlist(p) => &p.playlists,
+        }
+    }
 }
@@ -15,4 +15,5 @@ pub enum QueueCommand {
         job_thumbnail: String,
         new_tasks: Vec<Task>,
     },
+    TasksAdded(Vec<Task>),
 }
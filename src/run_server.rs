@@ -1,16 +1,20 @@
 use crate::announce::AnnounceSystem;
 use crate::auth::{AuthFairing, AuthSystem};
-use crate::database::ServerDatabase;
+use crate::credentials::CredentialStore;
+use crate::database::{ServerDatabase, ServerLock};
 use crate::env::{CliCommand, EnvironmentManager};
 use crate::exit_status::{ExitStatus, ExitStatusHandle};
 use crate::filesystem::FilesystemDriver;
 use crate::job_manager::JobManagerHandle;
+use crate::metrics::ServerMetrics;
 use crate::process::{ExternalShutdownFairing, GenericStopHandle};
-use crate::web_api::{get_api_routes, get_index_html_redirect};
+use crate::thumbnails::ThumbnailCache;
+use crate::web_api::{get_api_routes, get_index_html_redirect, get_metrics_routes};
+use crate::webhooks::WebhookSystem;
 use rocket::fs::FileServer;
 use std::sync::Arc;
 use tokio::sync::Mutex;
-use tracing::info;
+use tracing::{info, warn};
 
 pub async fn run_server(
     env: Arc<EnvironmentManager>,
@@ -24,15 +28,34 @@ pub async fn run_server(
     let fs = Arc::new(FilesystemDriver::new(env.clone()));
     fs.init_directories().await?;
 
-    let db = Arc::new(Mutex::new(ServerDatabase::open(&fs).await?));
+    let lock = ServerLock::acquire(fs.get_database_file()).await?;
+    let db = ServerDatabase::open(&fs).await?;
+    let metrics = Arc::new(ServerMetrics::new(db.get_query_durations()));
+    match db.load_metrics().await {
+        Ok(Some(snapshot)) => metrics.restore(snapshot),
+        Ok(None) => {}
+        Err(e) => warn!("Failed to load saved metrics, starting from zero: {e}"),
+    }
+    let db = Arc::new(Mutex::new(db));
     let auth_fairing = AuthFairing::new(db.clone());
     let auth_system = AuthSystem::new(db.clone());
+    let webhooks = Arc::new(WebhookSystem::new(env.clone(), db.clone()));
     let announcements = Arc::new(AnnounceSystem::new(
-        env.config.show_announcements,
+        &env.config,
+        db.clone(),
+        webhooks.clone(),
+    )?);
+    let thumbnails = Arc::new(ThumbnailCache::new(fs.clone(), db.clone()));
+    let credentials = Arc::new(CredentialStore::open(&env.paths.credentials_dir).await?);
+    let job_manager = JobManagerHandle::new(
+        announcements,
+        env.clone(),
+        fs.clone(),
+        credentials.clone(),
         db.clone(),
-    ));
-    let job_manager =
-        JobManagerHandle::new(announcements, env.clone(), fs.clone(), db.clone()).await?;
+        metrics,
+    )
+    .await?;
 
     let external_shutdown_monitor = {
         let internal_handle = job_manager.get_stop_handle();
@@ -49,23 +72,42 @@ pub async fn run_server(
 
     let job_manager_stop_handle = job_manager.get_stop_handle();
 
+    let webhooks_join_handle = {
+        let webhooks = webhooks.clone();
+        rocket::tokio::task::spawn(async move {
+            webhooks.run().await;
+        })
+    };
+
+    let webhooks_stop_handle = webhooks.get_stop_handle();
+
     let exit_state = ExitStatusHandle::new();
     let _rocket = rocket::build()
         .attach(auth_fairing)
         .attach(external_shutdown_monitor)
         .mount("/api", get_api_routes())
         .mount("/", get_index_html_redirect())
+        .mount("/", get_metrics_routes())
         .mount("/", FileServer::from("webui/"))
         .manage(auth_system)
         .manage(job_manager)
         .manage(env)
         .manage(exit_state.clone())
         .manage(fs)
+        .manage(credentials)
+        .manage(webhooks)
+        .manage(thumbnails)
         .launch()
         .await?;
 
     job_manager_stop_handle.stop();
     job_manager_join_handle.await.unwrap();
 
+    // Job manager is done, so there won't be any new events
+    webhooks_stop_handle.stop();
+    webhooks_join_handle.await.unwrap();
+
+    // Server might be started again right away with new config
+    lock.release().await?;
     Ok(exit_state.take().await)
 }
@@ -0,0 +1,87 @@
+use rocket::serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, sqlx::Type, PartialEq)]
+pub enum ArtifactKind {
+    Media,
+    Subtitles,
+    Thumbnail,
+    InfoJson,
+    Other,
+}
+
+impl ArtifactKind {
+    // Guesses kind of a sidecar file from its name.
+    pub fn from_sidecar_path(path: &Path) -> Self {
+        let name = path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_lowercase();
+        if name.ends_with(".info.json") {
+            return ArtifactKind::InfoJson;
+        }
+        let ext = path
+            .extension()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_lowercase();
+        match ext.as_str() {
+            "vtt" | "srt" | "ass" | "ssa" | "ttml" | "srv1" | "srv2" | "srv3" | "json3" | "lrc" => {
+                ArtifactKind::Subtitles
+            }
+            "jpg" | "jpeg" | "png" | "webp" => ArtifactKind::Thumbnail,
+            _ => ArtifactKind::Other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct TaskArtifact {
+    pub task_id: Uuid,
+    pub kind: ArtifactKind,
+    pub path: String,
+}
+
+// Files a task left in the output directory.
+#[derive(Debug, Clone)]
+pub struct OutputFiles {
+    pub media: PathBuf,
+    pub sidecars: Vec<PathBuf>,
+}
+
+impl OutputFiles {
+    pub fn new(media: PathBuf) -> Self {
+        Self {
+            media,
+            sidecars: vec![],
+        }
+    }
+
+    // Files which have gone missing since don't count.
+    pub async fn total_size(&self) -> u64 {
+        let mut total = 0;
+        for path in std::iter::once(&self.media).chain(&self.sidecars) {
+            if let Ok(metadata) = tokio::fs::metadata(path).await {
+                total += metadata.len();
+            }
+        }
+        total
+    }
+
+    pub fn to_artifacts(&self, task_id: Uuid) -> Vec<TaskArtifact> {
+        let media = TaskArtifact {
+            task_id,
+            kind: ArtifactKind::Media,
+            path: self.media.to_string_lossy().to_string(),
+        };
+        let sidecars = self.sidecars.iter().map(|path| TaskArtifact {
+            task_id,
+            kind: ArtifactKind::from_sidecar_path(path),
+            path: path.to_string_lossy().to_string(),
+        });
+        std::iter::once(media).chain(sidecars).collect()
+    }
+}
@@ -1,8 +1,9 @@
+use crate::credentials::CredentialStore;
 use crate::download_manager::command;
 use crate::download_manager::command::ChildWorker;
 use crate::download_manager::command::WorkerError;
 use crate::download_manager::WorkerControlHandle;
-use crate::env::YtdlpConfig;
+use crate::env::EnvironmentManager;
 use crate::filesystem::FilesystemDriver;
 use crate::process::read_output_to_log;
 use crate::task::{Task, TaskProgress, TaskResultData};
@@ -15,21 +16,24 @@ use tokio::sync::Mutex;
 
 pub async fn run_task_download_and_convert(
     task: Task,
-    ytdlp: Arc<YtdlpConfig>,
+    env: Arc<EnvironmentManager>,
     fs: Arc<FilesystemDriver>,
+    credentials: Arc<CredentialStore>,
+    proxy: Option<String>,
     progress: Arc<Mutex<TaskProgress>>,
     control_handle: WorkerControlHandle,
 ) -> anyhow::Result<TaskResultData> {
     let src_url = &task.url;
     if task.is_resumed {
-        // Remove output file as it could've been corrupted after pausing
-        let _ = fs.remove_worker_output_file(&task).await;
+        // Download continues from the partial files, but whatever
+        // post-processing produced could've been corrupted when interrupted
+        let _ = fs.remove_unfinished_output_files(&task).await;
     } else {
         // Perform cleanup as there could be leftover files from previous attempt
         let _ = fs.remove_worker_data_dir(task.task_id).await;
     }
     fs.create_worker_data_dir(task.task_id).await?;
-    let _ = fs.create_worker_log_dir(task.task_id).await;
+    let _ = fs.create_task_log_dir(task.task_id).await;
     let dst_file = fs.get_ytdlp_output_template(task.task_id);
 
     let (mut stdout_file, stderr_file) = fs.make_log_files(task.task_id).await?;
@@ -37,8 +41,13 @@ pub async fn run_task_download_and_convert(
     let mut command = command::new_downloader_command();
 
     // We use `unwrap` here because any misconfiguration is most likely the user's fault
+    let ytdlp = &env.ytdlp;
     let format = ytdlp.get_format(&task.format);
-    let args = ytdlp.render_download_command(src_url, format, &dst_file);
+    let auth_args = credentials
+        .get_ytdlp_args(src_url, task.credentials_profile.as_deref())
+        .await?;
+    let args =
+        ytdlp.render_download_command(src_url, format, &dst_file, proxy.as_deref(), &auth_args);
 
     for arg in args {
         command.arg(arg);
@@ -101,8 +110,8 @@ pub async fn run_task_download_and_convert(
         ChildWorker::run(&mut command, control_handle, stdout_reader, stderr_reader).await?;
 
     if exit_status.success() {
-        fs.move_output_file(&task).await?;
-        Ok(TaskResultData::DownloadAndConvert)
+        let output_files = fs.move_output_file(&task).await?;
+        Ok(TaskResultData::DownloadAndConvert(output_files))
     } else {
         Err(anyhow!(WorkerError::BadExitCode(exit_status.code())))
     }
@@ -0,0 +1,258 @@
+use rocket::serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, sqlx::Type, PartialEq)]
+pub enum FailureKind {
+    VideoUnavailable,
+    Private,
+    GeoBlocked,
+    AgeRestricted,
+    Throttled,
+    FfmpegMissing,
+    DiskFull,
+    NetworkError,
+    Unknown,
+}
+
+// Substrings of yt-dlp (and ffmpeg) error messages, checked in order,
+// so more specific patterns have to come first.
+const FAILURE_PATTERNS: &[(FailureKind, &[&str])] = &[
+    (
+        FailureKind::DiskFull,
+        &["no space left on device", "errno 28", "disk quota exceeded"],
+    ),
+    (
+        FailureKind::FfmpegMissing,
+        &[
+            "ffmpeg not found",
+            "ffprobe not found",
+            "ffmpeg is not installed",
+            "ffprobe and ffmpeg not found",
+        ],
+    ),
+    (
+        FailureKind::AgeRestricted,
+        &[
+            "confirm your age",
+            "age-restricted",
+            "age restricted",
+            "inappropriate for some users",
+        ],
+    ),
+    (
+        FailureKind::Private,
+        &[
+            "private video",
+            "video is private",
+            "members-only",
+            "join this channel to get access",
+            "available to this channel's members",
+        ],
+    ),
+    (
+        FailureKind::GeoBlocked,
+        &[
+            "available in your country",
+            "geo restrict",
+            "geo-restrict",
+            "from your location",
+        ],
+    ),
+    (
+        FailureKind::Throttled,
+        &[
+            "http error 429",
+            "too many requests",
+            "rate-limit",
+            "rate limit",
+        ],
+    ),
+    (
+        FailureKind::VideoUnavailable,
+        &[
+            "video unavailable",
+            "video is unavailable",
+            "has been removed",
+            "has been terminated",
+            "does not exist",
+            "http error 404",
+            "http error 410",
+            "unsupported url",
+        ],
+    ),
+    (
+        FailureKind::NetworkError,
+        &[
+            "unable to download webpage",
+            "unable to download video data",
+            "connection reset",
+            "connection refused",
+            "connection aborted",
+            "timed out",
+            "name resolution",
+            "getaddrinfo failed",
+            "network is unreachable",
+            "http error 5",
+            "incompleteread",
+        ],
+    ),
+];
+
+impl FailureKind {
+    // Retrying these won't help until something changes on the other end
+    // or in the server setup.
+    pub fn is_permanent(self) -> bool {
+        match self {
+            FailureKind::VideoUnavailable
+            | FailureKind::Private
+            | FailureKind::GeoBlocked
+            | FailureKind::AgeRestricted
+            | FailureKind::FfmpegMissing => true,
+            FailureKind::Throttled
+            | FailureKind::DiskFull
+            | FailureKind::NetworkError
+            | FailureKind::Unknown => false,
+        }
+    }
+
+    // Whether what's been downloaded before the failure is good to resume
+    // from. Permanent failures won't be resumed anyway, and the unknown ones
+    // (including crashed workers) could've left anything behind.
+    pub fn keeps_partial_data(self) -> bool {
+        match self {
+            FailureKind::Throttled | FailureKind::DiskFull | FailureKind::NetworkError => true,
+            FailureKind::VideoUnavailable
+            | FailureKind::Private
+            | FailureKind::GeoBlocked
+            | FailureKind::AgeRestricted
+            | FailureKind::FfmpegMissing
+            | FailureKind::Unknown => false,
+        }
+    }
+
+    fn from_message(message: &str) -> Option<Self> {
+        let message = message.to_lowercase();
+        FAILURE_PATTERNS
+            .iter()
+            .find(|(_, patterns)| patterns.iter().any(|x| message.contains(x)))
+            .map(|(kind, _)| *kind)
+    }
+}
+
+// Why the task has failed, as far as we can tell.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TaskFailure {
+    pub kind: FailureKind,
+    pub message: String,
+}
+
+impl TaskFailure {
+    pub fn new(kind: FailureKind, message: &str) -> Self {
+        Self {
+            kind,
+            message: message.to_string(),
+        }
+    }
+
+    // Looks at what the worker has written to stderr during its last run.
+    // yt-dlp reports fatal errors as "ERROR: ..." lines, the last one of
+    // those is the reason it gave up. Warnings are only used as a hint
+    // when the error itself isn't recognized.
+    pub fn from_stderr(stderr: &str, error: &anyhow::Error) -> Self {
+        let lines: Vec<&str> = stderr
+            .lines()
+            .map(str::trim)
+            .filter(|x| !x.is_empty())
+            .collect();
+        let error_line = lines
+            .iter()
+            .rev()
+            .find_map(|x| x.strip_prefix("ERROR:"))
+            .map(str::trim);
+
+        let message = match error_line {
+            Some(line) => line.to_string(),
+            None => lines
+                .last()
+                .map_or_else(|| error.to_string(), |x| x.to_string()),
+        };
+        let kind = error_line
+            .and_then(FailureKind::from_message)
+            .or_else(|| {
+                lines
+                    .iter()
+                    .rev()
+                    .find_map(|x| FailureKind::from_message(x))
+            })
+            .unwrap_or(FailureKind::Unknown);
+        Self { kind, message }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classify(stderr: &str) -> TaskFailure {
+        TaskFailure::from_stderr(stderr, &anyhow::anyhow!("Bad exit code: 1"))
+    }
+
+    #[test]
+    fn last_error_line_decides_the_kind() {
+        let failure = classify(
+            "[youtube] abc: Downloading webpage\n\
+             ERROR: [youtube] abc: Unable to download webpage: timed out\n\
+             ERROR: [youtube] abc: Private video. Sign in if you've been granted access\n",
+        );
+        assert_eq!(failure.kind, FailureKind::Private);
+        assert_eq!(
+            failure.message,
+            "[youtube] abc: Private video. Sign in if you've been granted access"
+        );
+    }
+
+    #[test]
+    fn specific_patterns_win_over_generic_ones() {
+        let failure =
+            classify("ERROR: unable to download video data: [Errno 28] No space left on device");
+        assert_eq!(failure.kind, FailureKind::DiskFull);
+        let failure = classify("ERROR: [youtube] abc: HTTP Error 429: Too Many Requests");
+        assert_eq!(failure.kind, FailureKind::Throttled);
+    }
+
+    #[test]
+    fn warnings_are_used_when_error_is_not_recognized() {
+        let failure = classify(
+            "WARNING: [youtube] abc: This video is only available from your location\n\
+             ERROR: [youtube] abc: Something went wrong\n",
+        );
+        assert_eq!(failure.kind, FailureKind::GeoBlocked);
+        assert_eq!(failure.message, "[youtube] abc: Something went wrong");
+    }
+
+    #[test]
+    fn unrecognized_failure_is_unknown() {
+        let failure = classify("ERROR: [generic] something odd\n");
+        assert_eq!(failure.kind, FailureKind::Unknown);
+        let failure = classify("");
+        assert_eq!(failure.kind, FailureKind::Unknown);
+        assert_eq!(failure.message, "Bad exit code: 1");
+    }
+
+    #[test]
+    fn only_transient_failures_are_retried_by_default() {
+        assert!(FailureKind::VideoUnavailable.is_permanent());
+        assert!(FailureKind::Private.is_permanent());
+        assert!(FailureKind::FfmpegMissing.is_permanent());
+        assert!(!FailureKind::NetworkError.is_permanent());
+        assert!(!FailureKind::Throttled.is_permanent());
+        assert!(!FailureKind::Unknown.is_permanent());
+    }
+
+    #[test]
+    fn partial_data_is_kept_only_for_transient_failures() {
+        assert!(FailureKind::NetworkError.keeps_partial_data());
+        assert!(FailureKind::DiskFull.keeps_partial_data());
+        assert!(!FailureKind::Unknown.keeps_partial_data());
+        assert!(!FailureKind::VideoUnavailable.keeps_partial_data());
+    }
+}
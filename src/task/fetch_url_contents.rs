@@ -1,52 +1,134 @@
+use crate::credentials::CredentialStore;
 use crate::download_manager::command;
 use crate::download_manager::command::{ChildWorker, WorkerError};
 use crate::download_manager::WorkerControlHandle;
 use crate::env::YtdlpConfig;
 use crate::filesystem::FilesystemDriver;
-use crate::playlist::{PlaylistInfo, VideoInfo, VideoOrPlaylist};
+use crate::playlist::{
+    Chapter, MediaMetadata, NestedPlaylist, PlaylistInfo, VideoInfo, VideoOrPlaylist,
+};
 use crate::process::{read_output_to_buf_and_log, read_output_to_log};
 use crate::task::{Task, TaskProgress, TaskResultData};
-use rocket::serde::Deserialize;
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use rocket::serde::de::DeserializeOwned;
+use rocket::serde::{Deserialize, Deserializer};
 use serde_json::Value;
+use sqlx::types::Json;
+use std::future::Future;
 use std::sync::Arc;
 use tokio::process::{ChildStderr, ChildStdout};
 use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+// Extractors normally resolve these themselves, so more than a couple means a loop.
+const MAX_REDIRECTS: u32 = 5;
 
 #[derive(Deserialize)]
-struct DeserializeSingleVideoThumbnail {
+struct DeserializeThumbnail {
     pub url: String,
+    #[serde(default)]
+    pub width: Option<u64>,
+    #[serde(default)]
+    pub height: Option<u64>,
+    #[serde(default)]
+    pub preference: Option<i64>,
 }
 
-#[derive(Deserialize)]
-struct DeserializerSingleVideo {
-    pub original_url: String,
-    pub title: String,
-    pub thumbnails: Vec<DeserializeSingleVideoThumbnail>,
+// Extractors disagree on the types of what they report. A field that
+// doesn't parse is dropped rather than failing the whole fetch.
+fn lenient<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: DeserializeOwned,
+{
+    let value = Value::deserialize(deserializer)?;
+    Ok(serde_json::from_value(value).ok())
 }
 
 #[derive(Deserialize)]
-struct DeserializePlaylistVideoThumbnail {
-    pub url: String,
+struct DeserializeMetadata {
+    #[serde(default, deserialize_with = "lenient")]
+    pub extractor: Option<String>,
+    // Flat playlist entries only name the extractor meant for them
+    #[serde(default, deserialize_with = "lenient")]
+    pub ie_key: Option<String>,
+    #[serde(default, deserialize_with = "lenient")]
+    pub duration: Option<f64>,
+    #[serde(default, deserialize_with = "lenient")]
+    pub channel: Option<String>,
+    #[serde(default, deserialize_with = "lenient")]
+    pub timestamp: Option<f64>,
+    #[serde(default, deserialize_with = "lenient")]
+    pub upload_date: Option<String>,
+    #[serde(default, deserialize_with = "lenient")]
+    pub view_count: Option<i64>,
+    #[serde(default, deserialize_with = "lenient")]
+    pub like_count: Option<i64>,
+    #[serde(default, deserialize_with = "lenient")]
+    pub description: Option<String>,
+    #[serde(default, deserialize_with = "lenient")]
+    pub chapters: Option<Vec<Chapter>>,
+    #[serde(default, deserialize_with = "lenient")]
+    pub tags: Option<Vec<String>>,
 }
 
 #[derive(Deserialize)]
-struct DeserializerPlaylistVideo {
-    pub url: String,
-    pub title: String,
-    pub thumbnails: Vec<DeserializePlaylistVideoThumbnail>,
+struct DeserializerSingleVideo {
+    pub original_url: String,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub thumbnails: Vec<DeserializeThumbnail>,
+    #[serde(default)]
+    pub thumbnail: Option<String>,
+    #[serde(default)]
+    pub filesize: Option<f64>,
+    #[serde(default)]
+    pub filesize_approx: Option<f64>,
+    #[serde(default)]
+    pub uploader: Option<String>,
+    #[serde(flatten)]
+    pub metadata: DeserializeMetadata,
 }
 
 #[derive(Deserialize)]
-struct DeserializePlaylistThumbnail {
+struct DeserializerPlaylistVideo {
     pub url: String,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub thumbnails: Vec<DeserializeThumbnail>,
+    #[serde(default)]
+    pub thumbnail: Option<String>,
+    #[serde(default)]
+    pub filesize: Option<f64>,
+    #[serde(default)]
+    pub filesize_approx: Option<f64>,
+    #[serde(default)]
+    pub uploader: Option<String>,
+    #[serde(flatten)]
+    pub metadata: DeserializeMetadata,
 }
 
 #[derive(Deserialize)]
 struct DeserializerPlaylist {
     pub original_url: String,
-    pub title: String,
-    pub entries: Vec<DeserializerPlaylistVideo>,
-    pub thumbnails: Vec<DeserializePlaylistThumbnail>,
+    #[serde(default)]
+    pub title: Option<String>,
+    // Unavailable entries show up as nulls
+    pub entries: Vec<Value>,
+    #[serde(default)]
+    pub thumbnails: Vec<DeserializeThumbnail>,
+}
+
+#[derive(Deserialize)]
+struct DeserializerNestedPlaylist {
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub webpage_url: Option<String>,
+    #[serde(default)]
+    pub title: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -55,44 +137,160 @@ struct DeserializerCheckType {
     pub check_type: String,
 }
 
+#[derive(Deserialize)]
+struct DeserializerCheckEntryType {
+    #[serde(rename = "_type", default)]
+    pub check_type: Option<String>,
+    #[serde(default)]
+    pub ie_key: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct DeserializerRedirect {
+    pub url: String,
+}
+
+// yt-dlp reports exact size only when it's known upfront,
+// otherwise we may get an approximation or nothing at all.
+fn pick_bytes_estimate(filesize: Option<f64>, filesize_approx: Option<f64>) -> Option<i64> {
+    filesize.or(filesize_approx).map(|x| x as i64)
+}
+
+// Flat playlists often only have the date, if anything.
+fn pick_published_at(timestamp: Option<f64>, upload_date: Option<&str>) -> Option<DateTime<Utc>> {
+    if let Some(time) = timestamp.and_then(|x| Utc.timestamp_opt(x as i64, 0).single()) {
+        return Some(time);
+    }
+    let date = NaiveDate::parse_from_str(upload_date?, "%Y%m%d").ok()?;
+    Some(Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0)?))
+}
+
+// Largest one wins. Thumbnails come sorted from worst to best,
+// so without known dimensions yt-dlp's own order decides.
+fn pick_thumbnail(thumbnails: &[DeserializeThumbnail], fallback: Option<&str>) -> Option<String> {
+    thumbnails
+        .iter()
+        .enumerate()
+        .max_by_key(|(idx, x)| {
+            let area = x.width.zip(x.height).map(|(w, h)| w * h);
+            (area, x.preference, *idx)
+        })
+        .map(|(_, x)| x.url.clone())
+        .or_else(|| fallback.map(str::to_string))
+}
+
+impl DeserializeMetadata {
+    fn into_metadata(self) -> MediaMetadata {
+        MediaMetadata {
+            extractor: self.extractor.or(self.ie_key),
+            duration: self.duration,
+            channel: self.channel,
+            published_at: pick_published_at(self.timestamp, self.upload_date.as_deref()),
+            view_count: self.view_count,
+            like_count: self.like_count,
+            description: self.description,
+            chapters: self.chapters.map(Json),
+            tags: self.tags.map(Json),
+        }
+    }
+}
+
 fn parse_single_video(data: Value) -> anyhow::Result<VideoInfo> {
     let video: DeserializerSingleVideo = serde_json::from_value(data)?;
     Ok(VideoInfo {
-        url: video.original_url.clone(),
-        title: video.title.clone(),
-        thumbnail: video.thumbnails.get(0).map(|x| x.url.to_string()),
+        title: video.title.unwrap_or_else(|| video.original_url.clone()),
+        url: video.original_url,
+        thumbnail: pick_thumbnail(&video.thumbnails, video.thumbnail.as_deref()),
+        bytes_estimate: pick_bytes_estimate(video.filesize, video.filesize_approx),
+        uploader: video.uploader.or_else(|| video.metadata.channel.clone()),
+        metadata: video.metadata.into_metadata(),
     })
 }
 
-fn parse_playlist(data: Value) -> anyhow::Result<PlaylistInfo> {
+fn parse_playlist_video(data: Value) -> anyhow::Result<VideoInfo> {
+    let video: DeserializerPlaylistVideo = serde_json::from_value(data)?;
+    Ok(VideoInfo {
+        title: video.title.unwrap_or_else(|| video.url.clone()),
+        url: video.url,
+        thumbnail: pick_thumbnail(&video.thumbnails, video.thumbnail.as_deref()),
+        bytes_estimate: pick_bytes_estimate(video.filesize, video.filesize_approx),
+        uploader: video.uploader.or_else(|| video.metadata.channel.clone()),
+        metadata: video.metadata.into_metadata(),
+    })
+}
+
+// Inline playlists come with their entries, but fetching them again
+// keeps all nested playlists going the same way.
+fn parse_nested_playlist(data: Value) -> anyhow::Result<NestedPlaylist> {
+    let playlist: DeserializerNestedPlaylist = serde_json::from_value(data)?;
+    let url = playlist
+        .url
+        .or(playlist.webpage_url)
+        .ok_or_else(|| anyhow!("missing field `url`"))?;
+    Ok(NestedPlaylist {
+        title: playlist.title.unwrap_or_else(|| url.clone()),
+        url,
+    })
+}
+
+fn is_nested_playlist(entry: &Value, ytdlp: &YtdlpConfig) -> bool {
+    let entry_type: DeserializerCheckEntryType = match serde_json::from_value(entry.clone()) {
+        Ok(x) => x,
+        Err(_) => return false,
+    };
+    match entry_type.check_type.as_deref() {
+        Some("playlist" | "multi_video") => true,
+        _ => entry_type
+            .ie_key
+            .is_some_and(|x| ytdlp.is_playlist_extractor(&x)),
+    }
+}
+
+// A broken entry only costs that one video, not the whole playlist.
+fn parse_playlist(data: Value, ytdlp: &YtdlpConfig) -> anyhow::Result<PlaylistInfo> {
     let playlist: DeserializerPlaylist = serde_json::from_value(data)?;
-    let videos: anyhow::Result<Vec<VideoInfo>> = playlist
-        .entries
-        .iter()
-        .map(|video| -> anyhow::Result<VideoInfo> {
-            Ok(VideoInfo {
-                url: video.url.clone(),
-                title: video.title.clone(),
-                thumbnail: video.thumbnails.get(0).map(|x| x.url.to_string()),
-            })
-        })
-        .collect();
+    let mut videos = vec![];
+    let mut playlists = vec![];
+    for (idx, entry) in playlist.entries.into_iter().enumerate() {
+        if entry.is_null() {
+            continue;
+        }
+        let parsed = if is_nested_playlist(&entry, ytdlp) {
+            parse_nested_playlist(entry).map(|x| playlists.push(x))
+        } else {
+            parse_playlist_video(entry).map(|x| videos.push(x))
+        };
+        match parsed {
+            Ok(()) => {}
+            Err(e) => warn!(
+                "Skipping entry #{} of {}: {e}",
+                idx + 1,
+                playlist.original_url
+            ),
+        }
+    }
     Ok(PlaylistInfo {
+        thumbnail: pick_thumbnail(&playlist.thumbnails, None),
+        title: playlist
+            .title
+            .unwrap_or_else(|| playlist.original_url.clone()),
         url: playlist.original_url,
-        thumbnail: playlist.thumbnails.get(0).map(|x| x.url.clone()),
-        title: playlist.title,
-        videos: videos?,
+        videos,
+        playlists,
     })
 }
 
-fn parse_url_video_list(data: Value) -> anyhow::Result<VideoOrPlaylist> {
+// Returns `None` if the extractor handed the URL over to another one.
+fn parse_url_video_list(
+    data: Value,
+    ytdlp: &YtdlpConfig,
+) -> anyhow::Result<Option<VideoOrPlaylist>> {
     let check_type: DeserializerCheckType = serde_json::from_value(data.clone())?;
-    if check_type.check_type == "video" {
-        Ok(VideoOrPlaylist::Video(parse_single_video(data)?))
-    } else if check_type.check_type == "playlist" {
-        Ok(VideoOrPlaylist::Playlist(parse_playlist(data)?))
-    } else {
-        Err(anyhow!("failed to parse downloader output: expected '_type' to be 'video' or 'playlist', got {:?}", check_type.check_type))
+    match check_type.check_type.as_str() {
+        "video" => Ok(Some(VideoOrPlaylist::Video(Box::new(parse_single_video(data)?)))),
+        "playlist" | "multi_video" => Ok(Some(VideoOrPlaylist::Playlist(parse_playlist(data, ytdlp)?))),
+        "url" | "url_transparent" => Ok(None),
+        _ => Err(anyhow!("failed to parse downloader output: expected '_type' to be 'video', 'playlist' or 'url', got {:?}", check_type.check_type)),
     }
 }
 
@@ -100,14 +298,61 @@ pub async fn run_task_fetch_url_contents(
     task: Task,
     ytdlp: Arc<YtdlpConfig>,
     fs: Arc<FilesystemDriver>,
+    credentials: Arc<CredentialStore>,
+    proxy: Option<String>,
     _progress: Arc<Mutex<TaskProgress>>,
     control_handle: WorkerControlHandle,
 ) -> anyhow::Result<TaskResultData> {
     fs.create_worker_data_dir(task.task_id).await?;
-    let _ = fs.create_worker_log_dir(task.task_id).await;
+    let _ = fs.create_task_log_dir(task.task_id).await;
 
+    let (task, fs, credentials) = (&task, &*fs, &*credentials);
+    let contents = follow_redirects(&task.url, &ytdlp, |url| {
+        let (ytdlp, proxy, control_handle) = (&ytdlp, proxy.as_deref(), control_handle.clone());
+        async move { fetch_url(task, &url, ytdlp, fs, credentials, proxy, control_handle).await }
+    })
+    .await?;
+    Ok(TaskResultData::FetchUrlContents(contents))
+}
+
+// Fetches `url` again and again for as long as it points somewhere else.
+async fn follow_redirects<F, Fut>(
+    url: &str,
+    ytdlp: &YtdlpConfig,
+    mut fetch: F,
+) -> anyhow::Result<VideoOrPlaylist>
+where
+    F: FnMut(String) -> Fut,
+    Fut: Future<Output = anyhow::Result<Value>>,
+{
+    let mut url = url.to_string();
+    for _ in 0..=MAX_REDIRECTS {
+        let data = fetch(url.clone()).await?;
+        if let Some(contents) = parse_url_video_list(data.clone(), ytdlp)? {
+            return Ok(contents);
+        }
+        let redirect: DeserializerRedirect = serde_json::from_value(data)?;
+        info!("{url} points to {}, fetching that instead", redirect.url);
+        url = redirect.url;
+    }
+    Err(anyhow!("too many redirects, gave up at {url}"))
+}
+
+// yt-dlp's JSON output, all attempts go to the same log.
+async fn fetch_url(
+    task: &Task,
+    url: &str,
+    ytdlp: &YtdlpConfig,
+    fs: &FilesystemDriver,
+    credentials: &CredentialStore,
+    proxy: Option<&str>,
+    control_handle: WorkerControlHandle,
+) -> anyhow::Result<Value> {
     let mut command = command::new_downloader_command();
-    let args = ytdlp.render_fetch_url_command(&task.url);
+    let auth_args = credentials
+        .get_ytdlp_args(url, task.credentials_profile.as_deref())
+        .await?;
+    let args = ytdlp.render_fetch_url_command(url, proxy, &auth_args);
     for arg in args {
         command.arg(arg);
     }
@@ -131,10 +376,124 @@ pub async fn run_task_fetch_url_contents(
 
     if exit_status.success() {
         let buf_lock = stdout_buf.lock().await;
-        let data: Value = serde_json::from_slice(&buf_lock)?;
-        let contents = parse_url_video_list(data)?;
-        Ok(TaskResultData::FetchUrlContents(contents))
+        Ok(serde_json::from_slice(&buf_lock)?)
     } else {
         Err(anyhow!(WorkerError::BadExitCode(exit_status.code())))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env::EnvironmentManager;
+    use crate::test_util::TempDir;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    fn redirect(url: &str) -> Value {
+        json!({ "_type": "url", "url": url })
+    }
+
+    // Serves canned yt-dlp output, remembering which URLs were asked for.
+    async fn follow(
+        start: &str,
+        pages: HashMap<String, Value>,
+    ) -> (anyhow::Result<VideoOrPlaylist>, Vec<String>) {
+        let dir = TempDir::new();
+        let env = EnvironmentManager::new_for_tests(dir.path(), |_| {});
+        let requested = std::sync::Mutex::new(vec![]);
+        let result = follow_redirects(start, &env.ytdlp, |url| {
+            requested.lock().unwrap().push(url.clone());
+            let page = pages.get(&url).cloned();
+            async move { page.ok_or_else(|| anyhow!("no such page {url}")) }
+        })
+        .await;
+        (result, requested.into_inner().unwrap())
+    }
+
+    #[test]
+    fn float_timestamps_are_accepted() {
+        let video = parse_single_video(json!({
+            "original_url": "https://example.com/v",
+            "timestamp": 1767225600.5,
+            "upload_date": "20200101",
+        }))
+        .unwrap();
+        assert_eq!(
+            video.metadata.published_at,
+            Utc.timestamp_opt(1767225600, 0).single()
+        );
+    }
+
+    #[test]
+    fn metadata_fields_that_fail_to_parse_are_dropped() {
+        let video = parse_single_video(json!({
+            "original_url": "https://example.com/v",
+            "title": "Video",
+            "duration": "about an hour",
+            "view_count": 12.5,
+            "like_count": 3,
+            "timestamp": "yesterday",
+            "upload_date": "20260102",
+            "chapters": [{"bogus": true}],
+            "tags": ["a", "b"],
+        }))
+        .unwrap();
+        assert_eq!(video.title, "Video");
+        assert_eq!(video.metadata.duration, None);
+        assert_eq!(video.metadata.view_count, None);
+        assert_eq!(video.metadata.like_count, Some(3));
+        assert_eq!(
+            video.metadata.published_at,
+            Utc.with_ymd_and_hms(2026, 1, 2, 0, 0, 0).single()
+        );
+        assert!(video.metadata.chapters.is_none());
+        assert_eq!(
+            video.metadata.tags.map(|x| x.0),
+            Some(vec!["a".to_string(), "b".to_string()])
+        );
+    }
+
+    #[test]
+    fn required_fields_still_fail_the_parse() {
+        assert!(parse_single_video(json!({ "title": "Video" })).is_err());
+    }
+
+    #[tokio::test]
+    async fn redirects_are_followed_to_the_contents() {
+        let pages = HashMap::from([
+            ("a".to_string(), redirect("b")),
+            ("b".to_string(), redirect("c")),
+            (
+                "c".to_string(),
+                json!({ "_type": "video", "original_url": "c", "title": "Video" }),
+            ),
+        ]);
+        let (result, requested) = follow("a", pages).await;
+        match result.unwrap() {
+            VideoOrPlaylist::Video(video) => assert_eq!(video.title, "Video"),
+            VideoOrPlaylist::Playlist(_) => panic!("expected a video"),
+        }
+        assert_eq!(requested, ["a", "b", "c"]);
+    }
+
+    #[tokio::test]
+    async fn redirect_loops_are_given_up_on() {
+        let pages = HashMap::from([
+            ("a".to_string(), redirect("b")),
+            ("b".to_string(), redirect("a")),
+        ]);
+        let (result, requested) = follow("a", pages).await;
+        let error = result.err().unwrap().to_string();
+        assert!(error.contains("too many redirects"), "{error}");
+        assert_eq!(requested.len(), MAX_REDIRECTS as usize + 1);
+    }
+
+    #[tokio::test]
+    async fn fetch_errors_stop_the_redirects() {
+        let pages = HashMap::from([("a".to_string(), redirect("b"))]);
+        let (result, requested) = follow("a", pages).await;
+        assert!(result.is_err());
+        assert_eq!(requested, ["a", "b"]);
+    }
+}
@@ -1,52 +1,92 @@
+use crate::credentials::CredentialStore;
 use crate::download_manager::WorkerControlHandle;
-use crate::env::YtdlpConfig;
+use crate::env::EnvironmentManager;
 use crate::filesystem::FilesystemDriver;
 use crate::job::Job;
-use crate::playlist::{VideoInfo, VideoOrPlaylist};
+use crate::playlist::{MediaMetadata, NestedPlaylist, VideoInfo, VideoOrPlaylist};
+use crate::url_host::serialize_redacted_proxy;
+pub use artifact::{OutputFiles, TaskArtifact};
 use chrono::{DateTime, Utc};
+pub use failure::{FailureKind, TaskFailure};
 use fetch_url_contents::run_task_fetch_url_contents;
+pub use post_download_hooks::{run_post_download_hooks, HookFailure};
 use rocket::serde::{Deserialize, Serialize};
+use sqlx::types::Json;
 use sqlx::FromRow;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use uuid::Uuid;
 
+mod artifact;
 mod download_and_convert;
+mod failure;
 mod fetch_url_contents;
+mod post_download_hooks;
+mod transcode;
 
 pub struct TaskResult {
     pub task: Task,
     pub status: TaskStatus,
     pub data: Option<TaskResultData>,
+    pub failure: Option<TaskFailure>,
 }
 
 #[derive(Debug)]
 pub enum TaskResultData {
-    DownloadAndConvert,
+    DownloadAndConvert(OutputFiles),
     FetchUrlContents(VideoOrPlaylist),
+    Transcode(OutputFiles),
+}
+
+impl TaskResultData {
+    // Final location of the files produced by task, if any.
+    pub fn output_files(&self) -> Option<&OutputFiles> {
+        match self {
+            TaskResultData::DownloadAndConvert(files) | TaskResultData::Transcode(files) => {
+                Some(files)
+            }
+            TaskResultData::FetchUrlContents(_) => None,
+        }
+    }
 }
 
 pub async fn run_task(
     task: Task,
-    ytdlp: Arc<YtdlpConfig>,
+    env: Arc<EnvironmentManager>,
     fs: Arc<FilesystemDriver>,
+    credentials: Arc<CredentialStore>,
+    proxy: Option<String>,
     progress: Arc<Mutex<TaskProgress>>,
     control_handle: WorkerControlHandle,
 ) -> anyhow::Result<TaskResultData> {
     match task.kind {
         TaskKind::FetchUrlContents => {
-            run_task_fetch_url_contents(task, ytdlp, fs, progress, control_handle).await
+            run_task_fetch_url_contents(
+                task,
+                env.ytdlp.clone(),
+                fs,
+                credentials,
+                proxy,
+                progress,
+                control_handle,
+            )
+            .await
         }
         TaskKind::DownloadAndConvert => {
             download_and_convert::run_task_download_and_convert(
                 task,
-                ytdlp,
+                env,
                 fs,
+                credentials,
+                proxy,
                 progress,
                 control_handle,
             )
             .await
         }
+        TaskKind::Transcode => {
+            transcode::run_task_transcode(task, env, fs, progress, control_handle).await
+        }
     }
 }
 
@@ -60,10 +100,44 @@ pub enum TaskStatus {
     Cancelled,
 }
 
+impl TaskStatus {
+    pub const ALL: [TaskStatus; 6] = [
+        TaskStatus::Waiting,
+        TaskStatus::Processing,
+        TaskStatus::Done,
+        TaskStatus::Paused,
+        TaskStatus::Failed,
+        TaskStatus::Cancelled,
+    ];
+}
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, sqlx::Type, PartialEq)]
 pub enum TaskKind {
     FetchUrlContents,
     DownloadAndConvert,
+    Transcode,
+}
+
+// Tasks from different pools don't compete for worker slots.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WorkerPool {
+    Download,
+    Transcode,
+}
+
+impl TaskKind {
+    pub const ALL: [TaskKind; 3] = [
+        TaskKind::FetchUrlContents,
+        TaskKind::DownloadAndConvert,
+        TaskKind::Transcode,
+    ];
+
+    pub fn worker_pool(&self) -> WorkerPool {
+        match self {
+            TaskKind::FetchUrlContents | TaskKind::DownloadAndConvert => WorkerPool::Download,
+            TaskKind::Transcode => WorkerPool::Transcode,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow, PartialEq)]
@@ -83,6 +157,27 @@ pub struct Task {
     pub pending_cleanup: bool,
     pub pending_delete: bool,
     pub title: String,
+    pub bytes_estimate: Option<i64>,
+    pub credentials_profile: Option<String>,
+    #[serde(serialize_with = "serialize_redacted_proxy")]
+    pub proxy: Option<String>,
+    pub transcode_preset: Option<String>,
+    pub source_path: Option<String>,
+    pub output_path: Option<String>,
+    // Task which must be done before this one can start
+    pub depends_on: Option<Uuid>,
+    pub num_retries: u32,
+    pub failure_kind: Option<FailureKind>,
+    pub failure_message: Option<String>,
+    // Set once post-download hooks have run, cleared when all succeed
+    pub hook_failures: Option<Json<Vec<HookFailure>>>,
+    pub uploader: Option<String>,
+    // Total size of files left in the output directory
+    pub output_size: Option<i64>,
+    // Kept in its own table, only loaded along with a single job
+    #[serde(default)]
+    #[sqlx(skip)]
+    pub metadata: Option<MediaMetadata>,
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -93,7 +188,7 @@ pub struct TaskProgress {
 }
 
 impl Task {
-    pub fn new_from_video_info(owner_job_id: Uuid, video: &VideoInfo, format: String) -> Self {
+    pub fn new_from_video_info(fetch_task: &Task, video: &VideoInfo) -> Self {
         let created_at = Utc::now();
         let task_id = Uuid::new_v4();
         Self {
@@ -104,9 +199,9 @@ impl Task {
                 .thumbnail
                 .as_ref()
                 .map_or_else(String::default, |x| x.to_string()),
-            owner_job_id,
+            owner_job_id: fetch_task.owner_job_id,
             url: video.url.clone(),
-            format,
+            format: fetch_task.format.clone(),
             created_at,
             started_at: None,
             finished_at: None,
@@ -115,6 +210,94 @@ impl Task {
             pending_delete: false,
             pending_cleanup: false,
             title: video.title.clone(),
+            bytes_estimate: video.bytes_estimate,
+            credentials_profile: fetch_task.credentials_profile.clone(),
+            proxy: fetch_task.proxy.clone(),
+            transcode_preset: fetch_task.transcode_preset.clone(),
+            source_path: None,
+            output_path: None,
+            depends_on: Some(fetch_task.task_id),
+            num_retries: 0,
+            failure_kind: None,
+            failure_message: None,
+            hook_failures: None,
+            uploader: video.uploader.clone(),
+            output_size: None,
+            metadata: Some(video.metadata.clone()),
+        }
+    }
+
+    // Transcodes the file downloaded by `download_task`.
+    pub fn new_transcode(download_task: &Task, source_path: &str) -> Self {
+        let created_at = Utc::now();
+        let task_id = Uuid::new_v4();
+        Self {
+            task_id,
+            status: TaskStatus::Waiting,
+            kind: TaskKind::Transcode,
+            thumbnail: download_task.thumbnail.clone(),
+            owner_job_id: download_task.owner_job_id,
+            url: download_task.url.clone(),
+            format: download_task.format.clone(),
+            created_at,
+            started_at: None,
+            finished_at: None,
+            prioritized: download_task.prioritized,
+            is_resumed: false,
+            pending_delete: false,
+            pending_cleanup: false,
+            title: download_task.title.clone(),
+            bytes_estimate: None,
+            credentials_profile: None,
+            proxy: None,
+            transcode_preset: download_task.transcode_preset.clone(),
+            source_path: Some(source_path.to_string()),
+            output_path: None,
+            depends_on: Some(download_task.task_id),
+            num_retries: 0,
+            failure_kind: None,
+            failure_message: None,
+            hook_failures: None,
+            uploader: download_task.uploader.clone(),
+            output_size: None,
+            metadata: None,
+        }
+    }
+
+    // Fetches a playlist found inside of the one `parent_task` fetched.
+    pub fn new_nested_fetch(parent_task: &Task, playlist: &NestedPlaylist) -> Self {
+        let created_at = Utc::now();
+        let task_id = Uuid::new_v4();
+        Self {
+            task_id,
+            status: TaskStatus::Waiting,
+            kind: TaskKind::FetchUrlContents,
+            thumbnail: String::default(),
+            owner_job_id: parent_task.owner_job_id,
+            url: playlist.url.clone(),
+            format: parent_task.format.clone(),
+            created_at,
+            started_at: None,
+            finished_at: None,
+            prioritized: parent_task.prioritized,
+            is_resumed: false,
+            pending_delete: false,
+            pending_cleanup: false,
+            title: playlist.title.clone(),
+            bytes_estimate: None,
+            credentials_profile: parent_task.credentials_profile.clone(),
+            proxy: parent_task.proxy.clone(),
+            transcode_preset: parent_task.transcode_preset.clone(),
+            source_path: None,
+            output_path: None,
+            depends_on: Some(parent_task.task_id),
+            num_retries: 0,
+            failure_kind: None,
+            failure_message: None,
+            hook_failures: None,
+            uploader: None,
+            output_size: None,
+            metadata: None,
         }
     }
 
@@ -137,6 +320,20 @@ impl Task {
             pending_delete: false,
             pending_cleanup: false,
             title: "[Fetch Contents]".to_string(),
+            bytes_estimate: None,
+            credentials_profile: job.credentials_profile.clone(),
+            proxy: job.proxy.clone(),
+            transcode_preset: job.transcode_preset.clone(),
+            source_path: None,
+            output_path: None,
+            depends_on: None,
+            num_retries: 0,
+            failure_kind: None,
+            failure_message: None,
+            hook_failures: None,
+            uploader: None,
+            output_size: None,
+            metadata: None,
         }
     }
 }
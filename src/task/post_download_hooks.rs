@@ -0,0 +1,112 @@
+use crate::download_manager::command::{ChildWorker, WorkerError};
+use crate::download_manager::WorkerControlHandle;
+use crate::env::HookConfig;
+use crate::filesystem::FilesystemDriver;
+use crate::process::read_output_to_log;
+use crate::task::Task;
+use handlebars::Handlebars;
+use rocket::serde::{Deserialize, Serialize};
+use std::path::Path;
+use tokio::io::AsyncWriteExt;
+use tokio::process::{ChildStderr, ChildStdout, Command};
+use tracing::warn;
+use uuid::Uuid;
+
+#[derive(Serialize)]
+struct HookData<'a> {
+    output_path: String,
+    title: &'a str,
+    url: &'a str,
+    job_id: Uuid,
+    task_id: Uuid,
+    format: &'a str,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HookFailure {
+    pub name: String,
+    pub message: String,
+}
+
+// Task is already done by now, so failed hooks only get reported.
+pub async fn run_post_download_hooks(
+    task: &Task,
+    output_path: &Path,
+    hooks: &[HookConfig],
+    fs: &FilesystemDriver,
+    control_handle: WorkerControlHandle,
+) -> Vec<HookFailure> {
+    let data = HookData {
+        output_path: output_path.to_string_lossy().to_string(),
+        title: &task.title,
+        url: &task.url,
+        job_id: task.owner_job_id,
+        task_id: task.task_id,
+        format: &task.format,
+    };
+    let mut handlebars = Handlebars::new();
+    handlebars.register_escape_fn(handlebars::no_escape);
+
+    let mut failures = vec![];
+    for hook in hooks {
+        if control_handle.is_stopped() {
+            break;
+        }
+        let result = run_hook(hook, &data, &handlebars, fs, control_handle.clone()).await;
+        if let Err(e) = result {
+            warn!(
+                "Post-download hook {:?} failed for task {}: {e}",
+                hook.name, task.task_id
+            );
+            failures.push(HookFailure {
+                name: hook.name.clone(),
+                message: e.to_string(),
+            });
+        }
+    }
+    failures
+}
+
+async fn run_hook(
+    hook: &HookConfig,
+    data: &HookData<'_>,
+    handlebars: &Handlebars<'_>,
+    fs: &FilesystemDriver,
+    control_handle: WorkerControlHandle,
+) -> anyhow::Result<()> {
+    let mut log_file = fs.make_hooks_log_file(data.task_id).await?;
+
+    let mut command = Command::new(&hook.command);
+    for arg in &hook.args {
+        command.arg(handlebars.render_template(arg, data)?);
+    }
+
+    let header = format!("[{}] Running {command:?}\n", hook.name);
+    log_file.write_all(header.as_bytes()).await?;
+
+    let stdout_file = log_file.try_clone().await?;
+    let stderr_file = log_file.try_clone().await?;
+
+    let stdout_reader = async move |stdout: ChildStdout| -> anyhow::Result<()> {
+        read_output_to_log(stdout, stdout_file).await
+    };
+
+    let stderr_reader = async move |stderr: ChildStderr| -> anyhow::Result<()> {
+        read_output_to_log(stderr, stderr_file).await
+    };
+
+    let result = ChildWorker::run(&mut command, control_handle, stdout_reader, stderr_reader).await;
+
+    let footer = match &result {
+        Ok(exit_status) => format!("[{}] Finished: {exit_status}\n", hook.name),
+        Err(e) => format!("[{}] Failed: {e}\n", hook.name),
+    };
+    log_file.write_all(footer.as_bytes()).await?;
+
+    let exit_status = result?;
+    if exit_status.success() {
+        Ok(())
+    } else {
+        Err(anyhow!(WorkerError::BadExitCode(exit_status.code())))
+    }
+}
@@ -0,0 +1,122 @@
+use crate::download_manager::command;
+use crate::download_manager::command::{ChildWorker, WorkerError};
+use crate::download_manager::WorkerControlHandle;
+use crate::env::EnvironmentManager;
+use crate::filesystem::FilesystemDriver;
+use crate::task::{OutputFiles, Task, TaskProgress, TaskResultData};
+use anyhow::Context;
+use bytelines::AsyncByteLines;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncWriteExt, BufReader};
+use tokio::process::{ChildStderr, ChildStdout};
+use tokio::sync::Mutex;
+
+// Parses "  Duration: 00:03:25.12, start: ..." line into microseconds.
+fn parse_duration_line(line: &str) -> Option<u64> {
+    let value = line.trim_start().strip_prefix("Duration: ")?;
+    let value = value.split(',').next()?;
+    let mut parts = value.split(':');
+    let hours: f64 = parts.next()?.parse().ok()?;
+    let minutes: f64 = parts.next()?.parse().ok()?;
+    let seconds: f64 = parts.next()?.parse().ok()?;
+    Some(((hours * 3600.0 + minutes * 60.0 + seconds) * 1e6) as u64)
+}
+
+pub async fn run_task_transcode(
+    task: Task,
+    env: Arc<EnvironmentManager>,
+    fs: Arc<FilesystemDriver>,
+    progress: Arc<Mutex<TaskProgress>>,
+    control_handle: WorkerControlHandle,
+) -> anyhow::Result<TaskResultData> {
+    let preset_id = task
+        .transcode_preset
+        .as_deref()
+        .context("transcode task has no preset")?;
+    let preset = env
+        .config
+        .get_transcode_preset(preset_id)
+        .with_context(|| format!("unknown transcode preset {preset_id:?}"))?;
+    let src_file = PathBuf::from(
+        task.source_path
+            .as_deref()
+            .context("transcode task has no source file")?,
+    );
+
+    // Output is always written from scratch, so leftovers are of no use
+    let _ = fs.remove_worker_data_dir(task.task_id).await;
+    fs.create_worker_data_dir(task.task_id).await?;
+    let _ = fs.create_task_log_dir(task.task_id).await;
+    let dst_file = fs.get_transcode_output_file(task.task_id, &preset.ext);
+
+    let (mut stdout_file, stderr_file) = fs.make_log_files(task.task_id).await?;
+
+    let mut command = command::new_transcoder_command();
+    command
+        .args([
+            "-hide_banner",
+            "-nostdin",
+            "-y",
+            "-progress",
+            "pipe:1",
+            "-nostats",
+        ])
+        .arg("-i")
+        .arg(&src_file)
+        .args(&preset.args)
+        .arg(&dst_file);
+
+    // Reported on stderr, but needed to calculate progress from stdout
+    let duration_us = Arc::new(AtomicU64::new(0));
+    let duration_us_stdout_copy = duration_us.clone();
+    let state_stdout_copy = progress.clone();
+
+    let stdout_reader = async move |stdout: ChildStdout| -> anyhow::Result<()> {
+        let stdout_reader = BufReader::new(stdout);
+        let mut lines = AsyncByteLines::new(stdout_reader);
+        while let Some(line_raw) = lines.next().await? {
+            stdout_file.write_all(line_raw).await?;
+            stdout_file.write_u8(b'\n').await?;
+            let line = String::from_utf8_lossy(line_raw);
+            let out_time_us = match line.strip_prefix("out_time_us=") {
+                Some(value) => value.trim().parse::<u64>().unwrap_or_default(),
+                None => continue,
+            };
+            let duration_us = duration_us_stdout_copy.load(Ordering::Relaxed);
+            if duration_us > 0 {
+                let percent = (out_time_us as f64 * 100f64 / duration_us as f64).round() as i32;
+                state_stdout_copy.lock().await.percent = percent.clamp(0, 100);
+            }
+        }
+        Ok(())
+    };
+
+    let stderr_reader = async move |stderr: ChildStderr| -> anyhow::Result<()> {
+        let mut stderr_file = stderr_file;
+        let stderr_reader = BufReader::new(stderr);
+        let mut lines = AsyncByteLines::new(stderr_reader);
+        while let Some(line_raw) = lines.next().await? {
+            stderr_file.write_all(line_raw).await?;
+            stderr_file.write_u8(b'\n').await?;
+            if duration_us.load(Ordering::Relaxed) == 0 {
+                if let Some(value) = parse_duration_line(&String::from_utf8_lossy(line_raw)) {
+                    duration_us.store(value, Ordering::Relaxed);
+                }
+            }
+        }
+        Ok(())
+    };
+
+    let exit_status =
+        ChildWorker::run(&mut command, control_handle, stdout_reader, stderr_reader).await?;
+
+    if exit_status.success() {
+        // Transcoded file replaces the original one
+        let output_path = fs.move_transcoded_file(&dst_file, &src_file).await?;
+        Ok(TaskResultData::Transcode(OutputFiles::new(output_path)))
+    } else {
+        Err(anyhow!(WorkerError::BadExitCode(exit_status.code())))
+    }
+}
@@ -0,0 +1,55 @@
+use crate::database::ServerDatabase;
+use crate::env::{Config, EnvironmentManager};
+use crate::filesystem::FilesystemDriver;
+use crate::web_api::NewJobForm;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+// Directory which is removed along with everything in it once dropped.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new() -> Self {
+        let path = std::env::temp_dir().join(format!("youtube-dl-server-test-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+// Server environment with its files and database kept in `dir`.
+pub async fn new_test_server(
+    dir: &TempDir,
+    edit_config: impl FnOnce(&mut Config),
+) -> (Arc<EnvironmentManager>, Arc<Mutex<ServerDatabase>>) {
+    let env = Arc::new(EnvironmentManager::new_for_tests(dir.path(), edit_config));
+    let fs = FilesystemDriver::new(env.clone());
+    fs.init_directories().await.unwrap();
+    let db = ServerDatabase::open(&fs).await.unwrap();
+    (env, Arc::new(Mutex::new(db)))
+}
+
+pub fn new_job_form(url: &str) -> NewJobForm {
+    NewJobForm {
+        url: url.to_string(),
+        format: "mp3".to_string(),
+        credentials_profile: None,
+        proxy: None,
+        transcode_preset: None,
+        preview: None,
+        select_first: None,
+        select_newest: None,
+        select_title: None,
+    }
+}
@@ -0,0 +1,299 @@
+use crate::database::ServerDatabase;
+use crate::download_manager::command;
+use crate::filesystem::{ensure_writable_dir_exists, path_exists, FilesystemDriver};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tracing::warn;
+use uuid::Uuid;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_SOURCE_SIZE: usize = 16 * 1024 * 1024;
+const SOURCE_FILE_STEM: &str = "source";
+const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp", "gif"];
+const FAILURE_BACKOFF: Duration = Duration::from_secs(60);
+const MAX_FAILURE_BACKOFF: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ThumbnailSize {
+    Small,
+    Large,
+}
+
+impl ThumbnailSize {
+    const ALL: [ThumbnailSize; 2] = [ThumbnailSize::Small, ThumbnailSize::Large];
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "small" => Some(ThumbnailSize::Small),
+            "large" => Some(ThumbnailSize::Large),
+            _ => None,
+        }
+    }
+
+    // Small one is meant for cards in the web UI, at twice their width.
+    fn width(self) -> u32 {
+        match self {
+            ThumbnailSize::Small => 320,
+            ThumbnailSize::Large => 1280,
+        }
+    }
+
+    fn file_name(self) -> &'static str {
+        match self {
+            ThumbnailSize::Small => "small.jpg",
+            ThumbnailSize::Large => "large.jpg",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct FetchFailure {
+    num_failures: u32,
+    retry_at: Instant,
+}
+
+// Thumbnails are fetched on first request and kept until their task
+// is cleaned up. Original image is only kept around if resizing fails.
+#[derive(Debug)]
+pub struct ThumbnailCache {
+    fs: Arc<FilesystemDriver>,
+    db: Arc<Mutex<ServerDatabase>>,
+    client: reqwest::Client,
+    failures: Mutex<HashMap<Uuid, FetchFailure>>,
+    // Held while fetching, so each thumbnail is fetched only once at a time
+    fetch_locks: Mutex<HashMap<Uuid, Arc<Mutex<()>>>>,
+}
+
+impl ThumbnailCache {
+    pub fn new(fs: Arc<FilesystemDriver>, db: Arc<Mutex<ServerDatabase>>) -> Self {
+        Self {
+            fs,
+            db,
+            client: reqwest::Client::new(),
+            failures: Mutex::new(HashMap::new()),
+            fetch_locks: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Returns `None` if the task has no thumbnail, or fetching it
+    // has failed recently.
+    pub async fn get(&self, task_id: Uuid, size: ThumbnailSize) -> anyhow::Result<Option<PathBuf>> {
+        let dir = self.fs.get_thumbnail_dir(task_id);
+        if let Some(path) = find_cached(&dir, size).await {
+            return Ok(Some(path));
+        }
+
+        let lock = self
+            .fetch_locks
+            .lock()
+            .await
+            .entry(task_id)
+            .or_default()
+            .clone();
+        let result = {
+            let _guard = lock.lock().await;
+            // Whoever held the lock before may have fetched it already
+            match find_cached(&dir, size).await {
+                Some(path) => Ok(Some(path)),
+                None => self.fetch(task_id, size, &dir).await,
+            }
+        };
+        let mut fetch_locks = self.fetch_locks.lock().await;
+        if Arc::strong_count(&lock) == 2 {
+            fetch_locks.remove(&task_id);
+        }
+        result
+    }
+
+    async fn fetch(
+        &self,
+        task_id: Uuid,
+        size: ThumbnailSize,
+        dir: &Path,
+    ) -> anyhow::Result<Option<PathBuf>> {
+        if self.is_backing_off(task_id).await {
+            return Ok(None);
+        }
+
+        let (url, proxy) = match self.db.lock().await.get_thumbnail_source(task_id).await? {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+        ensure_writable_dir_exists(dir).await?;
+        let (temp_source, ext) = match self.download(&url, proxy.as_deref(), dir).await {
+            Ok(x) => x,
+            Err(e) => {
+                self.record_failure(task_id).await;
+                return Err(e);
+            }
+        };
+        self.failures.lock().await.remove(&task_id);
+
+        let resized = dir.join(size.file_name());
+        let mut is_resized = true;
+        for size in ThumbnailSize::ALL {
+            if let Err(e) = resize(&temp_source, &dir.join(size.file_name()), size.width()).await {
+                warn!("Failed to resize thumbnail of {task_id}: {e}");
+                is_resized = false;
+            }
+        }
+        // Downloaded file is private to this request until it's moved
+        // in place, so nobody can be reading it when it's removed.
+        if !is_resized {
+            let source = dir.join(format!("{SOURCE_FILE_STEM}.{ext}"));
+            tokio::fs::rename(&temp_source, &source).await?;
+            return Ok(Some(source));
+        }
+        let _ = tokio::fs::remove_file(&temp_source).await;
+        Ok(Some(resized))
+    }
+
+    async fn is_backing_off(&self, task_id: Uuid) -> bool {
+        self.failures
+            .lock()
+            .await
+            .get(&task_id)
+            .is_some_and(|x| x.retry_at > Instant::now())
+    }
+
+    // Failures are forgotten once they are long past their backoff,
+    // so tasks deleted meanwhile don't stay around.
+    async fn record_failure(&self, task_id: Uuid) {
+        let now = Instant::now();
+        let mut failures = self.failures.lock().await;
+        failures.retain(|_, x| x.retry_at + MAX_FAILURE_BACKOFF > now);
+        let num_failures = failures.get(&task_id).map_or(0, |x| x.num_failures) + 1;
+        failures.insert(
+            task_id,
+            FetchFailure {
+                num_failures,
+                retry_at: now + failure_backoff(num_failures),
+            },
+        );
+    }
+
+    async fn download(
+        &self,
+        url: &str,
+        proxy: Option<&str>,
+        dir: &Path,
+    ) -> anyhow::Result<(PathBuf, &'static str)> {
+        // Same route videos take, so the CDN doesn't see anything new
+        let client = match proxy {
+            Some(proxy) => reqwest::Client::builder()
+                .proxy(reqwest::Proxy::all(proxy)?)
+                .build()?,
+            None => self.client.clone(),
+        };
+        let response = client
+            .get(url)
+            .timeout(REQUEST_TIMEOUT)
+            .send()
+            .await?
+            .error_for_status()?;
+        if response.content_length().unwrap_or(0) as usize > MAX_SOURCE_SIZE {
+            return Err(anyhow!("thumbnail is too large"));
+        }
+        let ext = guess_extension(response.headers().get("content-type"), url);
+        let contents = response.bytes().await?;
+        if contents.len() > MAX_SOURCE_SIZE {
+            return Err(anyhow!("thumbnail is too large"));
+        }
+
+        // Unique name, so a part file left behind by a crash can't get in the way
+        let path = dir.join(format!("{}.part.{ext}", Uuid::new_v4()));
+        tokio::fs::write(&path, &contents).await?;
+        Ok((path, ext))
+    }
+}
+
+async fn find_cached(dir: &Path, size: ThumbnailSize) -> Option<PathBuf> {
+    let resized = dir.join(size.file_name());
+    if path_exists(&resized).await {
+        return Some(resized);
+    }
+    find_source(dir).await
+}
+
+async fn find_source(dir: &Path) -> Option<PathBuf> {
+    for ext in IMAGE_EXTENSIONS {
+        let path = dir.join(format!("{SOURCE_FILE_STEM}.{ext}"));
+        if path_exists(&path).await {
+            return Some(path);
+        }
+    }
+    None
+}
+
+// Served files get their content type from the extension.
+fn guess_extension(content_type: Option<&reqwest::header::HeaderValue>, url: &str) -> &'static str {
+    let content_type = content_type
+        .and_then(|x| x.to_str().ok())
+        .unwrap_or_default();
+    match content_type.split(';').next().unwrap_or_default().trim() {
+        "image/jpeg" => return "jpg",
+        "image/png" => return "png",
+        "image/webp" => return "webp",
+        "image/gif" => return "gif",
+        _ => {}
+    }
+    let url_ext = url
+        .split(['?', '#'])
+        .next()
+        .and_then(|x| x.rsplit_once('.'))
+        .map(|(_, ext)| ext.to_lowercase())
+        .unwrap_or_default();
+    IMAGE_EXTENSIONS
+        .iter()
+        .find(|x| **x == url_ext)
+        .copied()
+        .unwrap_or("jpg")
+}
+
+fn failure_backoff(num_failures: u32) -> Duration {
+    let factor = 1u32 << num_failures.saturating_sub(1).min(16);
+    (FAILURE_BACKOFF * factor).min(MAX_FAILURE_BACKOFF)
+}
+
+// Images narrower than `width` are left at their size.
+async fn resize(source: &Path, target: &Path, width: u32) -> anyhow::Result<()> {
+    let temp_path = target.with_file_name(format!("{}.part.jpg", Uuid::new_v4()));
+    let status = command::new_transcoder_command()
+        .arg("-y")
+        .arg("-loglevel")
+        .arg("error")
+        .arg("-i")
+        .arg(source)
+        .arg("-vf")
+        .arg(format!("scale='min({width},iw)':-2"))
+        .arg("-frames:v")
+        .arg("1")
+        .arg(&temp_path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .await?;
+    if !status.success() {
+        let _ = tokio::fs::remove_file(&temp_path).await;
+        return Err(anyhow!("ffmpeg exited with {status}"));
+    }
+    tokio::fs::rename(&temp_path, target).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failure_backoff_doubles_up_to_a_cap() {
+        let delays: Vec<u64> = (1..=8).map(|x| failure_backoff(x).as_secs()).collect();
+        assert_eq!(delays, [60, 120, 240, 480, 960, 1920, 3600, 3600]);
+        assert_eq!(failure_backoff(100), MAX_FAILURE_BACKOFF);
+    }
+}
@@ -0,0 +1,50 @@
+use crate::auth::User;
+use crate::job_manager::JobManagerHandle;
+use rocket::fs::NamedFile;
+use rocket::http::{ContentType, Status};
+use rocket::response::{self, Responder};
+use rocket::{post, Request, Response, State};
+use tracing::warn;
+
+pub struct BackupFile {
+    file: NamedFile,
+    name: String,
+}
+
+impl<'r> Responder<'r, 'static> for BackupFile {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        Response::build_from(self.file.respond_to(request)?)
+            .header(ContentType::Binary)
+            .raw_header(
+                "Content-Disposition",
+                format!("attachment; filename=\"{}\"", self.name),
+            )
+            .ok()
+    }
+}
+
+// Backup is also kept in the backups directory and counts toward rotation.
+#[post("/admin/backup")]
+pub async fn create_backup(
+    state: &State<JobManagerHandle>,
+    _user: User,
+) -> (Status, Option<BackupFile>) {
+    let path = match state.create_backup().await {
+        Ok(x) => x,
+        Err(e) => {
+            warn!("Failed to back up database: {e}");
+            return (Status::InternalServerError, None);
+        }
+    };
+    let name = path
+        .file_name()
+        .map(|x| x.to_string_lossy().to_string())
+        .unwrap_or_default();
+    match NamedFile::open(&path).await {
+        Ok(file) => (Status::Ok, Some(BackupFile { file, name })),
+        Err(e) => {
+            warn!("Failed to open backup {path:?}: {e}");
+            (Status::InternalServerError, None)
+        }
+    }
+}
@@ -19,18 +19,20 @@ pub async fn get_config(
     env: &State<Arc<EnvironmentManager>>,
     _user: User,
 ) -> (Status, Json<Config>) {
-    (Status::Ok, Json(env.config.clone()))
+    (Status::Ok, Json(env.config.redacted()))
 }
 
 #[post("/config", data = "<data>")]
 pub async fn set_config(
+    env: &State<Arc<EnvironmentManager>>,
     exit_status: &State<ExitStatusHandle>,
     shutdown: Shutdown,
     _user: User,
     data: Form<NewConfigForm>,
 ) -> Status {
     match serde_json::from_str::<Config>(&data.value) {
-        Ok(new_config) => {
+        Ok(mut new_config) => {
+            new_config.restore_secrets(&env.config);
             if EnvironmentManager::check_config_validity(&new_config).await {
                 warn!("Preparing for config update, requesting shutdown...");
                 exit_status
@@ -40,13 +42,13 @@ pub async fn set_config(
                 Status::Accepted
             } else {
                 {
-                    warn!("New config failed validation: {:?}", new_config);
+                    warn!("New config failed validation: {:?}", new_config.redacted());
                     Status::BadRequest
                 }
             }
         }
         Err(e) => {
-            warn!("Failed to parse proposed config: {e} {:?}", data.value);
+            warn!("Failed to parse proposed config: {e}");
             Status::BadRequest
         }
     }
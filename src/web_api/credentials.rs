@@ -0,0 +1,61 @@
+use crate::auth::User;
+use crate::credentials::{CredentialStore, CredentialsProfileInfo, CredentialsProfileUpdate};
+use rocket::form::Form;
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket::serde::{Deserialize, Serialize};
+use rocket::{get, post, FromForm, State};
+use std::sync::Arc;
+use tracing::warn;
+
+#[derive(Debug, Serialize, Deserialize, FromForm)]
+pub struct CredentialsProfileForm {
+    pub value: String,
+}
+
+#[get("/credentials")]
+pub async fn get_credentials(
+    credentials: &State<Arc<CredentialStore>>,
+    _user: User,
+) -> (Status, Json<Vec<CredentialsProfileInfo>>) {
+    (Status::Ok, Json(credentials.list().await))
+}
+
+#[post("/credentials/set", data = "<data>")]
+pub async fn set_credentials(
+    credentials: &State<Arc<CredentialStore>>,
+    _user: User,
+    data: Form<CredentialsProfileForm>,
+) -> Status {
+    let update = match serde_json::from_str::<CredentialsProfileUpdate>(&data.value) {
+        Ok(update) => update,
+        Err(e) => {
+            // Don't log the value, it contains secrets
+            warn!("Failed to parse proposed credentials profile: {e}");
+            return Status::BadRequest;
+        }
+    };
+    let name = update.profile.name.clone();
+    match credentials.update(update).await {
+        Ok(_) => Status::Accepted,
+        Err(e) => {
+            warn!("Failed to update credentials profile {name:?}: {e}");
+            Status::BadRequest
+        }
+    }
+}
+
+#[post("/credentials/delete/<name>")]
+pub async fn delete_credentials(
+    credentials: &State<Arc<CredentialStore>>,
+    _user: User,
+    name: &str,
+) -> Status {
+    match credentials.remove(name).await {
+        Ok(_) => Status::Accepted,
+        Err(e) => {
+            warn!("Failed to delete credentials profile {name:?}: {e}");
+            Status::NotFound
+        }
+    }
+}
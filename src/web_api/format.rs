@@ -1,5 +1,5 @@
 use crate::auth::User;
-use crate::env::{DownloadFormat, EnvironmentManager};
+use crate::env::{DownloadFormat, EnvironmentManager, TranscodePreset};
 use rocket::http::Status;
 use rocket::serde::json::Json;
 use rocket::{get, State};
@@ -13,3 +13,11 @@ pub async fn get_formats(
     let formats = env.ytdlp.get_all_formats();
     (Status::Ok, Json(formats))
 }
+
+#[get("/transcode_presets")]
+pub async fn get_transcode_presets(
+    env: &State<Arc<EnvironmentManager>>,
+    _user: User,
+) -> (Status, Json<&[TranscodePreset]>) {
+    (Status::Ok, Json(&env.config.transcode_presets))
+}
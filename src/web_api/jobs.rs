@@ -1,27 +1,104 @@
 use crate::auth::User;
-use crate::job::Job;
+use crate::credentials::CredentialStore;
+use crate::database::PlaylistEntry;
+use crate::env::EnvironmentManager;
+use crate::job::{parse_entry_indices, Job, SelectionRules};
+use crate::job_import::{parse_import, ImportFormat, ImportLineError, ImportOptions};
 use crate::job_manager::JobManagerHandle;
 use crate::queue_command::QueueCommand;
+use crate::url_host::is_valid_proxy_url;
+use rocket::data::{ByteUnit, Data};
 use rocket::form::Form;
 use rocket::http::Status;
 use rocket::serde::json::Json;
 use rocket::serde::{Deserialize, Serialize};
 use rocket::{get, post, FromForm, State};
+use std::sync::Arc;
 use tracing::warn;
 use uuid::Uuid;
 
+const MAX_IMPORT_SIZE: ByteUnit = ByteUnit::Mebibyte(16);
+
 #[derive(Debug, Serialize, Deserialize, FromForm)]
 pub struct NewJobForm {
     pub url: String,
     pub format: String,
+    pub credentials_profile: Option<String>,
+    pub proxy: Option<String>,
+    pub transcode_preset: Option<String>,
+    pub preview: Option<bool>,
+    pub select_first: Option<u32>,
+    pub select_newest: Option<u32>,
+    pub select_title: Option<String>,
+}
+
+impl NewJobForm {
+    pub fn selection_rules(&self) -> SelectionRules {
+        SelectionRules {
+            select_first: self.select_first,
+            select_newest: self.select_newest,
+            select_title: self.select_title.clone(),
+        }
+    }
+
+    // Selection rules only make sense for preview jobs.
+    pub fn is_preview(&self) -> bool {
+        self.preview.unwrap_or(false) || !self.selection_rules().is_empty()
+    }
+}
+
+// Settings shared by jobs created through any of the endpoints.
+async fn are_job_options_valid(
+    env: &EnvironmentManager,
+    credentials: &CredentialStore,
+    credentials_profile: Option<&str>,
+    proxy: Option<&str>,
+    transcode_preset: Option<&str>,
+) -> bool {
+    if let Some(preset) = transcode_preset {
+        if env.config.get_transcode_preset(preset).is_none() {
+            warn!("Refusing to start job with unknown transcode preset {preset:?}");
+            return false;
+        }
+    }
+    if let Some(proxy) = proxy {
+        if !is_valid_proxy_url(proxy) {
+            warn!("Refusing to start job with invalid proxy {proxy:?}");
+            return false;
+        }
+    }
+    if let Some(profile) = credentials_profile {
+        if !credentials.contains(profile).await {
+            warn!("Refusing to start job with unknown credentials profile {profile:?}");
+            return false;
+        }
+    }
+    true
 }
 
 #[post("/jobs/new", data = "<data>")]
 pub async fn new_job(
     state: &State<JobManagerHandle>,
+    credentials: &State<Arc<CredentialStore>>,
+    env: &State<Arc<EnvironmentManager>>,
     _user: User,
     data: Form<NewJobForm>,
 ) -> (Status, Option<Json<Job>>) {
+    let is_valid = are_job_options_valid(
+        env,
+        credentials,
+        data.credentials_profile.as_deref(),
+        data.proxy.as_deref(),
+        data.transcode_preset.as_deref(),
+    )
+    .await;
+    if !is_valid {
+        return (Status::BadRequest, None);
+    }
+    if let Err(e) = data.selection_rules().title_regex() {
+        warn!("Refusing to start job with invalid title pattern: {e}");
+        return (Status::BadRequest, None);
+    }
     match state.create_job(&data).await {
         Ok(val) => (Status::Accepted, Some(Json(val))),
         Err(e) => {
@@ -34,6 +111,165 @@ pub async fn new_job(
     }
 }
 
+#[derive(Debug, Serialize)]
+pub struct ImportReport {
+    pub jobs: Vec<Job>,
+    pub num_imported: usize,
+    pub num_duplicates: u32,
+    pub errors: Vec<ImportLineError>,
+}
+
+// Body is the file itself: a list of URLs, CSV, browser bookmarks or
+// Google Takeout JSON. `source` is detected when not given, `format`
+// applies to entries which don't specify their own.
+#[allow(clippy::too_many_arguments)]
+#[post(
+    "/jobs/import?<format>&<source>&<group>&<credentials_profile>&<proxy>&<transcode_preset>",
+    data = "<contents>"
+)]
+pub async fn import_jobs(
+    state: &State<JobManagerHandle>,
+    credentials: &State<Arc<CredentialStore>>,
+    env: &State<Arc<EnvironmentManager>>,
+    _user: User,
+    format: String,
+    source: Option<&str>,
+    group: Option<bool>,
+    credentials_profile: Option<String>,
+    proxy: Option<String>,
+    transcode_preset: Option<String>,
+    contents: Data<'_>,
+) -> (Status, Option<Json<ImportReport>>) {
+    if env.ytdlp.try_get_format(&format).is_none() {
+        warn!("Refusing to import jobs with unknown format {format:?}");
+        return (Status::BadRequest, None);
+    }
+    let is_valid = are_job_options_valid(
+        env,
+        credentials,
+        credentials_profile.as_deref(),
+        proxy.as_deref(),
+        transcode_preset.as_deref(),
+    )
+    .await;
+    if !is_valid {
+        return (Status::BadRequest, None);
+    }
+
+    let contents = match contents.open(MAX_IMPORT_SIZE).into_string().await {
+        Ok(x) if x.is_complete() => x.into_inner(),
+        Ok(_) => return (Status::PayloadTooLarge, None),
+        Err(e) => {
+            warn!("Failed to read imported list: {e}");
+            return (Status::BadRequest, None);
+        }
+    };
+    let source = match source.map(ImportFormat::parse) {
+        Some(Some(x)) => x,
+        Some(None) => return (Status::BadRequest, None),
+        None => ImportFormat::detect(&contents),
+    };
+    let parsed = parse_import(&contents, source, |x| env.ytdlp.try_get_format(x).is_some());
+
+    let options = ImportOptions {
+        format,
+        credentials_profile,
+        proxy,
+        transcode_preset,
+        group: group.unwrap_or(true),
+    };
+    let imported = match state.import_jobs(&parsed.entries, &options).await {
+        Ok(x) => x,
+        Err(e) => {
+            warn!("Failed to import jobs: {e}");
+            return (Status::InternalServerError, None);
+        }
+    };
+
+    let num_imported = parsed.entries.len() - imported.num_already_queued as usize;
+    let status = if num_imported > 0 {
+        Status::Accepted
+    } else if parsed.errors.is_empty() {
+        Status::Ok
+    } else {
+        Status::BadRequest
+    };
+    let report = ImportReport {
+        jobs: imported.jobs,
+        num_imported,
+        num_duplicates: parsed.num_duplicates + imported.num_already_queued,
+        errors: parsed.errors,
+    };
+    (status, Some(Json(report)))
+}
+
+#[get("/jobs/entries/<job_id>")]
+pub async fn get_playlist_entries(
+    state: &State<JobManagerHandle>,
+    _user: User,
+    job_id: Uuid,
+) -> (Status, Option<Json<Vec<PlaylistEntry>>>) {
+    match state.get_playlist_entries(job_id).await {
+        Ok(val) => (Status::Ok, Some(Json(val))),
+        Err(e) => {
+            warn!("Failed to get playlist entries of job {job_id}: {e}");
+            (Status::InternalServerError, None)
+        }
+    }
+}
+
+#[derive(Debug, FromForm)]
+pub struct SelectEntriesForm {
+    pub entries: Option<String>,
+    pub select_first: Option<u32>,
+    pub select_newest: Option<u32>,
+    pub select_title: Option<String>,
+}
+
+// Entries are given as "1-3,7,10-", counting from 1, and/or picked by
+// the same rules preview jobs accept on creation.
+#[post("/jobs/select/<job_id>", data = "<data>")]
+pub async fn select_playlist_entries(
+    state: &State<JobManagerHandle>,
+    _user: User,
+    job_id: Uuid,
+    data: Form<SelectEntriesForm>,
+) -> (Status, Option<Json<Job>>) {
+    let entries = match state.get_playlist_entries(job_id).await {
+        Ok(x) => x,
+        Err(e) => {
+            warn!("Failed to get playlist entries of job {job_id}: {e}");
+            return (Status::InternalServerError, None);
+        }
+    };
+    let mut indices = match &data.entries {
+        Some(spec) => match parse_entry_indices(spec, entries.len() as u32) {
+            Some(x) => x,
+            None => return (Status::BadRequest, None),
+        },
+        None => Default::default(),
+    };
+    let rules = SelectionRules {
+        select_first: data.select_first,
+        select_newest: data.select_newest,
+        select_title: data.select_title.clone(),
+    };
+    match rules.select(&entries) {
+        Ok(x) => indices.extend(x),
+        Err(e) => {
+            warn!("Refusing to select entries with invalid title pattern: {e}");
+            return (Status::BadRequest, None);
+        }
+    }
+    match state.select_playlist_entries(job_id, &indices).await {
+        Ok(val) => (Status::Accepted, Some(Json(val))),
+        Err(e) => {
+            warn!("Failed to select playlist entries of job {job_id}: {e}");
+            (Status::InternalServerError, None)
+        }
+    }
+}
+
 #[get("/jobs/get/<job_id>")]
 pub async fn get_job(
     state: &State<JobManagerHandle>,
@@ -0,0 +1,110 @@
+use crate::auth::User;
+use crate::job::{JobManifest, ManifestFormat};
+use crate::job_manager::JobManagerHandle;
+use rocket::http::{ContentType, Status};
+use rocket::response::{self, Responder};
+use rocket::{get, Request, Response, State};
+use std::io::Cursor;
+use tracing::warn;
+use uuid::Uuid;
+
+pub struct ManifestFile {
+    content_type: ContentType,
+    name: String,
+    contents: String,
+}
+
+impl<'r> Responder<'r, 'static> for ManifestFile {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        Response::build()
+            .header(self.content_type)
+            .raw_header("Content-Disposition", content_disposition(&self.name))
+            .sized_body(self.contents.len(), Cursor::new(self.contents))
+            .ok()
+    }
+}
+
+// Titles are rarely plain ASCII, so the real name goes in `filename*`
+// (RFC 5987) and older clients get one with the rest replaced.
+fn content_disposition(name: &str) -> String {
+    let fallback: String = name
+        .chars()
+        .map(|x| match x {
+            ' '..='~' if x != '"' && x != '\\' => x,
+            _ => '_',
+        })
+        .collect();
+    let encoded: String = name
+        .bytes()
+        .map(|x| {
+            if x.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&x) {
+                (x as char).to_string()
+            } else {
+                format!("%{x:02X}")
+            }
+        })
+        .collect();
+    format!("attachment; filename=\"{fallback}\"; filename*=UTF-8''{encoded}")
+}
+
+// Playlist exported here lists paths as they were saved, since it's
+// not necessarily going to end up next to the files.
+#[get("/jobs/export/<job_id>?<format>")]
+pub async fn export_job(
+    state: &State<JobManagerHandle>,
+    _user: User,
+    job_id: Uuid,
+    format: Option<&str>,
+) -> (Status, Option<ManifestFile>) {
+    let format = match format.map(ManifestFormat::parse) {
+        Some(Some(x)) => x,
+        Some(None) => return (Status::BadRequest, None),
+        None => ManifestFormat::Json,
+    };
+    let job = match state.get_job(job_id).await {
+        Ok(x) => x,
+        Err(e) => {
+            warn!("Failed to get job {job_id}: {e}");
+            return (Status::InternalServerError, None);
+        }
+    };
+    let manifest = JobManifest::new(&job);
+    let (content_type, contents) = match format {
+        ManifestFormat::Json => match serde_json::to_string_pretty(&manifest) {
+            Ok(x) => (ContentType::JSON, x),
+            Err(e) => {
+                warn!("Failed to serialize manifest of job {job_id}: {e}");
+                return (Status::InternalServerError, None);
+            }
+        },
+        ManifestFormat::Csv => (ContentType::CSV, manifest.to_csv()),
+        ManifestFormat::M3u => (
+            ContentType::new("audio", "x-mpegurl"),
+            manifest.to_m3u(None),
+        ),
+    };
+    let name = format!("{}.{}", filenamify::filenamify(&job.title), format.ext());
+    let file = ManifestFile {
+        content_type,
+        name,
+        contents,
+    };
+    (Status::Ok, Some(file))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn content_disposition_has_ascii_fallback_and_utf8_name() {
+        assert_eq!(
+            content_disposition("Mix.m3u8"),
+            "attachment; filename=\"Mix.m3u8\"; filename*=UTF-8''Mix.m3u8"
+        );
+        assert_eq!(
+            content_disposition("Café \"live\".csv"),
+            "attachment; filename=\"Caf_ _live_.csv\"; filename*=UTF-8''Caf%C3%A9%20%22live%22.csv"
+        );
+    }
+}
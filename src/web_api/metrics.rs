@@ -0,0 +1,28 @@
+use crate::auth::User;
+use crate::env::EnvironmentManager;
+use crate::job_manager::JobManagerHandle;
+use rocket::http::{ContentType, Status};
+use rocket::{get, State};
+use std::sync::Arc;
+use tracing::warn;
+
+#[get("/metrics")]
+pub async fn get_metrics(
+    env: &State<Arc<EnvironmentManager>>,
+    state: &State<JobManagerHandle>,
+    user: Option<User>,
+) -> (Status, Option<(ContentType, String)>) {
+    if user.is_none() && !env.config.metrics_auth_exempt {
+        return (Status::Unauthorized, None);
+    }
+    match state.render_metrics().await {
+        Ok(text) => {
+            let content_type = ContentType::new("text", "plain").with_params(("version", "0.0.4"));
+            (Status::Ok, Some((content_type, text)))
+        }
+        Err(e) => {
+            warn!("Failed to render metrics: {e}");
+            (Status::InternalServerError, None)
+        }
+    }
+}
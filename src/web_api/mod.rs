@@ -1,24 +1,40 @@
 use rocket::{routes, Route};
 
+mod admin;
 mod config;
+mod credentials;
 mod format;
 mod index;
 mod jobs;
+mod manifests;
+mod metrics;
 mod ping;
 mod sessions;
 mod shutdown;
+mod stats;
 mod status;
+mod task_logs;
 mod tasks;
+mod thumbnails;
+mod webhooks;
 
+use admin::*;
 use config::*;
+use credentials::*;
 use format::*;
 use index::*;
 use jobs::*;
+use manifests::*;
+use metrics::*;
 use ping::*;
 use sessions::*;
 use shutdown::*;
+use stats::*;
 use status::*;
+use task_logs::*;
 use tasks::*;
+use thumbnails::*;
+use webhooks::*;
 
 pub use jobs::NewJobForm;
 
@@ -26,13 +42,17 @@ pub fn get_api_routes() -> Vec<Route> {
     routes![
         pong,
         new_job,
+        import_jobs,
         get_job,
+        get_playlist_entries,
+        select_playlist_entries,
         pause_job,
         resume_job,
         cancel_job,
         retry_job,
         delete_job,
         get_all_jobs,
+        export_job,
         pause_all_jobs,
         resume_all_jobs,
         cancel_all_jobs,
@@ -40,21 +60,37 @@ pub fn get_api_routes() -> Vec<Route> {
         delete_all_jobs,
         get_task_stdout,
         get_task_stderr,
+        get_task_hooks_log,
+        follow_task_log,
+        get_task_artifacts,
+        get_thumbnail,
         pause_task,
         resume_task,
         cancel_task,
         retry_task,
         delete_task,
         get_status,
+        get_history_stats,
         get_config,
         set_config,
         get_formats,
+        get_transcode_presets,
+        get_credentials,
+        set_credentials,
+        delete_credentials,
+        get_webhook_deliveries,
+        create_backup,
         shutdown_server,
         new_session,
         expire_all_sessions,
     ]
 }
 
+// Served outside of `/api`, where Prometheus expects to find it.
+pub fn get_metrics_routes() -> Vec<Route> {
+    routes![get_metrics]
+}
+
 pub fn get_index_html_redirect() -> Vec<Route> {
     routes![root_redirect, index_html_redirect,]
 }
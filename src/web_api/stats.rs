@@ -0,0 +1,62 @@
+use crate::auth::User;
+use crate::database::{HistoryBucket, HistoryStats};
+use crate::job_manager::JobManagerHandle;
+use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket::{get, State};
+use tracing::warn;
+
+const DEFAULT_HISTORY_DAYS: i64 = 30;
+
+// Accepts either RFC 3339 time or a plain date. A plain date used as
+// the end of range includes the whole day.
+fn parse_time(value: &str, is_range_end: bool) -> Option<DateTime<Utc>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Some(time.with_timezone(&Utc));
+    }
+    let mut date = NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?;
+    if is_range_end {
+        date = date.succ_opt()?;
+    }
+    Some(Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0)?))
+}
+
+#[get("/stats/history?<from>&<to>&<bucket>")]
+pub async fn get_history_stats(
+    state: &State<JobManagerHandle>,
+    _user: User,
+    from: Option<&str>,
+    to: Option<&str>,
+    bucket: Option<&str>,
+) -> (Status, Option<Json<HistoryStats>>) {
+    let to = match to {
+        Some(value) => match parse_time(value, true) {
+            Some(x) => x,
+            None => return (Status::BadRequest, None),
+        },
+        None => Utc::now(),
+    };
+    let from = match from {
+        Some(value) => match parse_time(value, false) {
+            Some(x) => x,
+            None => return (Status::BadRequest, None),
+        },
+        None => to - Duration::days(DEFAULT_HISTORY_DAYS),
+    };
+    let bucket = match bucket.map(HistoryBucket::parse) {
+        Some(Some(x)) => x,
+        Some(None) => return (Status::BadRequest, None),
+        None => HistoryBucket::Day,
+    };
+    if from >= to {
+        return (Status::BadRequest, None);
+    }
+    match state.get_history_stats(from, to, bucket).await {
+        Ok(stats) => (Status::Ok, Some(Json(stats))),
+        Err(e) => {
+            warn!("Failed to get history stats: {e}");
+            (Status::InternalServerError, None)
+        }
+    }
+}
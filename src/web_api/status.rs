@@ -1,18 +1,37 @@
 use crate::auth::User;
 use crate::database::TaskStats;
-use crate::job_manager::JobManagerHandle;
+use crate::filesystem::SweepStats;
+use crate::job_manager::{JobManagerHandle, PauseReason};
 use rocket::http::Status;
 use rocket::serde::json::Json;
+use rocket::serde::Serialize;
 use rocket::{get, State};
 use tracing::warn;
 
+#[derive(Debug, Serialize)]
+pub struct ServerStatus {
+    #[serde(flatten)]
+    pub tasks: TaskStats,
+    pub pause_reason: Option<PauseReason>,
+    pub held_back_tasks: Vec<PauseReason>,
+    pub worker_dir_sweeps: SweepStats,
+}
+
 #[get("/status")]
 pub async fn get_status(
     state: &State<JobManagerHandle>,
     _user: User,
-) -> (Status, Option<Json<TaskStats>>) {
+) -> (Status, Option<Json<ServerStatus>>) {
     match state.get_overall_stats().await {
-        Ok(val) => (Status::Ok, Some(Json(val))),
+        Ok(val) => {
+            let status = ServerStatus {
+                tasks: val,
+                pause_reason: state.get_pause_reason().await,
+                held_back_tasks: state.get_held_back_tasks().await,
+                worker_dir_sweeps: state.get_sweep_stats().await,
+            };
+            (Status::Ok, Some(Json(status)))
+        }
         Err(e) => {
             warn!("Failed to get queue status: {e}");
             (Status::InternalServerError, None)
@@ -0,0 +1,402 @@
+use crate::auth::User;
+use crate::filesystem::FilesystemDriver;
+use crate::job_manager::JobManagerHandle;
+use rocket::http::{ContentType, Status};
+use rocket::request::{FromParam, FromRequest, Outcome};
+use rocket::response::stream::{Event, EventStream};
+use rocket::response::{self, Responder};
+use rocket::{get, Request, Response, Shutdown, State};
+use std::io::{Cursor, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use uuid::Uuid;
+
+const FOLLOW_POLL_INTERVAL: Duration = Duration::from_millis(250);
+const DEFAULT_FOLLOW_TAIL: usize = 100;
+const TAIL_READ_CHUNK: u64 = 64 * 1024;
+
+#[derive(Debug, Clone, Copy)]
+pub enum LogKind {
+    Stdout,
+    Stderr,
+    Hooks,
+}
+
+impl LogKind {
+    fn get_path(self, fs: &FilesystemDriver, task_id: Uuid) -> PathBuf {
+        match self {
+            LogKind::Stdout => fs.get_ytdlp_stdout_file(task_id),
+            LogKind::Stderr => fs.get_ytdlp_stderr_file(task_id),
+            LogKind::Hooks => fs.get_hooks_log_file(task_id),
+        }
+    }
+}
+
+impl<'a> FromParam<'a> for LogKind {
+    type Error = &'a str;
+
+    fn from_param(param: &'a str) -> Result<Self, Self::Error> {
+        match param {
+            "stdout" => Ok(LogKind::Stdout),
+            "stderr" => Ok(LogKind::Stderr),
+            "hooks" => Ok(LogKind::Hooks),
+            _ => Err(param),
+        }
+    }
+}
+
+// Raw value of the `Range` header.
+pub struct RangeHeader(String);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RangeHeader {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match req.headers().get_one("Range") {
+            Some(value) => Outcome::Success(RangeHeader(value.to_string())),
+            None => Outcome::Forward(()),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum ByteRange {
+    Whole,
+    Slice { start: u64, end: u64 },
+    Unsatisfiable,
+}
+
+impl ByteRange {
+    // Only a single range is supported, anything else gets the whole file.
+    fn parse(value: &str, total: u64) -> Self {
+        let spec = match value.trim().strip_prefix("bytes=") {
+            Some(spec) if !spec.contains(',') => spec.trim(),
+            _ => return ByteRange::Whole,
+        };
+        let (first, last) = match spec.split_once('-') {
+            Some(x) => x,
+            None => return ByteRange::Whole,
+        };
+        if first.is_empty() {
+            // Suffix range, e.g. "bytes=-500" for the last 500 bytes
+            return match last.parse::<u64>() {
+                Ok(0) => ByteRange::Unsatisfiable,
+                Ok(_) if total == 0 => ByteRange::Unsatisfiable,
+                Ok(len) => ByteRange::Slice {
+                    start: total.saturating_sub(len),
+                    end: total - 1,
+                },
+                Err(_) => ByteRange::Whole,
+            };
+        }
+        let start = match first.parse::<u64>() {
+            Ok(start) => start,
+            Err(_) => return ByteRange::Whole,
+        };
+        let end = if last.is_empty() {
+            total.saturating_sub(1)
+        } else {
+            match last.parse::<u64>() {
+                Ok(end) if end >= start => end.min(total.saturating_sub(1)),
+                _ => return ByteRange::Whole,
+            }
+        };
+        if start >= total {
+            ByteRange::Unsatisfiable
+        } else {
+            ByteRange::Slice { start, end }
+        }
+    }
+}
+
+pub enum LogResponse {
+    NotFound,
+    Full(Vec<u8>),
+    Partial {
+        data: Vec<u8>,
+        start: u64,
+        total: u64,
+    },
+    Unsatisfiable {
+        total: u64,
+    },
+}
+
+impl<'r> Responder<'r, 'static> for LogResponse {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        let mut response = Response::build();
+        response
+            .header(ContentType::Plain)
+            .raw_header("Accept-Ranges", "bytes");
+        match self {
+            LogResponse::NotFound => {
+                let body = "Log not available.";
+                response
+                    .status(Status::NotFound)
+                    .sized_body(body.len(), Cursor::new(body));
+            }
+            LogResponse::Full(data) => {
+                response.sized_body(data.len(), Cursor::new(data));
+            }
+            LogResponse::Partial { data, start, total } => {
+                let end = start + data.len() as u64 - 1;
+                response
+                    .status(Status::PartialContent)
+                    .raw_header("Content-Range", format!("bytes {start}-{end}/{total}"))
+                    .sized_body(data.len(), Cursor::new(data));
+            }
+            LogResponse::Unsatisfiable { total } => {
+                response
+                    .status(Status::RangeNotSatisfiable)
+                    .raw_header("Content-Range", format!("bytes */{total}"));
+            }
+        }
+        response.ok()
+    }
+}
+
+async fn read_from(file: &mut File, start: u64, len: u64) -> std::io::Result<Vec<u8>> {
+    let mut data = vec![];
+    file.seek(SeekFrom::Start(start)).await?;
+    file.take(len).read_to_end(&mut data).await?;
+    Ok(data)
+}
+
+// Reads the last `num_lines` lines of the first `total` bytes of file,
+// going backwards so that huge logs don't have to be read whole.
+async fn read_tail(file: &mut File, total: u64, num_lines: usize) -> std::io::Result<Vec<u8>> {
+    if num_lines == 0 {
+        return Ok(vec![]);
+    }
+    let mut data = vec![];
+    let mut start = total;
+    let mut first_line_start = None;
+    while first_line_start.is_none() && start > 0 {
+        let chunk_start = start.saturating_sub(TAIL_READ_CHUNK);
+        let mut chunk = read_from(file, chunk_start, start - chunk_start).await?;
+        chunk.append(&mut data);
+        data = chunk;
+        start = chunk_start;
+
+        // Trailing newline ends the last line rather than starts a new one
+        let body_len = data.strip_suffix(b"\n").unwrap_or(&data).len();
+        first_line_start = data[..body_len]
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, x)| **x == b'\n')
+            .nth(num_lines - 1)
+            .map(|(pos, _)| pos + 1);
+    }
+    Ok(data.split_off(first_line_start.unwrap_or(0)))
+}
+
+async fn serve_log(path: &Path, tail: Option<usize>, range: Option<RangeHeader>) -> LogResponse {
+    let mut file = match File::open(path).await {
+        Ok(file) => file,
+        Err(_) => return LogResponse::NotFound,
+    };
+    let total = match file.metadata().await {
+        Ok(metadata) => metadata.len(),
+        Err(_) => return LogResponse::NotFound,
+    };
+    if let Some(num_lines) = tail {
+        return match read_tail(&mut file, total, num_lines).await {
+            Ok(data) => LogResponse::Full(data),
+            Err(_) => LogResponse::NotFound,
+        };
+    }
+    let range = match range {
+        Some(range) => ByteRange::parse(&range.0, total),
+        None => ByteRange::Whole,
+    };
+    match range {
+        ByteRange::Whole => match read_from(&mut file, 0, total).await {
+            Ok(data) => LogResponse::Full(data),
+            Err(_) => LogResponse::NotFound,
+        },
+        ByteRange::Slice { start, end } => match read_from(&mut file, start, end - start + 1).await
+        {
+            Ok(data) if !data.is_empty() => LogResponse::Partial { data, start, total },
+            Ok(_) => LogResponse::Unsatisfiable { total },
+            Err(_) => LogResponse::NotFound,
+        },
+        ByteRange::Unsatisfiable => LogResponse::Unsatisfiable { total },
+    }
+}
+
+fn line_to_event(line: &[u8]) -> Event {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    Event::data(String::from_utf8_lossy(line).replace('\r', ""))
+}
+
+#[get("/tasks/get_stdout/<task_id>?<tail>")]
+pub async fn get_task_stdout(
+    fs: &State<Arc<FilesystemDriver>>,
+    _user: User,
+    task_id: Uuid,
+    tail: Option<usize>,
+    range: Option<RangeHeader>,
+) -> LogResponse {
+    serve_log(&LogKind::Stdout.get_path(fs, task_id), tail, range).await
+}
+
+#[get("/tasks/get_stderr/<task_id>?<tail>")]
+pub async fn get_task_stderr(
+    fs: &State<Arc<FilesystemDriver>>,
+    _user: User,
+    task_id: Uuid,
+    tail: Option<usize>,
+    range: Option<RangeHeader>,
+) -> LogResponse {
+    serve_log(&LogKind::Stderr.get_path(fs, task_id), tail, range).await
+}
+
+#[get("/tasks/get_hooks_log/<task_id>?<tail>")]
+pub async fn get_task_hooks_log(
+    fs: &State<Arc<FilesystemDriver>>,
+    _user: User,
+    task_id: Uuid,
+    tail: Option<usize>,
+    range: Option<RangeHeader>,
+) -> LogResponse {
+    serve_log(&LogKind::Hooks.get_path(fs, task_id), tail, range).await
+}
+
+// Sends last `tail` lines of the log, and then new lines as they get
+// written, until the task stops running.
+#[get("/tasks/follow_log/<task_id>/<kind>?<tail>")]
+pub async fn follow_task_log(
+    fs: &State<Arc<FilesystemDriver>>,
+    state: &State<JobManagerHandle>,
+    _user: User,
+    task_id: Uuid,
+    kind: LogKind,
+    tail: Option<usize>,
+    mut shutdown: Shutdown,
+) -> EventStream![] {
+    let path = kind.get_path(fs, task_id);
+    let job_manager = state.inner().clone();
+    let num_lines = tail.unwrap_or(DEFAULT_FOLLOW_TAIL);
+    EventStream! {
+        let mut offset = 0u64;
+        if let Ok(mut file) = File::open(&path).await {
+            let total = file.metadata().await.map(|x| x.len()).unwrap_or_default();
+            let data = read_tail(&mut file, total, num_lines).await.unwrap_or_default();
+            for line in data.split_inclusive(|&x| x == b'\n') {
+                yield line_to_event(line);
+            }
+            offset = total;
+        }
+
+        let mut pending = vec![];
+        loop {
+            // Checked before reading, so that nothing written last gets lost
+            let is_active = job_manager.is_task_active(task_id).await;
+            if let Ok(mut file) = File::open(&path).await {
+                let total = file.metadata().await.map(|x| x.len()).unwrap_or_default();
+                // Log has been rewritten from scratch
+                if total < offset {
+                    offset = 0;
+                }
+                if let Ok(mut data) = read_from(&mut file, offset, total - offset).await {
+                    offset += data.len() as u64;
+                    pending.append(&mut data);
+                }
+            }
+            while let Some(pos) = pending.iter().position(|&x| x == b'\n') {
+                let line: Vec<u8> = pending.drain(..=pos).collect();
+                yield line_to_event(&line);
+            }
+            if !is_active {
+                if !pending.is_empty() {
+                    yield line_to_event(&pending);
+                }
+                break;
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(FOLLOW_POLL_INTERVAL) => {},
+                _ = &mut shutdown => break,
+            }
+        }
+        yield Event::empty().event("end");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn suffix_range_is_clamped_to_the_file() {
+        let slice = |start, end| ByteRange::Slice { start, end };
+        assert_eq!(ByteRange::parse("bytes=-3", 10), slice(7, 9));
+        assert_eq!(ByteRange::parse("bytes=-30", 10), slice(0, 9));
+        assert_eq!(ByteRange::parse("bytes=-0", 10), ByteRange::Unsatisfiable);
+        assert_eq!(ByteRange::parse("bytes=-3", 0), ByteRange::Unsatisfiable);
+    }
+
+    #[test]
+    fn range_past_the_end_is_unsatisfiable() {
+        assert_eq!(ByteRange::parse("bytes=10-", 10), ByteRange::Unsatisfiable);
+        assert_eq!(
+            ByteRange::parse("bytes=12-20", 10),
+            ByteRange::Unsatisfiable
+        );
+        assert_eq!(ByteRange::parse("bytes=0-", 0), ByteRange::Unsatisfiable);
+        assert_eq!(
+            ByteRange::parse("bytes=2-100", 10),
+            ByteRange::Slice { start: 2, end: 9 }
+        );
+    }
+
+    #[test]
+    fn unsupported_ranges_get_the_whole_file() {
+        assert_eq!(ByteRange::parse("bytes=0-1,5-6", 10), ByteRange::Whole);
+        assert_eq!(ByteRange::parse("bytes=5-2", 10), ByteRange::Whole);
+        assert_eq!(ByteRange::parse("bytes=a-", 10), ByteRange::Whole);
+        assert_eq!(ByteRange::parse("lines=0-1", 10), ByteRange::Whole);
+    }
+
+    async fn tail_of(dir: &TempDir, contents: &[u8], num_lines: usize) -> Vec<u8> {
+        let path = dir.path().join("log.txt");
+        tokio::fs::write(&path, contents).await.unwrap();
+        let mut file = File::open(&path).await.unwrap();
+        read_tail(&mut file, contents.len() as u64, num_lines)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn tail_of_empty_file_is_empty() {
+        let dir = TempDir::new();
+        assert!(tail_of(&dir, b"", 10).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn tail_keeps_trailing_newline_as_it_is() {
+        let dir = TempDir::new();
+        assert_eq!(tail_of(&dir, b"a\nb\nc\n", 2).await, b"b\nc\n");
+        assert_eq!(tail_of(&dir, b"a\nb\nc", 2).await, b"b\nc");
+        assert_eq!(tail_of(&dir, b"a\nb\nc", 5).await, b"a\nb\nc");
+        assert!(tail_of(&dir, b"a\nb\nc", 0).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn tail_spans_several_chunks() {
+        let dir = TempDir::new();
+        let lines: Vec<String> = (0..2000).map(|x| format!("{x:099}\n")).collect();
+        let contents = lines.concat();
+        // 700 lines of 100 bytes go past the first chunk read
+        let expected = lines[1300..].concat();
+        assert!(expected.len() as u64 > TAIL_READ_CHUNK);
+        let tail = tail_of(&dir, contents.as_bytes(), 700).await;
+        assert_eq!(String::from_utf8(tail).unwrap(), expected);
+    }
+}
@@ -1,37 +1,26 @@
 use crate::auth::User;
-use crate::filesystem::FilesystemDriver;
 use crate::job_manager::JobManagerHandle;
 use crate::queue_command::QueueCommand;
-use rocket::fs::NamedFile;
+use crate::task::TaskArtifact;
 use rocket::http::Status;
-use rocket::response::status::NotFound;
+use rocket::serde::json::Json;
 use rocket::{get, post, State};
-use std::sync::Arc;
 use tracing::warn;
 use uuid::Uuid;
 
-#[get("/tasks/get_stdout/<task_id>")]
-pub async fn get_task_stdout(
-    fs: &State<Arc<FilesystemDriver>>,
-    _user: User,
-    task_id: Uuid,
-) -> Result<NamedFile, NotFound<String>> {
-    let path = fs.get_ytdlp_stdout_file(task_id);
-    NamedFile::open(path)
-        .await
-        .map_err(|_| NotFound("Log not available.".to_string()))
-}
-
-#[get("/tasks/get_stderr/<task_id>")]
-pub async fn get_task_stderr(
-    fs: &State<Arc<FilesystemDriver>>,
+#[get("/tasks/get_artifacts/<task_id>")]
+pub async fn get_task_artifacts(
+    state: &State<JobManagerHandle>,
     _user: User,
     task_id: Uuid,
-) -> Result<NamedFile, NotFound<String>> {
-    let path = fs.get_ytdlp_stderr_file(task_id);
-    NamedFile::open(path)
-        .await
-        .map_err(|_| NotFound("Log not available.".to_string()))
+) -> (Status, Option<Json<Vec<TaskArtifact>>>) {
+    match state.get_task_artifacts(task_id).await {
+        Ok(artifacts) => (Status::Ok, Some(Json(artifacts))),
+        Err(e) => {
+            warn!("Failed to get artifacts of task {task_id}: {e}");
+            (Status::InternalServerError, None)
+        }
+    }
 }
 
 #[post("/tasks/pause/<task_id>")]
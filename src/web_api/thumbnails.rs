@@ -0,0 +1,55 @@
+use crate::auth::User;
+use crate::thumbnails::{ThumbnailCache, ThumbnailSize};
+use rocket::fs::NamedFile;
+use rocket::http::Status;
+use rocket::response::{self, Responder};
+use rocket::{get, Request, Response, State};
+use std::sync::Arc;
+use tracing::warn;
+use uuid::Uuid;
+
+// Cached thumbnail of a task never changes.
+const CACHE_CONTROL: &str = "private, max-age=604800, immutable";
+
+pub struct ThumbnailFile {
+    file: NamedFile,
+}
+
+impl<'r> Responder<'r, 'static> for ThumbnailFile {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        Response::build_from(self.file.respond_to(request)?)
+            .raw_header("Cache-Control", CACHE_CONTROL)
+            .ok()
+    }
+}
+
+// Web UI fetches these with its auth headers rather than linking them
+// from image tags, since every request may make the server go fetch one.
+#[get("/thumbnails/<task_id>?<size>")]
+pub async fn get_thumbnail(
+    cache: &State<Arc<ThumbnailCache>>,
+    _user: User,
+    task_id: Uuid,
+    size: Option<&str>,
+) -> (Status, Option<ThumbnailFile>) {
+    let size = match size.map(ThumbnailSize::parse) {
+        Some(Some(x)) => x,
+        Some(None) => return (Status::BadRequest, None),
+        None => ThumbnailSize::Small,
+    };
+    let path = match cache.get(task_id, size).await {
+        Ok(Some(x)) => x,
+        Ok(None) => return (Status::NotFound, None),
+        Err(e) => {
+            warn!("Failed to get thumbnail of {task_id}: {e}");
+            return (Status::BadGateway, None);
+        }
+    };
+    match NamedFile::open(&path).await {
+        Ok(file) => (Status::Ok, Some(ThumbnailFile { file })),
+        Err(e) => {
+            warn!("Failed to open thumbnail {path:?}: {e}");
+            (Status::InternalServerError, None)
+        }
+    }
+}
@@ -0,0 +1,21 @@
+use crate::auth::User;
+use crate::webhooks::{WebhookDelivery, WebhookSystem};
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket::{get, State};
+use std::sync::Arc;
+use tracing::warn;
+
+#[get("/webhooks/deliveries")]
+pub async fn get_webhook_deliveries(
+    webhooks: &State<Arc<WebhookSystem>>,
+    _user: User,
+) -> (Status, Option<Json<Vec<WebhookDelivery>>>) {
+    match webhooks.get_recent_deliveries().await {
+        Ok(deliveries) => (Status::Ok, Some(Json(deliveries))),
+        Err(e) => {
+            warn!("Failed to list webhook deliveries: {e}");
+            (Status::InternalServerError, None)
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use crate::job::{Job, JobEvent};
+use chrono::{DateTime, Utc};
+use rocket::serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, sqlx::Type, PartialEq)]
+pub enum WebhookDeliveryStatus {
+    Pending,
+    Delivered,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct WebhookDelivery {
+    pub delivery_id: Uuid,
+    pub url: String,
+    pub event: JobEvent,
+    pub payload: String,
+    pub status: WebhookDeliveryStatus,
+    pub num_attempts: u32,
+    pub created_at: DateTime<Utc>,
+    pub next_attempt_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct WebhookPayload<'a> {
+    delivery_id: Uuid,
+    event: JobEvent,
+    timestamp: DateTime<Utc>,
+    job: &'a Job,
+}
+
+impl WebhookDelivery {
+    pub fn new(url: &str, event: JobEvent, job: &Job) -> anyhow::Result<Self> {
+        let delivery_id = Uuid::new_v4();
+        let created_at = Utc::now();
+        let payload = serde_json::to_string(&WebhookPayload {
+            delivery_id,
+            event,
+            timestamp: created_at,
+            job,
+        })?;
+        Ok(Self {
+            delivery_id,
+            url: url.to_string(),
+            event,
+            payload,
+            status: WebhookDeliveryStatus::Pending,
+            num_attempts: 0,
+            created_at,
+            next_attempt_at: created_at,
+            delivered_at: None,
+            last_error: None,
+        })
+    }
+}
@@ -0,0 +1,453 @@
+use crate::database::ServerDatabase;
+use crate::env::{EnvironmentManager, WebhookConfig};
+use crate::job::{Job, JobEvent};
+use crate::process::GenericStopHandle;
+use chrono::{DateTime, Utc};
+pub use delivery::{WebhookDelivery, WebhookDeliveryStatus};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+mod delivery;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_RETRY_DELAY: u64 = 3600;
+const NUM_RECENT_DELIVERIES: u32 = 100;
+const PRUNE_INTERVAL: Duration = Duration::from_secs(600);
+const DELIVERY_RETENTION_DAYS: i64 = 7;
+
+#[derive(Debug)]
+pub struct WebhookSystem {
+    env: Arc<EnvironmentManager>,
+    db: Arc<Mutex<ServerDatabase>>,
+    client: reqwest::Client,
+    stop_handle: GenericStopHandle,
+}
+
+impl WebhookSystem {
+    pub fn new(env: Arc<EnvironmentManager>, db: Arc<Mutex<ServerDatabase>>) -> Self {
+        Self {
+            env,
+            db,
+            client: reqwest::Client::new(),
+            stop_handle: GenericStopHandle::new(),
+        }
+    }
+
+    pub fn get_stop_handle(&self) -> GenericStopHandle {
+        self.stop_handle.clone()
+    }
+
+    pub async fn on_job_event(&self, event: JobEvent, job: &Job) -> anyhow::Result<()> {
+        let deliveries = self
+            .env
+            .config
+            .webhooks
+            .iter()
+            .filter(|x| x.accepts(event))
+            .map(|x| WebhookDelivery::new(&x.url, event, job))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        if deliveries.is_empty() {
+            return Ok(());
+        }
+        self.db
+            .lock()
+            .await
+            .add_webhook_deliveries(&deliveries)
+            .await
+    }
+
+    pub async fn get_recent_deliveries(&self) -> anyhow::Result<Vec<WebhookDelivery>> {
+        self.db
+            .lock()
+            .await
+            .get_recent_webhook_deliveries(NUM_RECENT_DELIVERIES)
+            .await
+    }
+
+    // Errors are only logged, deliveries which failed to be
+    // updated will be attempted again on the next round.
+    pub async fn run(&self) {
+        info!("Started webhook delivery.");
+        let mut last_prune = None;
+        while !self.stop_handle.is_stopped() {
+            rocket::tokio::time::sleep(POLL_INTERVAL).await;
+
+            if let Err(e) = self.deliver_due(Utc::now()).await {
+                warn!("Failed to deliver webhooks: {e}");
+            }
+            if last_prune.is_none_or(|x: Instant| x.elapsed() >= PRUNE_INTERVAL) {
+                last_prune = Some(Instant::now());
+                if let Err(e) = self.prune_deliveries(Utc::now()).await {
+                    warn!("Failed to prune webhook deliveries: {e}");
+                }
+            }
+        }
+        info!("Shutdown webhook delivery.");
+    }
+
+    async fn deliver_due(&self, now: DateTime<Utc>) -> anyhow::Result<()> {
+        let due = self.db.lock().await.get_due_webhook_deliveries(now).await?;
+        for mut delivery in due {
+            if self.stop_handle.is_stopped() {
+                break;
+            }
+            self.attempt_delivery(&mut delivery, now).await;
+            if let Err(e) = self
+                .db
+                .lock()
+                .await
+                .update_webhook_delivery(&delivery)
+                .await
+            {
+                warn!("Failed to update webhook {}: {e}", delivery.delivery_id);
+            }
+        }
+        Ok(())
+    }
+
+    async fn prune_deliveries(&self, now: DateTime<Utc>) -> anyhow::Result<()> {
+        let before = now - chrono::Duration::days(DELIVERY_RETENTION_DAYS);
+        let num_pruned = self
+            .db
+            .lock()
+            .await
+            .prune_webhook_deliveries(before)
+            .await?;
+        if num_pruned > 0 {
+            info!("Pruned {num_pruned} old webhook deliveries.");
+        }
+        Ok(())
+    }
+
+    fn find_webhook(&self, url: &str) -> Option<&WebhookConfig> {
+        self.env.config.webhooks.iter().find(|x| x.url == url)
+    }
+
+    async fn attempt_delivery(&self, delivery: &mut WebhookDelivery, now: DateTime<Utc>) {
+        delivery.num_attempts += 1;
+
+        let result = match self.find_webhook(&delivery.url) {
+            Some(webhook) => self.send(webhook, delivery).await,
+            // Removed from config since the event happened
+            None => {
+                delivery.status = WebhookDeliveryStatus::Failed;
+                delivery.last_error = Some("Webhook is no longer configured".to_string());
+                return;
+            }
+        };
+
+        match result {
+            Ok(_) => {
+                delivery.status = WebhookDeliveryStatus::Delivered;
+                delivery.delivered_at = Some(now);
+                delivery.last_error = None;
+            }
+            Err(e) => {
+                warn!(
+                    "Failed to deliver webhook {} to {}: {e}",
+                    delivery.delivery_id, delivery.url
+                );
+                delivery.last_error = Some(e.to_string());
+                if delivery.num_attempts >= self.env.config.webhook_max_attempts {
+                    delivery.status = WebhookDeliveryStatus::Failed;
+                } else {
+                    let delay = self.retry_delay(delivery.num_attempts);
+                    delivery.next_attempt_at = now + chrono::Duration::seconds(delay as i64);
+                }
+            }
+        }
+    }
+
+    // Doubles with each failed attempt, starting at `webhook_retry_delay`.
+    fn retry_delay(&self, num_attempts: u32) -> u64 {
+        let base = self.env.config.webhook_retry_delay as u64;
+        let factor = 1u64 << num_attempts.saturating_sub(1).min(16);
+        (base * factor).min(MAX_RETRY_DELAY)
+    }
+
+    async fn send(
+        &self,
+        webhook: &WebhookConfig,
+        delivery: &WebhookDelivery,
+    ) -> anyhow::Result<()> {
+        let mut request = self
+            .client
+            .post(&delivery.url)
+            .timeout(REQUEST_TIMEOUT)
+            .header("Content-Type", "application/json")
+            .header("X-Webhook-Event", format!("{:?}", delivery.event))
+            .header("X-Webhook-Delivery", delivery.delivery_id.to_string());
+        if let Some(secret) = &webhook.secret {
+            request = request.header(
+                "X-Webhook-Signature",
+                format!(
+                    "sha256={}",
+                    sign_payload(secret.expose(), &delivery.payload)
+                ),
+            );
+        }
+        request
+            .body(delivery.payload.clone())
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+fn sign_payload(secret: &str, payload: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(payload.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env::Secret;
+    use crate::test_util::{new_job_form, new_test_server, TempDir};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    struct Request {
+        headers: Vec<(String, String)>,
+        body: String,
+    }
+
+    impl Request {
+        fn header(&self, name: &str) -> Option<&str> {
+            self.headers
+                .iter()
+                .find(|(x, _)| x.eq_ignore_ascii_case(name))
+                .map(|(_, x)| x.as_str())
+        }
+    }
+
+    // Answers requests with given statuses in turn, then with 200.
+    async fn start_stub(statuses: Vec<u16>) -> (String, Arc<Mutex<Vec<Request>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(vec![]));
+        let received = requests.clone();
+        tokio::spawn(async move {
+            let mut statuses = statuses.into_iter();
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut buf = vec![];
+                let mut chunk = [0u8; 4096];
+                let (head_len, body_len) = loop {
+                    let num_read = stream.read(&mut chunk).await.unwrap();
+                    buf.extend_from_slice(&chunk[..num_read]);
+                    let text = String::from_utf8_lossy(&buf);
+                    if let Some(head_len) = text.find("\r\n\r\n") {
+                        let body_len = text[..head_len]
+                            .lines()
+                            .filter_map(|x| x.split_once(':'))
+                            .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+                            .map_or(0, |(_, x)| x.trim().parse().unwrap());
+                        break (head_len + 4, body_len);
+                    }
+                };
+                while buf.len() < head_len + body_len {
+                    let num_read = stream.read(&mut chunk).await.unwrap();
+                    buf.extend_from_slice(&chunk[..num_read]);
+                }
+                let head = String::from_utf8_lossy(&buf[..head_len]).to_string();
+                let headers = head
+                    .lines()
+                    .skip(1)
+                    .filter_map(|x| x.split_once(':'))
+                    .map(|(name, value)| (name.to_string(), value.trim().to_string()))
+                    .collect();
+                let body = String::from_utf8_lossy(&buf[head_len..]).to_string();
+                received.lock().await.push(Request { headers, body });
+
+                let status = statuses.next().unwrap_or(200);
+                let response = format!(
+                    "HTTP/1.1 {status} X\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        (url, requests)
+    }
+
+    async fn new_job(db: &Mutex<ServerDatabase>) -> Job {
+        let form = new_job_form("https://example.com/watch?v=1");
+        db.lock().await.create_job(&form).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn delivers_signed_payload_and_retries_with_backoff() {
+        let (url, requests) = start_stub(vec![500, 503]).await;
+        let dir = TempDir::new();
+        let (env, db) = new_test_server(&dir, |config| {
+            config.webhooks = vec![WebhookConfig {
+                url: url.clone(),
+                events: vec![JobEvent::JobDone],
+                secret: Some(Secret::from("hunter2")),
+            }];
+            config.webhook_retry_delay = 10;
+            config.webhook_max_attempts = 5;
+        })
+        .await;
+        let webhooks = WebhookSystem::new(env, db.clone());
+        let job = new_job(&db).await;
+
+        // Events the webhook isn't subscribed to are left out
+        webhooks
+            .on_job_event(JobEvent::JobFailed, &job)
+            .await
+            .unwrap();
+        webhooks
+            .on_job_event(JobEvent::JobDone, &job)
+            .await
+            .unwrap();
+
+        let now = Utc::now();
+        webhooks.deliver_due(now).await.unwrap();
+        let deliveries = webhooks.get_recent_deliveries().await.unwrap();
+        assert_eq!(deliveries.len(), 1);
+        let delivery = &deliveries[0];
+        assert_eq!(delivery.status, WebhookDeliveryStatus::Pending);
+        assert_eq!(delivery.num_attempts, 1);
+        assert_eq!(
+            delivery.next_attempt_at,
+            now + chrono::Duration::seconds(10)
+        );
+
+        // Not due yet
+        webhooks.deliver_due(now).await.unwrap();
+        assert_eq!(requests.lock().await.len(), 1);
+
+        // Delay doubles after the second failure
+        let now = now + chrono::Duration::seconds(10);
+        webhooks.deliver_due(now).await.unwrap();
+        let delivery = &webhooks.get_recent_deliveries().await.unwrap()[0];
+        assert_eq!(delivery.num_attempts, 2);
+        assert_eq!(
+            delivery.next_attempt_at,
+            now + chrono::Duration::seconds(20)
+        );
+
+        let now = now + chrono::Duration::seconds(20);
+        webhooks.deliver_due(now).await.unwrap();
+        let delivery = &webhooks.get_recent_deliveries().await.unwrap()[0];
+        assert_eq!(delivery.status, WebhookDeliveryStatus::Delivered);
+        assert_eq!(delivery.num_attempts, 3);
+        assert_eq!(delivery.last_error, None);
+
+        let requests = requests.lock().await;
+        assert_eq!(requests.len(), 3);
+        let request = &requests[2];
+        assert_eq!(request.body, delivery.payload);
+        assert_eq!(request.header("X-Webhook-Event"), Some("JobDone"));
+        assert_eq!(
+            request.header("X-Webhook-Delivery"),
+            Some(delivery.delivery_id.to_string().as_str())
+        );
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"hunter2").unwrap();
+        mac.update(request.body.as_bytes());
+        let signature = format!("sha256={}", hex::encode(mac.finalize().into_bytes()));
+        assert_eq!(
+            request.header("X-Webhook-Signature"),
+            Some(signature.as_str())
+        );
+
+        let payload: serde_json::Value = serde_json::from_str(&request.body).unwrap();
+        assert_eq!(payload["event"], "JobDone");
+        assert_eq!(payload["job"]["job_id"], job.job_id.to_string());
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_attempts() {
+        let (url, requests) = start_stub(vec![500, 500, 500]).await;
+        let dir = TempDir::new();
+        let (env, db) = new_test_server(&dir, |config| {
+            config.webhooks = vec![WebhookConfig {
+                url: url.clone(),
+                events: vec![],
+                secret: None,
+            }];
+            config.webhook_retry_delay = 1;
+            config.webhook_max_attempts = 2;
+        })
+        .await;
+        let webhooks = WebhookSystem::new(env, db.clone());
+        let job = new_job(&db).await;
+        webhooks
+            .on_job_event(JobEvent::FetchFailed, &job)
+            .await
+            .unwrap();
+
+        let now = Utc::now();
+        webhooks.deliver_due(now).await.unwrap();
+        webhooks
+            .deliver_due(now + chrono::Duration::seconds(1))
+            .await
+            .unwrap();
+        webhooks
+            .deliver_due(now + chrono::Duration::seconds(60))
+            .await
+            .unwrap();
+
+        let delivery = &webhooks.get_recent_deliveries().await.unwrap()[0];
+        assert_eq!(delivery.status, WebhookDeliveryStatus::Failed);
+        assert_eq!(delivery.num_attempts, 2);
+        assert!(delivery.last_error.is_some());
+        let requests = requests.lock().await;
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].header("X-Webhook-Signature"), None);
+    }
+
+    #[tokio::test]
+    async fn prunes_only_old_finished_deliveries() {
+        let (url, _) = start_stub(vec![]).await;
+        let dir = TempDir::new();
+        let (env, db) = new_test_server(&dir, |config| {
+            config.webhooks = vec![WebhookConfig {
+                url: url.clone(),
+                events: vec![],
+                secret: None,
+            }];
+        })
+        .await;
+        let webhooks = WebhookSystem::new(env, db.clone());
+        let job = new_job(&db).await;
+        webhooks
+            .on_job_event(JobEvent::JobDone, &job)
+            .await
+            .unwrap();
+        webhooks.deliver_due(Utc::now()).await.unwrap();
+        webhooks
+            .on_job_event(JobEvent::JobDone, &job)
+            .await
+            .unwrap();
+
+        // Both are recent
+        webhooks.prune_deliveries(Utc::now()).await.unwrap();
+        assert_eq!(webhooks.get_recent_deliveries().await.unwrap().len(), 2);
+
+        // The pending one outlives the retention period
+        let later = Utc::now() + chrono::Duration::days(DELIVERY_RETENTION_DAYS + 1);
+        webhooks.prune_deliveries(later).await.unwrap();
+        let deliveries = webhooks.get_recent_deliveries().await.unwrap();
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0].status, WebhookDeliveryStatus::Pending);
+    }
+
+    #[tokio::test]
+    async fn retry_delay_is_capped() {
+        let dir = TempDir::new();
+        let (env, db) = new_test_server(&dir, |config| config.webhook_retry_delay = 1000).await;
+        let webhooks = WebhookSystem::new(env, db);
+        let delays: Vec<u64> = (1..=4).map(|x| webhooks.retry_delay(x)).collect();
+        assert_eq!(delays, [1000, 2000, MAX_RETRY_DELAY, MAX_RETRY_DELAY]);
+    }
+}
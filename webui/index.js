@@ -293,6 +293,36 @@ function delete_job(event) {
     })
 }
 
+// Lists the entries of a job awaiting selection and asks which ones to
+// download, in the "1-3,7,10-" form the server accepts.
+function select_job_entries(event) {
+    const job_id = get_job_id_from_event(event)
+    fetch("api/jobs/entries/" + job_id, { headers: make_auth_headers() })
+        .then(r => {
+            if (r.status !== 200) {
+                throw new Error("status code " + r.status)
+            }
+            return r.json()
+        })
+        .then(entries => {
+            const listing = entries.map(entry => `${entry.entry_index}. ${entry.title}`).join("\n");
+            const selection = window.prompt("Entries to download (e.g. 1-3,7,10-):\n" + listing, "1-");
+            if (selection === null || selection.trim() === "") {
+                return;
+            }
+            return fetch("api/jobs/select/" + job_id, {
+                method: "POST",
+                headers: make_auth_headers(),
+                body: new URLSearchParams({
+                    entries: selection,
+                })
+            })
+        })
+        .then(() => {}).catch((e)=>{
+        console.error(e);
+    })
+}
+
 function cancel_all_jobs() {
     fetch("api/jobs/cancel_all", { method: "POST", headers: make_auth_headers() })
         .then(() => {}).catch((e)=>{
@@ -383,6 +413,25 @@ function get_task_logs(task_id, log_kind) {
         })
 }
 
+function load_thumbnail(img, task_id) {
+    // Same as with logs, image tags can't send our auth headers
+    fetch("api/thumbnails/" + task_id, { headers: make_auth_headers() })
+        .then(resp => {
+            if (resp.status !== 200) {
+                throw new Error("Failed to get thumbnail: " + resp.status);
+            }
+            return resp.blob()
+        })
+        .then(blob => {
+            const _url = window.URL.createObjectURL(blob);
+            img.onload = () => window.URL.revokeObjectURL(_url);
+            img.src = _url;
+        })
+        .catch((e) => {
+            console.error(e);
+        })
+}
+
 const ServerStatusKind = {
     Offline: "offline",
     Unauthorized: "unauthorized",
@@ -453,9 +502,11 @@ function make_job_card(template_card, job) {
     info_url.setAttribute("href", job.url);
     info_url.textContent = job.url;
 
-    if (job.thumbnail !== "") {
+    // Fetch task shows the thumbnail of the whole job
+    const fetch_task = job.tasks.find(task => task.kind === "FetchUrlContents");
+    if (job.thumbnail !== "" && fetch_task !== undefined) {
         const thumbnail = card.getElementsByClassName("card-thumbnail").item(0);
-        thumbnail.src = job.thumbnail;
+        load_thumbnail(thumbnail, fetch_task.task_id);
     }
 
     let status;
@@ -472,6 +523,9 @@ function make_job_card(template_card, job) {
     } else if (job.status === "Processing") {
         status = "status-processing";
         status_text = "Processing"
+    } else if (job.status === "AwaitingSelection") {
+        status = "status-awaiting-selection";
+        status_text = "Awaiting Selection";
     } else { // "Waiting"
         status = "status-waiting";
     }
@@ -540,6 +594,12 @@ function make_job_card(template_card, job) {
     let control_delete = control_panel.getElementsByClassName("control-delete").item(0);
     control_delete.onclick = delete_job;
 
+    let control_select = control_panel.getElementsByClassName("control-select").item(0);
+    if (job.status === "AwaitingSelection") {
+        control_select.classList.remove("hidden");
+        control_select.onclick = select_job_entries;
+    }
+
     return card;
 }
 
@@ -581,7 +641,7 @@ function make_task_card(template_card, task) {
 
     if (task.thumbnail !== "") {
         const thumbnail = card.getElementsByClassName("card-thumbnail").item(0);
-        thumbnail.src = task.thumbnail;
+        load_thumbnail(thumbnail, task.task_id);
     }
 
     let status;